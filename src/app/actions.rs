@@ -0,0 +1,132 @@
+use crate::{
+    app::{log_store::ExportFormat, project_view::ProjectEntry, start_dialog::StartDialog},
+    dfx_commands::{DfxCommands, DfxContext, DfxOutput},
+    metrics::Cycles,
+};
+use std::{
+    fmt,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+/// Lifecycle operations that can be run on the selected canister.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanisterAction {
+    Start,
+    Stop,
+    DepositCycles(Cycles),
+    UninstallCode,
+    Delete,
+}
+
+impl CanisterAction {
+    /// Whether the action needs a confirmation dialog first.
+    pub fn is_destructive(&self) -> bool {
+        matches!(
+            self,
+            CanisterAction::Stop | CanisterAction::UninstallCode | CanisterAction::Delete
+        )
+    }
+
+    fn command(&self, canister_id: &str) -> DfxCommands {
+        let canister_id = canister_id.to_string();
+        match self {
+            CanisterAction::Start => DfxCommands::CanisterStart { canister_id },
+            CanisterAction::Stop => DfxCommands::CanisterStop { canister_id },
+            CanisterAction::DepositCycles(cycles) => DfxCommands::CanisterDepositCycles {
+                canister_id,
+                cycles: cycles.0,
+            },
+            CanisterAction::UninstallCode => DfxCommands::CanisterUninstallCode { canister_id },
+            CanisterAction::Delete => DfxCommands::CanisterDelete { canister_id },
+        }
+    }
+}
+
+impl fmt::Display for CanisterAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CanisterAction::Start => write!(f, "start"),
+            CanisterAction::Stop => write!(f, "stop"),
+            CanisterAction::DepositCycles(cycles) => write!(f, "deposit {cycles} into"),
+            CanisterAction::UninstallCode => write!(f, "uninstall the code of"),
+            CanisterAction::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+/// A modal dialog shown on top of the dashboard.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Dialog {
+    /// Asks before running a destructive action.
+    Confirm {
+        action: CanisterAction,
+        canister_id: String,
+    },
+    /// Asks before `dfx deploy --mode reinstall` wipes canister state.
+    ConfirmReinstall { entry: ProjectEntry },
+    /// Asks how many cycles to deposit, e.g. "1.5T" or "500_000_000".
+    DepositAmount {
+        canister_id: String,
+        input: String,
+        error: Option<String>,
+    },
+    /// Asks for the `dfx start` options, or a saved profile, to start with.
+    StartReplica(StartDialog),
+    /// Asks how to export the current logs tab.
+    ExportLogs {
+        format: ExportFormat,
+        filtered: bool,
+    },
+}
+
+#[derive(Debug)]
+pub enum ActionEvent {
+    Log(String),
+    Finished,
+}
+
+/// Runs lifecycle actions in the background and reports their output.
+#[derive(Debug)]
+pub struct Actions {
+    sender: Sender<ActionEvent>,
+    receiver: Receiver<ActionEvent>,
+}
+
+impl Default for Actions {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Actions { sender, receiver }
+    }
+}
+
+impl Actions {
+    pub fn run(&self, ctx: &DfxContext, action: CanisterAction, canister_id: &str) {
+        let command = action.command(canister_id);
+        let message = format!("[dfx-top] {action} canister {canister_id}...");
+        let (ctx, sender) = (ctx.clone(), self.sender.clone());
+        let canister_id = canister_id.to_string();
+        thread::spawn(move || {
+            let _ = sender.send(ActionEvent::Log(message));
+            match command.run(&ctx) {
+                Ok(DfxOutput::CanisterAction(output)) => {
+                    for line in output.lines().filter(|l| !l.trim().is_empty()) {
+                        let _ = sender.send(ActionEvent::Log(line.to_string()));
+                    }
+                    let _ = sender.send(ActionEvent::Log(format!(
+                        "[dfx-top] {action} canister {canister_id}: done"
+                    )));
+                }
+                Ok(other) => unreachable!("{action} produced {other:?}"),
+                Err(e) => {
+                    let _ = sender.send(ActionEvent::Log(format!("[dfx-top] {e}")));
+                }
+            }
+            let _ = sender.send(ActionEvent::Finished);
+        });
+    }
+
+    pub fn try_recv(&self) -> Option<ActionEvent> {
+        self.receiver.try_recv().ok()
+    }
+}
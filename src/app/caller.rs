@@ -0,0 +1,192 @@
+use crate::dfx_commands::{DfxCommands, DfxContext, DfxError, DfxOutput};
+use chrono::{DateTime, Local};
+use std::{
+    collections::HashMap,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+
+/// How many calls are remembered per canister.
+pub const CALL_HISTORY_LEN: usize = 50;
+
+/// Queries run with `--query`, updates go through consensus.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallKind {
+    Query,
+    Update,
+}
+
+impl std::fmt::Display for CallKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CallKind::Query => write!(f, "query"),
+            CallKind::Update => write!(f, "update"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallRecord {
+    pub id: u64,
+    pub method: String,
+    pub kind: CallKind,
+    /// Candid arguments as typed, e.g. `("world", 42)`.
+    pub args: String,
+    pub started: DateTime<Local>,
+    /// `None` while dfx is still running.
+    pub reply: Option<Result<String, DfxError>>,
+}
+
+/// Where the caller pane's focus is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CallerFocus {
+    #[default]
+    Methods,
+    History,
+}
+
+/// State of the Candid method caller shown in place of the canister info pane.
+#[derive(Debug)]
+pub struct Caller {
+    pub active: bool,
+    pub focus: CallerFocus,
+    pub selected_method_index: usize,
+    pub selected_history_index: usize,
+    /// Argument being typed; `Some` while the input form is open.
+    pub input: Option<String>,
+    /// Most recent call first, keyed by canister id.
+    pub history: HashMap<String, Vec<CallRecord>>,
+    next_id: u64,
+    sender: Sender<(String, u64, Result<String, DfxError>)>,
+    receiver: Receiver<(String, u64, Result<String, DfxError>)>,
+}
+
+impl Default for Caller {
+    fn default() -> Self {
+        let (sender, receiver) = mpsc::channel();
+        Caller {
+            active: false,
+            focus: CallerFocus::default(),
+            selected_method_index: 0,
+            selected_history_index: 0,
+            input: None,
+            history: HashMap::new(),
+            next_id: 0,
+            sender,
+            receiver,
+        }
+    }
+}
+
+impl Caller {
+    /// Runs `dfx canister call` on a background thread and records it as
+    /// pending; the reply is picked up by [`Caller::collect_replies`].
+    pub fn call(
+        &mut self,
+        ctx: &DfxContext,
+        canister_id: &str,
+        method: &str,
+        kind: CallKind,
+        args: &str,
+    ) {
+        self.next_id += 1;
+        let record = CallRecord {
+            id: self.next_id,
+            method: method.to_string(),
+            kind,
+            args: args.trim().to_string(),
+            started: Local::now(),
+            reply: None,
+        };
+        let command = DfxCommands::CanisterCall {
+            canister_id: canister_id.to_string(),
+            method: record.method.clone(),
+            args: record.args.clone(),
+            query: kind == CallKind::Query,
+        };
+        let history = self.history.entry(canister_id.to_string()).or_default();
+        history.insert(0, record);
+        history.truncate(CALL_HISTORY_LEN);
+        self.selected_history_index = 0;
+
+        let (ctx, canister_id, id) = (ctx.clone(), canister_id.to_string(), self.next_id);
+        let sender = self.sender.clone();
+        thread::spawn(move || {
+            let reply = command.run(&ctx).map(|output| match output {
+                DfxOutput::CallReply(reply) => reply,
+                other => unreachable!("CanisterCall produced {other:?}"),
+            });
+            let _ = sender.send((canister_id, id, reply));
+        });
+    }
+
+    /// Runs a previous call of `canister_id` again with the same arguments.
+    pub fn rerun(&mut self, ctx: &DfxContext, canister_id: &str, index: usize) {
+        let Some(record) = self
+            .history
+            .get(canister_id)
+            .and_then(|h| h.get(index))
+            .cloned()
+        else {
+            return;
+        };
+        self.call(ctx, canister_id, &record.method, record.kind, &record.args);
+    }
+
+    /// Fills in the replies of finished calls. Never blocks.
+    pub fn collect_replies(&mut self) {
+        while let Ok((canister_id, id, reply)) = self.receiver.try_recv() {
+            if let Some(record) = self
+                .history
+                .get_mut(&canister_id)
+                .and_then(|h| h.iter_mut().find(|r| r.id == id))
+            {
+                record.reply = Some(reply);
+            }
+        }
+    }
+
+    pub fn history_of(&self, canister_id: &str) -> &[CallRecord] {
+        self.history.get(canister_id).map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_call_reports_failure_and_is_rerunnable() {
+        let ctx = DfxContext {
+            path_to_dfx: "/nonexistent/dfx".to_string(),
+            ..Default::default()
+        };
+        let mut caller = Caller::default();
+        caller.call(
+            &ctx,
+            "bkyz2-fmaaa-aaaaa-qaaaq-cai",
+            "greet",
+            CallKind::Query,
+            "(\"world\")",
+        );
+        caller.rerun(&ctx, "bkyz2-fmaaa-aaaaa-qaaaq-cai", 0);
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let history = loop {
+            caller.collect_replies();
+            let history = caller.history_of("bkyz2-fmaaa-aaaaa-qaaaq-cai");
+            if history.iter().all(|r| r.reply.is_some()) || Instant::now() > deadline {
+                break history;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].args, history[1].args);
+        assert_eq!(history[0].kind, CallKind::Query);
+        assert!(matches!(
+            history[0].reply,
+            Some(Err(DfxError::Spawn { .. }))
+        ));
+    }
+}
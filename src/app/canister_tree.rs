@@ -0,0 +1,361 @@
+use crate::{dfx_project::ProjectDatabase, parse_replica_dashboard::CanisterInfo};
+use std::{collections::HashSet, fmt, path::PathBuf};
+
+/// Canisters the NNS installs on its well-known ids, locally with
+/// `dfx extension run nns install` as on mainnet.
+const NNS_CANISTERS: [(&str, &str); 10] = [
+    ("rwlgt-iiaaa-aaaaa-aaaaa-cai", "NNS registry"),
+    ("rrkah-fqaaa-aaaaa-aaaaq-cai", "NNS governance"),
+    ("ryjl3-tyaaa-aaaaa-aaaba-cai", "ICP ledger"),
+    ("r7inp-6aaaa-aaaaa-aaabq-cai", "NNS root"),
+    ("rkp4c-7iaaa-aaaaa-aaaca-cai", "cycles minting"),
+    ("rno2w-sqaaa-aaaaa-aaacq-cai", "NNS lifeline"),
+    ("renrk-eyaaa-aaaaa-aaada-cai", "genesis token"),
+    ("rdmx6-jaaaa-aaaaa-aaadq-cai", "Internet Identity"),
+    ("qoctq-giaaa-aaaaa-aaaea-cai", "NNS dapp"),
+    ("qaa6y-5yaaa-aaaaa-aaafa-cai", "SNS wasm"),
+];
+
+/// A section of the canister tree, in the order they are shown.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Group {
+    Project(PathBuf),
+    Unregistered,
+    /// Wallets, the Candid UI and the NNS.
+    System,
+}
+
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Group::Project(path) => match path.file_name() {
+                Some(name) => write!(f, "{}", name.to_string_lossy()),
+                None => write!(f, "{}", path.display()),
+            },
+            Group::Unregistered => write!(f, "unknown / unregistered"),
+            Group::System => write!(f, "system"),
+        }
+    }
+}
+
+/// How canisters are ordered within their section.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortBy {
+    #[default]
+    Name,
+    /// Most cycles first.
+    Cycles,
+    /// Most memory first.
+    Memory,
+}
+
+impl SortBy {
+    pub fn next(&self) -> SortBy {
+        match self {
+            SortBy::Name => SortBy::Cycles,
+            SortBy::Cycles => SortBy::Memory,
+            SortBy::Memory => SortBy::Name,
+        }
+    }
+}
+
+impl fmt::Display for SortBy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortBy::Name => write!(f, "name"),
+            SortBy::Cycles => write!(f, "cycles"),
+            SortBy::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+/// Identifies a row across refreshes, which add and remove rows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RowKey {
+    Group(Group),
+    Canister(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TreeRow {
+    Group {
+        group: Group,
+        canisters: usize,
+        collapsed: bool,
+    },
+    Canister {
+        /// Index into the replica's canisters.
+        index: usize,
+        canister_id: String,
+        name: Option<String>,
+        /// Names of the canisters it depends on in its dfx.json.
+        dependencies: Vec<String>,
+        /// Whether it is the last canister of its section.
+        last: bool,
+    },
+}
+
+impl TreeRow {
+    pub fn key(&self) -> RowKey {
+        match self {
+            TreeRow::Group { group, .. } => RowKey::Group(group.clone()),
+            TreeRow::Canister { canister_id, .. } => RowKey::Canister(canister_id.clone()),
+        }
+    }
+}
+
+/// The canisters pane: the replica's canisters grouped by project, with
+/// collapsible sections.
+#[derive(Debug, Default)]
+pub struct CanisterTree {
+    pub collapsed: HashSet<Group>,
+    pub sort_by: SortBy,
+    /// The selected row; `None` selects the first canister.
+    pub selected: Option<RowKey>,
+}
+
+/// The section, name and dependencies of a canister.
+fn classify(
+    canister: &CanisterInfo,
+    db: &ProjectDatabase,
+    network: &str,
+) -> (Group, Option<String>, Vec<String>) {
+    if let Some((path, name)) = db.canister_name(&canister.canister_id, network) {
+        if name == "__Candid_UI" {
+            return (Group::System, Some("Candid UI".to_string()), vec![]);
+        }
+        let dependencies = db.projects[path]
+            .canisters
+            .get(name)
+            .and_then(|info| info.dependencies.clone())
+            .unwrap_or_default();
+        return (
+            Group::Project(path.clone()),
+            Some(name.clone()),
+            dependencies,
+        );
+    }
+    if let Some((_, name)) = NNS_CANISTERS
+        .iter()
+        .find(|(id, _)| *id == canister.canister_id)
+    {
+        return (Group::System, Some(name.to_string()), vec![]);
+    }
+    let exports = &canister.exports;
+    let wallet = "wallet_balance".to_string();
+    if exports.exported_query_functions.contains(&wallet)
+        || exports.exported_update_functions.contains(&wallet)
+    {
+        return (Group::System, Some("cycles wallet".to_string()), vec![]);
+    }
+    (Group::Unregistered, None, vec![])
+}
+
+impl CanisterTree {
+    /// The visible rows: each section's header, followed by its canisters
+    /// unless it is collapsed.
+    pub fn rows(
+        &self,
+        canisters: &[CanisterInfo],
+        db: &ProjectDatabase,
+        network: &str,
+    ) -> Vec<TreeRow> {
+        let mut classified: Vec<_> = canisters
+            .iter()
+            .enumerate()
+            .map(|(index, canister)| {
+                let (group, name, dependencies) = classify(canister, db, network);
+                (group, index, name, dependencies)
+            })
+            .collect();
+        let label = |index: usize, name: &Option<String>| {
+            name.clone()
+                .unwrap_or_else(|| canisters[index].canister_id.clone())
+        };
+        classified.sort_by(|a, b| {
+            let (x, y) = (&canisters[a.1], &canisters[b.1]);
+            let by = match self.sort_by {
+                SortBy::Name => std::cmp::Ordering::Equal,
+                SortBy::Cycles => y.cycles_balance.cmp(&x.cycles_balance),
+                SortBy::Memory => y.memory_usage.cmp(&x.memory_usage),
+            };
+            a.0.cmp(&b.0)
+                .then(by)
+                .then_with(|| label(a.1, &a.2).cmp(&label(b.1, &b.2)))
+        });
+
+        let mut rows = vec![];
+        let mut classified = classified.into_iter().peekable();
+        while let Some((group, index, name, dependencies)) = classified.next() {
+            let mut members = vec![(index, name, dependencies)];
+            while let Some(next) = classified.next_if(|next| next.0 == group) {
+                members.push((next.1, next.2, next.3));
+            }
+            let collapsed = self.collapsed.contains(&group);
+            rows.push(TreeRow::Group {
+                group,
+                canisters: members.len(),
+                collapsed,
+            });
+            if collapsed {
+                continue;
+            }
+            let count = members.len();
+            for (i, (index, name, dependencies)) in members.into_iter().enumerate() {
+                rows.push(TreeRow::Canister {
+                    index,
+                    canister_id: canisters[index].canister_id.clone(),
+                    name,
+                    dependencies,
+                    last: i + 1 == count,
+                });
+            }
+        }
+        rows
+    }
+
+    /// Index of the selected row, the first canister if nothing or a row
+    /// that is gone is selected.
+    pub fn selected_row(&self, rows: &[TreeRow]) -> Option<usize> {
+        self.selected
+            .as_ref()
+            .and_then(|key| rows.iter().position(|row| row.key() == *key))
+            .or_else(|| {
+                rows.iter()
+                    .position(|row| matches!(row, TreeRow::Canister { .. }))
+            })
+            .or(if rows.is_empty() { None } else { Some(0) })
+    }
+
+    /// Index into the replica's canisters of the selected canister, if a
+    /// canister rather than a section is selected.
+    pub fn selected_canister(&self, rows: &[TreeRow]) -> Option<usize> {
+        match rows.get(self.selected_row(rows)?) {
+            Some(TreeRow::Canister { index, .. }) => Some(*index),
+            _ => None,
+        }
+    }
+
+    pub fn up(&mut self, rows: &[TreeRow]) {
+        if let Some(row) = self.selected_row(rows) {
+            self.selected = Some(rows[(row + rows.len() - 1) % rows.len()].key());
+        }
+    }
+
+    pub fn down(&mut self, rows: &[TreeRow]) {
+        if let Some(row) = self.selected_row(rows) {
+            self.selected = Some(rows[(row + 1) % rows.len()].key());
+        }
+    }
+
+    /// Collapses or expands the section of the selected row, and selects
+    /// its header so the selection stays visible.
+    pub fn toggle(&mut self, rows: &[TreeRow]) {
+        let Some(row) = self.selected_row(rows) else {
+            return;
+        };
+        let Some(group) = rows[..=row].iter().rev().find_map(|row| match row {
+            TreeRow::Group { group, .. } => Some(group.clone()),
+            _ => None,
+        }) else {
+            return;
+        };
+        if !self.collapsed.remove(&group) {
+            self.collapsed.insert(group.clone());
+        }
+        self.selected = Some(RowKey::Group(group));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dfx_project::DfxProjectData,
+        metrics::{Bytes, Cycles},
+    };
+
+    fn canister(id: &str, cycles: u128, memory: u64) -> CanisterInfo {
+        CanisterInfo {
+            canister_id: id.to_string(),
+            cycles_balance: Some(Cycles(cycles)),
+            memory_usage: Some(Bytes(memory)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_groups_sorts_and_collapses() {
+        let mut db = ProjectDatabase::default();
+        let project: DfxProjectData = serde_json::from_str(
+            r#"{
+  "canisters": {
+    "backend": { "type": "motoko" },
+    "frontend": { "type": "assets", "dependencies": ["backend"] }
+  },
+  "canister_ids": {
+    "__Candid_UI": { "local": "be2us-64aaa-aaaaa-qaabq-cai" },
+    "backend": { "local": "bkyz2-fmaaa-aaaaa-qaaaq-cai" },
+    "frontend": { "local": "bd3sg-teaaa-aaaaa-qaaba-cai" }
+  }
+}"#,
+        )
+        .unwrap();
+        db.projects.insert(PathBuf::from("/code/hello"), project);
+        let mut wallet = canister("bnz7o-iuaaa-aaaaa-qaaaa-cai", 0, 0);
+        wallet.exports.exported_query_functions = vec!["wallet_balance".to_string()];
+        let canisters = vec![
+            wallet,
+            canister("bkyz2-fmaaa-aaaaa-qaaaq-cai", 10, 500),
+            canister("be2us-64aaa-aaaaa-qaabq-cai", 0, 0),
+            canister("bd3sg-teaaa-aaaaa-qaaba-cai", 20, 100),
+            canister("br5f7-7uaaa-aaaaa-qaaca-cai", 0, 0),
+            canister("ryjl3-tyaaa-aaaaa-aaaba-cai", 0, 0),
+        ];
+        let mut tree = CanisterTree::default();
+        let names = |rows: &[TreeRow]| -> Vec<String> {
+            rows.iter()
+                .map(|row| match row {
+                    TreeRow::Group { group, .. } => format!("[{group}]"),
+                    TreeRow::Canister {
+                        name, canister_id, ..
+                    } => name.clone().unwrap_or(canister_id.clone()),
+                })
+                .collect()
+        };
+        let rows = tree.rows(&canisters, &db, "local");
+        assert_eq!(
+            names(&rows),
+            [
+                "[hello]",
+                "backend",
+                "frontend",
+                "[unknown / unregistered]",
+                "br5f7-7uaaa-aaaaa-qaaca-cai",
+                "[system]",
+                "Candid UI",
+                "ICP ledger",
+                "cycles wallet",
+            ]
+        );
+        assert!(matches!(
+            &rows[2],
+            TreeRow::Canister { dependencies, .. } if dependencies == &["backend"]
+        ));
+        // the first canister is selected until something else is
+        assert_eq!(tree.selected_canister(&rows), Some(1));
+
+        tree.sort_by = SortBy::Cycles;
+        let rows = tree.rows(&canisters, &db, "local");
+        assert_eq!(names(&rows)[1..3], ["frontend", "backend"]);
+
+        tree.down(&rows);
+        tree.toggle(&rows);
+        let rows = tree.rows(&canisters, &db, "local");
+        assert_eq!(names(&rows)[..2], ["[hello]", "[unknown / unregistered]"]);
+        assert_eq!(tree.selected_row(&rows), Some(0));
+        assert_eq!(tree.selected_canister(&rows), None);
+        tree.up(&rows);
+        assert_eq!(tree.selected_canister(&rows), Some(0));
+    }
+}
@@ -0,0 +1,172 @@
+use crate::{
+    metrics::{Bytes, Cycles, Round},
+    parse_replica_dashboard::ReplicaInfo,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// How many samples are kept per canister. At the default 1.5 s replica
+/// refresh this is about five minutes.
+pub const HISTORY_LEN: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    pub at: Instant,
+    pub cycles: Option<Cycles>,
+    pub memory: Option<Bytes>,
+    pub last_execution_round: Option<Round>,
+}
+
+/// Ring buffer of the most recent [`Sample`]s of one canister.
+#[derive(Debug, Clone, Default)]
+pub struct CanisterHistory {
+    samples: VecDeque<Sample>,
+}
+
+impl CanisterHistory {
+    pub fn push(&mut self, sample: Sample) {
+        if self.samples.len() == HISTORY_LEN {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub fn samples(&self) -> impl Iterator<Item = &Sample> {
+        self.samples.iter()
+    }
+
+    /// Values of one metric relative to its minimum over the window, so small
+    /// changes on top of a large balance are still visible in a sparkline.
+    pub fn sparkline<F: Fn(&Sample) -> Option<u128>>(&self, metric: F) -> Vec<u64> {
+        let values: Vec<u128> = self.samples.iter().filter_map(metric).collect();
+        let min = values.iter().copied().min().unwrap_or_default();
+        values
+            .iter()
+            .map(|v| u64::try_from(v - min).unwrap_or(u64::MAX))
+            .collect()
+    }
+
+    /// Average change per second of one metric between the oldest and newest
+    /// sample that have it. Positive means growing.
+    pub fn rate<F: Fn(&Sample) -> Option<u128>>(&self, metric: F) -> Option<f64> {
+        let first = self.samples.iter().find_map(|s| Some((s.at, metric(s)?)))?;
+        let last = self
+            .samples
+            .iter()
+            .rev()
+            .find_map(|s| Some((s.at, metric(s)?)))?;
+        let elapsed = last.0.duration_since(first.0).as_secs_f64();
+        if elapsed < 1.0 {
+            return None;
+        }
+        Some((last.1 as f64 - first.1 as f64) / elapsed)
+    }
+
+    /// Cycles burnt per second, if the balance went down over the window.
+    pub fn burn_rate(&self) -> Option<f64> {
+        self.rate(|s| s.cycles.map(|c| c.0))
+            .map(|rate| -rate)
+            .filter(|rate| *rate > 0.0)
+    }
+
+    /// Rough time until the canister freezes at the current burn rate. The
+    /// freezing threshold is the balance needed to keep the canister alive
+    /// for `freeze_threshold` at its burn rate, so the canister freezes once
+    /// `balance / burn_rate` drops to `freeze_threshold`.
+    pub fn time_until_frozen(&self, freeze_threshold: Duration) -> Option<Duration> {
+        let burn_rate = self.burn_rate()?;
+        let balance = self.samples.iter().rev().find_map(|s| s.cycles)?.0 as f64;
+        let runway = balance / burn_rate - freeze_threshold.as_secs_f64();
+        Some(Duration::from_secs_f64(runway.max(0.0)))
+    }
+}
+
+/// Per-canister history of the values reported by the replica.
+#[derive(Debug, Default)]
+pub struct History {
+    pub canisters: HashMap<String, CanisterHistory>,
+}
+
+impl History {
+    /// Appends one sample for every canister in `info`.
+    pub fn record(&mut self, info: &ReplicaInfo, at: Instant) {
+        for canister in &info.canisters {
+            self.canisters
+                .entry(canister.canister_id.clone())
+                .or_default()
+                .push(Sample {
+                    at,
+                    cycles: canister.cycles_balance,
+                    memory: canister.memory_usage,
+                    last_execution_round: canister.last_execution_round,
+                });
+        }
+    }
+
+    pub fn get(&self, canister_id: &str) -> Option<&CanisterHistory> {
+        self.canisters.get(canister_id)
+    }
+
+    pub fn clear(&mut self) {
+        self.canisters.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(at: Instant, secs: u64, cycles: u128) -> Sample {
+        Sample {
+            at: at + Duration::from_secs(secs),
+            cycles: Some(Cycles(cycles)),
+            memory: Some(Bytes(1024 + secs)),
+            last_execution_round: None,
+        }
+    }
+
+    #[test]
+    fn test_ring_buffer_keeps_latest() {
+        let start = Instant::now();
+        let mut history = CanisterHistory::default();
+        for i in 0..HISTORY_LEN as u64 + 10 {
+            history.push(sample(start, i, 1_000));
+        }
+        assert_eq!(history.samples().count(), HISTORY_LEN);
+        assert_eq!(
+            history.samples().next().unwrap().at,
+            start + Duration::from_secs(10)
+        );
+    }
+
+    #[test]
+    fn test_burn_rate_and_runway() {
+        let start = Instant::now();
+        let mut history = CanisterHistory::default();
+        history.push(sample(start, 0, 1_000_000));
+        history.push(sample(start, 10, 999_000));
+        assert_eq!(history.burn_rate(), Some(100.0));
+        // 999_000 / 100 = 9990 s of cycles, minus a 990 s freezing threshold
+        assert_eq!(
+            history.time_until_frozen(Duration::from_secs(990)),
+            Some(Duration::from_secs(9000))
+        );
+        assert_eq!(
+            history.rate(|s| s.memory.map(|m| u128::from(m.0))),
+            Some(1.0)
+        );
+        assert_eq!(history.sparkline(|s| s.cycles.map(|c| c.0)), vec![1_000, 0]);
+    }
+
+    #[test]
+    fn test_no_burn_when_topped_up() {
+        let start = Instant::now();
+        let mut history = CanisterHistory::default();
+        history.push(sample(start, 0, 1_000));
+        history.push(sample(start, 10, 2_000));
+        assert_eq!(history.burn_rate(), None);
+        assert_eq!(history.time_until_frozen(Duration::from_secs(1)), None);
+    }
+}
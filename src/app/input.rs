@@ -1,185 +1,253 @@
 use super::state::{LocalReplicaState, LogsPane};
-use crate::{app::state::AppState, dfx_commands::DfxCommands};
-use crossterm::event::{KeyCode, KeyEvent};
+use crate::{
+    app::{
+        actions::{CanisterAction, Dialog},
+        caller::CallerFocus,
+        keymap::{Action, Context},
+        log_files::FilePicker,
+        log_store::ExportFormat,
+        log_viewer::{plain_text, LogPrompt},
+        poller::Source,
+        project_view::{ProjectEntry, ProjectTask, ProjectView},
+        start_dialog::StartDialog,
+        state::AppState,
+    },
+    dfx_commands::{DfxContext, InstallMode},
+    logs::{LogLevel, LogLine},
+    metrics::Cycles,
+};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use std::time::Duration;
 
 impl AppState {
     #[allow(unused_assignments)]
-    pub fn handle_input(&mut self, key_event: KeyEvent, path_to_dfx: &str) {
-        match key_event.code {
-            KeyCode::Char('+') => {
-                if self.refresh_interval < Duration::from_secs(10) {
-                    self.refresh_interval = self.refresh_interval + Duration::from_millis(100)
+    pub fn handle_input(&mut self, key_event: KeyEvent) {
+        // any key closes the help
+        if self.help.take().is_some() {
+            return;
+        }
+        if self.dialog.is_some() {
+            self.handle_dialog_input(key_event);
+            return;
+        }
+        if self.caller.input.is_some() {
+            self.handle_call_args_input(key_event);
+            return;
+        }
+        if self.log_viewer.prompt.is_some() {
+            self.handle_log_prompt_input(key_event);
+            return;
+        }
+        if self.file_picker.is_some() {
+            self.handle_file_picker_input(key_event);
+            return;
+        }
+        let context = self.context();
+        let Some(action) = self.keymap.action(context, &key_event) else {
+            // the first nine start profiles have fixed keys
+            if let KeyCode::Char(c @ '1'..='9') = key_event.code {
+                if self.replica.state == LocalReplicaState::NotRunning {
+                    self.start_profile(c as usize - '1' as usize);
                 }
             }
-            KeyCode::Char('-') => {
-                if self.refresh_interval > Duration::from_millis(1000) {
-                    self.refresh_interval = self.refresh_interval - Duration::from_millis(100)
-                }
+            return;
+        };
+        match action {
+            Action::Help => self.help = Some(context),
+            Action::RefreshSlower if self.refresh_interval < Duration::from_secs(10) => {
+                self.refresh_interval += Duration::from_millis(100)
+            }
+            Action::RefreshFaster if self.refresh_interval > Duration::from_millis(1000) => {
+                self.refresh_interval -= Duration::from_millis(100)
             }
-            KeyCode::Char('i') => {
+            Action::SelectIdentity => {
                 self.network_selection_menu_active = false;
                 self.identity_selection_menu_active = true;
+                self.request_refresh(Source::Identities);
             }
-            KeyCode::Char('n') => {
+            Action::SelectNetwork => {
                 self.identity_selection_menu_active = false;
                 self.network_selection_menu_active = true;
             }
-            KeyCode::Esc => {
+            Action::ToggleProjects => {
+                self.project_view.active = !self.project_view.active;
+                self.caller.active = false;
+            }
+            Action::Build if self.project_view.active => {
+                self.request_project_task(ProjectTask::Build)
+            }
+            Action::Deploy if self.project_view.active => {
+                self.request_project_task(ProjectTask::Deploy)
+            }
+            Action::Generate if self.project_view.active => {
+                self.request_project_task(ProjectTask::Generate)
+            }
+            Action::InstallMode if self.project_view.active => self.project_view.next_mode(),
+            Action::RegisterProject if self.project_view.active => self.register_discovered(),
+            Action::ToggleCaller
+                if !self.network_selection_menu_active && !self.identity_selection_menu_active =>
+            {
+                self.project_view.active = false;
+                self.caller.active = !self.caller.active;
+                self.caller.focus = CallerFocus::Methods;
+                self.caller.selected_method_index = 0;
+            }
+            Action::StartCanister => self.request_action(CanisterAction::Start),
+            Action::StopCanister => self.request_action(CanisterAction::Stop),
+            Action::UninstallCode => self.request_action(CanisterAction::UninstallCode),
+            Action::DeleteCanister => self.request_action(CanisterAction::Delete),
+            Action::DepositCycles => {
+                if let Some(canister) = self.selected_canister() {
+                    self.dialog = Some(Dialog::DepositAmount {
+                        canister_id: canister.canister_id.clone(),
+                        input: String::new(),
+                        error: None,
+                    });
+                }
+            }
+            Action::SwitchFocus if self.caller.active => {
+                self.caller.focus = match self.caller.focus {
+                    CallerFocus::Methods => CallerFocus::History,
+                    CallerFocus::History => CallerFocus::Methods,
+                };
+            }
+            Action::Select
+                if self.caller.active
+                    && !self.network_selection_menu_active
+                    && !self.identity_selection_menu_active =>
+            {
+                match self.caller.focus {
+                    CallerFocus::Methods if !self.callable_methods().is_empty() => {
+                        self.caller.input = Some(String::new());
+                    }
+                    CallerFocus::Methods => {}
+                    CallerFocus::History => {
+                        if let Some(canister_id) =
+                            self.selected_canister().map(|c| c.canister_id.clone())
+                        {
+                            let index = self.caller.selected_history_index;
+                            self.caller.rerun(&self.dfx_context(), &canister_id, index);
+                        }
+                    }
+                }
+            }
+            Action::Select if context == Context::Canisters => {
+                let rows = self.canister_rows();
+                self.canister_tree.toggle(&rows);
+            }
+            Action::SortCanisters => {
+                self.canister_tree.sort_by = self.canister_tree.sort_by.next();
+            }
+            Action::Close => {
+                self.caller.active = false;
+                self.project_view.active = false;
                 self.network_selection_menu_active = false;
                 self.identity_selection_menu_active = false;
-                // self.logfile_selection_menu_active = None;
             }
-            KeyCode::Enter => {
+            Action::Select => {
                 if self.network_selection_menu_active {
                     self.network_selection_menu_active = false;
-                    self.selected_network = self.networks[self.selected_network_index].clone();
+                    let network = &self.networks[self.selected_network_index];
+                    self.selected_network = network.name.clone();
+                    self.selected_network_project = network.project_dir();
+                    self.clear_selection_data();
                 }
-                if self.identity_selection_menu_active {
+                // the identities are listed in the background and may not be there yet
+                if let Some(identity) = self
+                    .identities
+                    .get(self.selected_identity_index)
+                    .filter(|_| self.identity_selection_menu_active)
+                {
                     self.identity_selection_menu_active = false;
-                    self.selected_identity = self.identities[self.selected_identity_index].clone();
-                }
-            }
-            KeyCode::Char('s') => match self.replica.state {
-                LocalReplicaState::Running => {
-                    DfxCommands::StopReplica.run(&self, path_to_dfx);
-                    {
-                        let mut rx = self.replica_logs_reciver.take(); // Optionally consume any remaining logs
-                        if let Some(rx) = rx {
-                            while let Ok(log) = rx.try_recv() {
-                                self.collected_logs.push(log);
-                            }
-                        }
-                        rx = None;
-                        self.replica_logs_reciver = None;
-                    }
-                    self.replica.state = LocalReplicaState::NotRunning;
-                    self.collected_logs = vec![];
+                    self.selected_identity = identity.clone();
+                    self.clear_selection_data();
                 }
-                LocalReplicaState::NotRunning => {
-                    self.replica_logs_reciver =
-                        Some(DfxCommands::start_replica_stream(self.path_to_dfx.clone()));
-                    self.replica.state = LocalReplicaState::Running;
-                }
-            },
-            // KeyCode::Char('f') => {
-            //     if self.logs_source == LogsSource::Replica {
-            //         // self.collected_logs = vec![];
-            //         self.logs_source = LogsSource::File(None);
-            //         self.logfile_selection_menu_active = Some(dirs::home_dir().unwrap_or_default());
-            //         // self.replica_logs_reciver = None;
-            //     } else {
-            //         // self.collected_logs = vec![];
-            //         self.logs_source = LogsSource::Replica;
-            //         // self.replica_logs_reciver =
-            //         //     Some(DfxCommands::start_replica_stream(self.path_to_dfx.clone()));
-            //     }
-            // }
-            // KeyCode::Left if self.logfile_selection_menu_active.is_some() => {
-            //     // got to parent dir
-            //     let mut path = self
-            //         .logfile_selection_menu_active
-            //         .take()
-            //         .unwrap_or_default();
-            //     path.pop();
-            //     self.logfile_selection_menu_active = Some(path);
-            // }
-            // KeyCode::Right if self.logfile_selection_menu_active.is_some() => {
-            //     // got to child dir
-            //     let mut path = self
-            //         .logfile_selection_menu_active
-            //         .take()
-            //         .unwrap_or_default();
-            //     let entries = std::fs::read_dir(path.clone());
-            //     if let Ok(entries) = entries {
-            //         let entries = entries.map(|e| e.unwrap().path()).collect::<Vec<_>>();
-            //         let entries_len = entries.len();
-            //         if entries_len > 0 {
-            //             self.selected_fs_index = (self.selected_fs_index + 1) % entries_len;
-            //             path = entries[self.selected_fs_index].clone();
-            //         }
-            //         self.logfile_selection_menu_active = Some(path);
-            //     }
-            // }
-            KeyCode::Left => match self.logs_pane {
-                LogsPane::CanisterLogs => {
-                    self.logs_pane = LogsPane::ReplicaLogs;
-                }
-                LogsPane::ReplicaLogs => {
-                    self.logs_pane = LogsPane::CanisterLogs;
-                }
-            },
-            KeyCode::Right => match self.logs_pane {
-                LogsPane::CanisterLogs => {
-                    self.logs_pane = LogsPane::ReplicaLogs;
-                }
-                LogsPane::ReplicaLogs => {
-                    self.logs_pane = LogsPane::CanisterLogs;
+            }
+            // `replica.state` also reads Running for remote networks, so key
+            // off the local replica process instead
+            Action::StartStopReplica => {
+                let running_locally = self.selected_network == "local"
+                    && self.replica.state == LocalReplicaState::Running;
+                if self.replica_process.is_some() || self.attach_replica() || running_locally {
+                    self.stop_replica();
+                } else {
+                    self.open_start_dialog();
                 }
-            },
-            // KeyCode::Down if self.logfile_selection_menu_active.is_some() => {
-            //     let mut path = self
-            //         .logfile_selection_menu_active
-            //         .take()
-            //         .unwrap_or_default();
-            //     let entries = std::fs::read_dir(path.clone());
-            //     if let Ok(entries) = entries {
-            //         let entries = entries.map(|e| e.unwrap().path()).collect::<Vec<_>>();
-            //         let entries_len = entries.len();
-            //         if entries_len > 0 {
-            //             self.selected_fs_index = (self.selected_fs_index + 1) % entries_len;
-            //             path = entries[self.selected_fs_index].clone();
-            //         }
-            //         self.logfile_selection_menu_active = Some(path);
-            //     }
-            // }
-            // KeyCode::Up if self.logfile_selection_menu_active.is_some() => {
-            //     let mut path = self
-            //         .logfile_selection_menu_active
-            //         .take()
-            //         .unwrap_or_default();
-            //     let entries = std::fs::read_dir(path.clone());
-            //     if let Ok(entries) = entries {
-            //         let entries = entries.map(|e| e.unwrap().path()).collect::<Vec<_>>();
-            //         let entries_len = entries.len();
-            //         if entries_len > 0 {
-            //             self.selected_fs_index = if self.selected_fs_index > 0 {
-            //                 self.selected_fs_index - 1
-            //             } else {
-            //                 entries_len - 1
-            //             };
-            //             path = entries[self.selected_fs_index].clone();
-            //         }
-            //         self.logfile_selection_menu_active = Some(path);
-            //     }
-            // }
-            KeyCode::Down if self.network_selection_menu_active => {
+            }
+            Action::RestartReplica if self.replica_process.is_some() => self.restart_replica(),
+            Action::ToggleAutoRestart => self.toggle_auto_restart(),
+            Action::PreviousLogs => {
+                self.logs_pane = self.logs_pane.previous(self.file_sources.len());
+                self.log_viewer.follow();
+            }
+            Action::NextLogs => {
+                self.logs_pane = self.logs_pane.next(self.file_sources.len());
+                self.log_viewer.follow();
+            }
+            Action::SearchLogs => {
+                let search = self.log_viewer.search.clone();
+                self.log_viewer.prompt = Some((LogPrompt::Search, search));
+            }
+            Action::FilterLogs => {
+                let filter = self.log_viewer.filter.as_ref().map(|f| f.to_string());
+                self.log_viewer.prompt = Some((LogPrompt::Filter, filter.unwrap_or_default()));
+            }
+            Action::LogLevel => self.log_viewer.next_level(),
+            Action::FollowFile => {
+                let dir = std::env::current_dir()
+                    .unwrap_or_else(|_| dirs::home_dir().unwrap_or_default());
+                self.file_picker = Some(FilePicker::open(dir));
+            }
+            Action::CloseFile => self.unfollow_file(),
+            Action::ExportLogs => {
+                self.dialog = Some(Dialog::ExportLogs {
+                    format: ExportFormat::default(),
+                    filtered: false,
+                })
+            }
+            Action::PageUp => {
+                let page = self.log_viewer.viewport.get().1;
+                self.log_viewer.scroll_up(page)
+            }
+            Action::PageDown => {
+                let page = self.log_viewer.viewport.get().1;
+                self.log_viewer.scroll_down(page)
+            }
+            Action::LogsTop => self.log_viewer.scroll_to_top(),
+            Action::LogsTail => self.log_viewer.follow(),
+            Action::Down if self.network_selection_menu_active => {
                 let networks_len = self.networks.len();
                 if networks_len > 0 {
                     self.selected_network_index = (self.selected_network_index + 1) % networks_len;
                 }
             }
-            KeyCode::Down if self.identity_selection_menu_active => {
+            Action::Down if self.identity_selection_menu_active => {
                 let identities_len = self.identities.len();
                 if identities_len > 0 {
                     self.selected_identity_index =
                         (self.selected_identity_index + 1) % identities_len;
                 }
             }
-            KeyCode::Down
+            Action::Down if self.project_view.active => {
+                let len = ProjectView::entries(&self.db, &self.discovered).len();
+                if len > 0 {
+                    self.project_view.selected_index = (self.project_view.selected_index + 1) % len;
+                }
+            }
+            Action::Down if self.caller.active => {
+                let len = self.caller_list_len();
+                let index = self.caller_list_index();
+                if len > 0 {
+                    *index = (*index + 1) % len;
+                }
+            }
+            Action::Down
                 if !self.network_selection_menu_active && !self.identity_selection_menu_active =>
             {
-                let canisters_len = self
-                    .replica
-                    .info
-                    .as_ref()
-                    .map_or(0, |info| info.canisters.len());
-                if canisters_len > 0 {
-                    self.selected_canister_index =
-                        (self.selected_canister_index + 1) % canisters_len;
-                }
+                let rows = self.canister_rows();
+                self.canister_tree.down(&rows);
             }
-            KeyCode::Up if self.network_selection_menu_active => {
+            Action::Up if self.network_selection_menu_active => {
                 let networks_len = self.networks.len();
                 if networks_len > 0 {
                     self.selected_network_index = if self.selected_network_index > 0 {
@@ -189,7 +257,7 @@ impl AppState {
                     };
                 }
             }
-            KeyCode::Up if self.identity_selection_menu_active => {
+            Action::Up if self.identity_selection_menu_active => {
                 let identities_len = self.identities.len();
                 if identities_len > 0 {
                     self.selected_identity_index = if self.selected_identity_index > 0 {
@@ -199,23 +267,342 @@ impl AppState {
                     };
                 }
             }
-            KeyCode::Up
+            Action::Up if self.project_view.active => {
+                let len = ProjectView::entries(&self.db, &self.discovered).len();
+                let index = &mut self.project_view.selected_index;
+                if len > 0 {
+                    *index = if *index > 0 { *index - 1 } else { len - 1 };
+                }
+            }
+            Action::Up if self.caller.active => {
+                let len = self.caller_list_len();
+                let index = self.caller_list_index();
+                if len > 0 {
+                    *index = if *index > 0 { *index - 1 } else { len - 1 };
+                }
+            }
+            Action::Up
                 if !self.network_selection_menu_active && !self.identity_selection_menu_active =>
             {
-                let canisters_len = self
-                    .replica
-                    .info
-                    .as_ref()
-                    .map_or(0, |info| info.canisters.len());
-                if canisters_len > 0 {
-                    self.selected_canister_index = if self.selected_canister_index > 0 {
-                        self.selected_canister_index - 1
-                    } else {
-                        canisters_len - 1
-                    };
+                let rows = self.canister_rows();
+                self.canister_tree.up(&rows);
+            }
+            _ => {}
+        }
+    }
+
+    /// Runs `action` on the selected canister, asking first if it is destructive.
+    fn request_action(&mut self, action: CanisterAction) {
+        let Some(canister_id) = self.selected_canister().map(|c| c.canister_id.clone()) else {
+            return;
+        };
+        if action.is_destructive() {
+            self.dialog = Some(Dialog::Confirm {
+                action,
+                canister_id,
+            });
+        } else {
+            self.actions.run(&self.dfx_context(), action, &canister_id);
+        }
+    }
+
+    /// Runs `task` on the selected project entry, asking first before a
+    /// reinstall wipes canister state.
+    fn request_project_task(&mut self, task: ProjectTask) {
+        let Some(entry) = ProjectView::entries(&self.db, &self.discovered)
+            .get(self.project_view.selected_index)
+            .cloned()
+        else {
+            return;
+        };
+        if task == ProjectTask::Deploy && self.project_view.mode == Some(InstallMode::Reinstall) {
+            self.dialog = Some(Dialog::ConfirmReinstall { entry });
+        } else {
+            self.run_project_task(task, &entry);
+        }
+    }
+
+    fn run_project_task(&mut self, task: ProjectTask, entry: &ProjectEntry) {
+        if self.project_view.is_running() {
+            self.project_view.logs.push(LogLine::dfx_top(
+                LogLevel::Warn,
+                format!("[dfx-top] a task is already running, not starting {task}"),
+            ));
+            return;
+        }
+        let ctx = DfxContext {
+            cwd: Some(entry.project.clone()),
+            ..self.dfx_context()
+        };
+        let mode = self
+            .project_view
+            .mode
+            .filter(|_| task == ProjectTask::Deploy);
+        self.project_view.task = Some(task.command(entry, mode).stream(&ctx));
+        self.logs_pane = LogsPane::Project;
+    }
+
+    fn handle_dialog_input(&mut self, key_event: KeyEvent) {
+        match (self.dialog.take(), key_event.code) {
+            (_, KeyCode::Esc) => {}
+            (
+                Some(Dialog::Confirm { .. } | Dialog::ConfirmReinstall { .. }),
+                KeyCode::Char('n'),
+            ) => {}
+            (Some(Dialog::ConfirmReinstall { entry }), KeyCode::Char('y') | KeyCode::Enter) => {
+                self.run_project_task(ProjectTask::Deploy, &entry)
+            }
+            (
+                Some(Dialog::Confirm {
+                    action,
+                    canister_id,
+                }),
+                KeyCode::Char('y') | KeyCode::Enter,
+            ) => self.actions.run(&self.dfx_context(), action, &canister_id),
+            (
+                Some(Dialog::DepositAmount {
+                    canister_id, input, ..
+                }),
+                KeyCode::Enter,
+            ) => match input.parse::<Cycles>() {
+                Ok(cycles) if cycles.0 > 0 => self.actions.run(
+                    &self.dfx_context(),
+                    CanisterAction::DepositCycles(cycles),
+                    &canister_id,
+                ),
+                _ => {
+                    self.dialog = Some(Dialog::DepositAmount {
+                        canister_id,
+                        error: Some(format!("'{input}' is not a positive amount of cycles")),
+                        input,
+                    })
+                }
+            },
+            (
+                Some(Dialog::DepositAmount {
+                    canister_id,
+                    mut input,
+                    error,
+                }),
+                code,
+            ) => {
+                match code {
+                    KeyCode::Char(c) => input.push(c),
+                    KeyCode::Backspace => {
+                        input.pop();
+                    }
+                    _ => {}
+                }
+                self.dialog = Some(Dialog::DepositAmount {
+                    canister_id,
+                    input,
+                    error,
+                });
+            }
+            (Some(Dialog::StartReplica(dialog)), code) => {
+                self.handle_start_dialog_input(dialog, code, key_event.modifiers)
+            }
+            (Some(Dialog::ExportLogs { format, filtered }), KeyCode::Enter) => {
+                self.export_logs(format, filtered)
+            }
+            (Some(Dialog::ExportLogs { format, filtered }), KeyCode::Tab) => {
+                self.dialog = Some(Dialog::ExportLogs {
+                    format: format.toggle(),
+                    filtered,
+                })
+            }
+            (Some(Dialog::ExportLogs { format, filtered }), KeyCode::Char('f')) => {
+                self.dialog = Some(Dialog::ExportLogs {
+                    format,
+                    filtered: !filtered,
+                })
+            }
+            // keep asking until the user answers
+            (dialog, _) => self.dialog = dialog,
+        }
+    }
+
+    /// Up/Down pick a row, Space toggles a flag, Left/Right pick a profile or
+    /// project, Ctrl+S saves the options as the named profile and Ctrl+D
+    /// deletes it.
+    fn handle_start_dialog_input(
+        &mut self,
+        mut dialog: StartDialog,
+        code: KeyCode,
+        modifiers: KeyModifiers,
+    ) {
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+        match code {
+            KeyCode::Enter => match dialog.options() {
+                Ok(options) => return self.start_replica(options),
+                Err(e) => dialog.error = Some(e),
+            },
+            KeyCode::Char('s') if ctrl => {
+                let name = dialog.name.trim().to_string();
+                let saved = match dialog.options() {
+                    _ if name.is_empty() => Err("name the profile first".to_string()),
+                    Ok(options) => self
+                        .config
+                        .save_start_profile(&name, options)
+                        .map_err(|e| format!("{e:#}")),
+                    Err(e) => Err(e),
+                };
+                dialog.error = saved.err();
+            }
+            KeyCode::Char('d') if ctrl => {
+                let name = dialog.name.trim().to_string();
+                dialog.error = self
+                    .config
+                    .remove_start_profile(&name)
+                    .map_err(|e| format!("{e:#}"))
+                    .err();
+            }
+            KeyCode::Up | KeyCode::BackTab => dialog.up(),
+            KeyCode::Down | KeyCode::Tab => dialog.down(),
+            KeyCode::Left | KeyCode::Right => {
+                dialog.cycle(code == KeyCode::Right, &self.config.start_profiles)
+            }
+            KeyCode::Char(' ') if !dialog.is_text_field() => dialog.toggle(),
+            KeyCode::Char(c) => dialog.push(c),
+            KeyCode::Backspace => dialog.pop(),
+            _ => {}
+        }
+        self.dialog = Some(Dialog::StartReplica(dialog));
+    }
+
+    /// Edits the Candid arguments of the method call being prepared.
+    fn handle_call_args_input(&mut self, key_event: KeyEvent) {
+        let Some(input) = self.caller.input.as_mut() else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => self.caller.input = None,
+            KeyCode::Enter => {
+                let args = self.caller.input.take().unwrap_or_default();
+                let method = self
+                    .callable_methods()
+                    .get(self.caller.selected_method_index)
+                    .cloned();
+                let canister_id = self.selected_canister().map(|c| c.canister_id.clone());
+                if let (Some((method, kind)), Some(canister_id)) = (method, canister_id) {
+                    self.caller
+                        .call(&self.dfx_context(), &canister_id, &method, kind, &args);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_file_picker_input(&mut self, key_event: KeyEvent) {
+        let Some(picker) = self.file_picker.as_mut() else {
+            return;
+        };
+        match self.keymap.action(Context::FilePicker, &key_event) {
+            Some(Action::Up) => picker.up(),
+            Some(Action::Down) => picker.down(),
+            Some(Action::PickerParent) => picker.parent(),
+            Some(Action::PickerOpen) => {
+                if let Some(path) = picker.enter() {
+                    self.file_picker = None;
+                    self.follow_file(None, path);
                 }
             }
+            Some(Action::Close) => self.file_picker = None,
+            Some(Action::Help) => self.help = Some(Context::FilePicker),
             _ => {}
         }
     }
+
+    /// Search is incremental: every key re-highlights and jumps to the latest
+    /// match, Up/Down move between matches. Filters apply on Enter.
+    fn handle_log_prompt_input(&mut self, key_event: KeyEvent) {
+        let Some((prompt, input)) = self.log_viewer.prompt.as_mut() else {
+            return;
+        };
+        let prompt = *prompt;
+        match key_event.code {
+            KeyCode::Char(c) => input.push(c),
+            KeyCode::Backspace => {
+                input.pop();
+            }
+            KeyCode::Esc => {
+                self.log_viewer.prompt = None;
+                self.log_viewer.filter_error = None;
+                if prompt == LogPrompt::Search {
+                    self.log_viewer.search.clear();
+                }
+                return;
+            }
+            KeyCode::Up | KeyCode::Down if prompt == LogPrompt::Search => {
+                self.jump_to_search_match(key_event.code == KeyCode::Up);
+                return;
+            }
+            KeyCode::Enter => {
+                let input = self.log_viewer.prompt.take().map(|(_, input)| input);
+                if let (LogPrompt::Filter, Some(input)) = (prompt, input) {
+                    if let Err(e) = self.log_viewer.set_filter(&input) {
+                        self.log_viewer.filter_error = Some(e.to_string());
+                        self.log_viewer.prompt = Some((prompt, input));
+                        return;
+                    }
+                    self.log_viewer.filter_error = None;
+                }
+                return;
+            }
+            _ => return,
+        }
+        if prompt == LogPrompt::Search {
+            self.log_viewer.search = input.clone();
+            self.log_viewer.follow();
+            self.jump_to_search_match(true);
+        }
+    }
+
+    fn jump_to_search_match(&mut self, backwards: bool) {
+        let texts: Vec<String> = self
+            .shown_logs()
+            .iter()
+            .map(|line| plain_text(line, self.logs_pane == LogsPane::Canister))
+            .collect();
+        let texts: Vec<&str> = texts.iter().map(String::as_str).collect();
+        self.log_viewer
+            .viewport
+            .set((texts.len(), self.log_viewer.viewport.get().1));
+        self.log_viewer.jump_to_match(&texts, backwards);
+    }
+
+    /// Length of the list the caller pane's focus is on.
+    fn caller_list_len(&self) -> usize {
+        match self.caller.focus {
+            CallerFocus::Methods => self.callable_methods().len(),
+            CallerFocus::History => self
+                .selected_canister()
+                .map_or(0, |c| self.caller.history_of(&c.canister_id).len()),
+        }
+    }
+
+    fn caller_list_index(&mut self) -> &mut usize {
+        match self.caller.focus {
+            CallerFocus::Methods => &mut self.caller.selected_method_index,
+            CallerFocus::History => &mut self.caller.selected_history_index,
+        }
+    }
+
+    /// Drops data fetched for the previous identity/network; the workers
+    /// repopulate it as soon as they poll with the new selection.
+    fn clear_selection_data(&mut self) {
+        self.selected_identity_principal = None;
+        self.selected_identity_icp = None;
+        self.selected_identity_cycles = None;
+        self.selected_identity_wallet_cycles = None;
+        self.replica.ping = None;
+        self.replica.info = None;
+        self.replica.status_source = None;
+        self.history.clear();
+        self.canister_tree.selected = None;
+    }
 }
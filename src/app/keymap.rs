@@ -0,0 +1,595 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+/// Something the dashboard can do from a key press. Names are the kebab-case
+/// variants, e.g. `start-stop-replica`, as used in the config's keybindings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    Quit,
+    Help,
+    Up,
+    Down,
+    Select,
+    Close,
+    PickerOpen,
+    PickerParent,
+    SwitchFocus,
+    RefreshSlower,
+    RefreshFaster,
+    SelectIdentity,
+    SelectNetwork,
+    StartStopReplica,
+    RestartReplica,
+    ToggleAutoRestart,
+    ToggleProjects,
+    Build,
+    Deploy,
+    Generate,
+    InstallMode,
+    RegisterProject,
+    ToggleCaller,
+    SortCanisters,
+    StartCanister,
+    StopCanister,
+    DepositCycles,
+    UninstallCode,
+    DeleteCanister,
+    PreviousLogs,
+    NextLogs,
+    SearchLogs,
+    FilterLogs,
+    LogLevel,
+    FollowFile,
+    CloseFile,
+    ExportLogs,
+    PageUp,
+    PageDown,
+    LogsTop,
+    LogsTail,
+}
+
+impl Action {
+    pub const ALL: [Action; 41] = [
+        Action::Quit,
+        Action::Help,
+        Action::Up,
+        Action::Down,
+        Action::Select,
+        Action::Close,
+        Action::PickerOpen,
+        Action::PickerParent,
+        Action::SwitchFocus,
+        Action::RefreshSlower,
+        Action::RefreshFaster,
+        Action::SelectIdentity,
+        Action::SelectNetwork,
+        Action::StartStopReplica,
+        Action::RestartReplica,
+        Action::ToggleAutoRestart,
+        Action::ToggleProjects,
+        Action::Build,
+        Action::Deploy,
+        Action::Generate,
+        Action::InstallMode,
+        Action::RegisterProject,
+        Action::ToggleCaller,
+        Action::SortCanisters,
+        Action::StartCanister,
+        Action::StopCanister,
+        Action::DepositCycles,
+        Action::UninstallCode,
+        Action::DeleteCanister,
+        Action::PreviousLogs,
+        Action::NextLogs,
+        Action::SearchLogs,
+        Action::FilterLogs,
+        Action::LogLevel,
+        Action::FollowFile,
+        Action::CloseFile,
+        Action::ExportLogs,
+        Action::PageUp,
+        Action::PageDown,
+        Action::LogsTop,
+        Action::LogsTail,
+    ];
+
+    /// Keys bound to the action unless the config says otherwise.
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::Quit => &["q"],
+            Action::Help => &["?"],
+            Action::Up => &["up", "k"],
+            Action::Down => &["down", "j"],
+            Action::Select => &["enter"],
+            Action::Close => &["esc"],
+            Action::PickerOpen => &["enter", "right"],
+            Action::PickerParent => &["left", "backspace"],
+            Action::SwitchFocus => &["tab"],
+            Action::RefreshSlower => &["+"],
+            Action::RefreshFaster => &["-"],
+            Action::SelectIdentity => &["i"],
+            Action::SelectNetwork => &["n"],
+            Action::StartStopReplica => &["s"],
+            Action::RestartReplica => &["R"],
+            Action::ToggleAutoRestart => &["A"],
+            Action::ToggleProjects => &["P"],
+            Action::Build => &["b"],
+            Action::Deploy => &["d"],
+            Action::Generate => &["g"],
+            Action::InstallMode => &["m"],
+            Action::RegisterProject => &["r"],
+            Action::ToggleCaller => &["c"],
+            Action::SortCanisters => &["o"],
+            Action::StartCanister => &["t"],
+            Action::StopCanister => &["p"],
+            Action::DepositCycles => &["$"],
+            Action::UninstallCode => &["u"],
+            Action::DeleteCanister => &["D"],
+            Action::PreviousLogs => &["left"],
+            Action::NextLogs => &["right"],
+            Action::SearchLogs => &["/"],
+            Action::FilterLogs => &["&"],
+            Action::LogLevel => &["L"],
+            Action::FollowFile => &["f"],
+            Action::CloseFile => &["x"],
+            Action::ExportLogs => &["E"],
+            Action::PageUp => &["pageup"],
+            Action::PageDown => &["pagedown"],
+            Action::LogsTop => &["home"],
+            Action::LogsTail => &["end"],
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(name)) => write!(f, "{name}"),
+            _ => write!(f, "{self:?}"),
+        }
+    }
+}
+
+/// Where key presses go, which decides the actions they can trigger.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Context {
+    Canisters,
+    NetworkMenu,
+    IdentityMenu,
+    Caller,
+    Projects,
+    FilePicker,
+}
+
+/// Actions that work in every context but the file picker.
+const DASHBOARD_ACTIONS: &[(Action, &str)] = &[
+    (Action::Help, "show the keys"),
+    (Action::Quit, "quit"),
+    (Action::SelectNetwork, "select a network"),
+    (Action::SelectIdentity, "select an identity"),
+    (Action::RefreshFaster, "refresh faster"),
+    (Action::RefreshSlower, "refresh slower"),
+    (Action::StartStopReplica, "start or stop the local replica"),
+    (Action::RestartReplica, "restart the local replica"),
+    (
+        Action::ToggleAutoRestart,
+        "restart a crashed replica automatically",
+    ),
+    (Action::ToggleCaller, "call a method of the canister"),
+    (Action::ToggleProjects, "show the projects"),
+    (Action::StartCanister, "start the canister"),
+    (Action::StopCanister, "stop the canister"),
+    (Action::DepositCycles, "deposit cycles into the canister"),
+    (Action::UninstallCode, "uninstall the canister's code"),
+    (Action::DeleteCanister, "delete the canister"),
+    (Action::PreviousLogs, "previous logs tab"),
+    (Action::NextLogs, "next logs tab"),
+    (Action::SearchLogs, "search the logs"),
+    (Action::FilterLogs, "filter the logs"),
+    (Action::LogLevel, "minimum log level"),
+    (Action::FollowFile, "follow a log file"),
+    (Action::CloseFile, "close the log file tab"),
+    (Action::ExportLogs, "export the logs"),
+    (Action::PageUp, "scroll the logs up"),
+    (Action::PageDown, "scroll the logs down"),
+    (Action::LogsTop, "oldest logs"),
+    (Action::LogsTail, "follow the newest logs"),
+];
+
+impl Context {
+    pub const ALL: [Context; 6] = [
+        Context::Canisters,
+        Context::NetworkMenu,
+        Context::IdentityMenu,
+        Context::Caller,
+        Context::Projects,
+        Context::FilePicker,
+    ];
+
+    /// Actions only this context has, with what they do in it. They win over
+    /// the dashboard-wide actions when both are bound to a key.
+    pub fn actions(&self) -> &'static [(Action, &'static str)] {
+        match self {
+            Context::Canisters => &[
+                (Action::Up, "previous canister or project"),
+                (Action::Down, "next canister or project"),
+                (Action::Select, "collapse or expand the project"),
+                (Action::SortCanisters, "sort by name, cycles or memory"),
+            ],
+            Context::NetworkMenu => &[
+                (Action::Up, "previous network"),
+                (Action::Down, "next network"),
+                (Action::Select, "switch to the network"),
+                (Action::Close, "close the menu"),
+            ],
+            Context::IdentityMenu => &[
+                (Action::Up, "previous identity"),
+                (Action::Down, "next identity"),
+                (Action::Select, "switch to the identity"),
+                (Action::Close, "close the menu"),
+            ],
+            Context::Caller => &[
+                (Action::Up, "previous method or call"),
+                (Action::Down, "next method or call"),
+                (Action::Select, "call the method or run the call again"),
+                (Action::SwitchFocus, "switch between methods and history"),
+                (Action::Close, "close the caller"),
+            ],
+            Context::Projects => &[
+                (Action::Up, "previous project or canister"),
+                (Action::Down, "next project or canister"),
+                (Action::Build, "dfx build"),
+                (Action::Deploy, "dfx deploy"),
+                (Action::Generate, "dfx generate"),
+                (Action::InstallMode, "install mode of deploy"),
+                (Action::RegisterProject, "register the discovered project"),
+                (Action::Close, "close the projects"),
+            ],
+            Context::FilePicker => &[
+                (Action::Up, "previous entry"),
+                (Action::Down, "next entry"),
+                (Action::PickerOpen, "open the directory or follow the file"),
+                (Action::PickerParent, "parent directory"),
+                (Action::Close, "cancel"),
+                (Action::Help, "show the keys"),
+                (Action::Quit, "quit"),
+            ],
+        }
+    }
+
+    /// The dashboard-wide actions, if they work in this context.
+    pub fn dashboard_actions(&self) -> &'static [(Action, &'static str)] {
+        match self {
+            Context::FilePicker => &[],
+            _ => DASHBOARD_ACTIONS,
+        }
+    }
+
+    fn accepts(&self, action: Action) -> bool {
+        self.actions()
+            .iter()
+            .chain(self.dashboard_actions())
+            .any(|(a, _)| *a == action)
+    }
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Context::Canisters => write!(f, "canisters"),
+            Context::NetworkMenu => write!(f, "network menu"),
+            Context::IdentityMenu => write!(f, "identity menu"),
+            Context::Caller => write!(f, "caller"),
+            Context::Projects => write!(f, "projects"),
+            Context::FilePicker => write!(f, "file picker"),
+        }
+    }
+}
+
+/// Whether the two actions are ever triggered in the same context, so a key
+/// can only belong to one of them.
+fn share_context(a: Action, b: Action) -> bool {
+    Context::ALL
+        .iter()
+        .any(|context| context.accepts(a) && context.accepts(b))
+}
+
+/// A key with or without Ctrl, written e.g. `j`, `R`, `pageup` or `ctrl+s`.
+/// Shift is part of the character, so `R` is Shift+r.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    pub code: KeyCode,
+    pub ctrl: bool,
+}
+
+impl Key {
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        event.code == self.code && event.modifiers.contains(KeyModifiers::CONTROL) == self.ctrl
+    }
+}
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (ctrl, name) = match s.split_once('+') {
+            Some((modifier, name)) if modifier.eq_ignore_ascii_case("ctrl") && !name.is_empty() => {
+                (true, name)
+            }
+            _ => (false, s),
+        };
+        let mut chars = name.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match name.to_ascii_lowercase().as_str() {
+                "enter" => KeyCode::Enter,
+                "esc" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" => KeyCode::Delete,
+                "space" => KeyCode::Char(' '),
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "pageup" | "pgup" => KeyCode::PageUp,
+                "pagedown" | "pgdn" => KeyCode::PageDown,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                f => match f.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n @ 1..=12) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key '{s}'")),
+                },
+            },
+        };
+        Ok(Key { code, ctrl })
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "Space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::Up => write!(f, "UP"),
+            KeyCode::Down => write!(f, "DOWN"),
+            KeyCode::Left => write!(f, "LEFT"),
+            KeyCode::Right => write!(f, "RIGHT"),
+            KeyCode::PageUp => write!(f, "PgUp"),
+            KeyCode::PageDown => write!(f, "PgDn"),
+            KeyCode::BackTab => write!(f, "Shift+Tab"),
+            KeyCode::F(n) => write!(f, "F{n}"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+/// Which action each key triggers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: Vec<(Action, Key)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::with_overrides(&BTreeMap::new()).expect("default keys parse")
+    }
+}
+
+impl Keymap {
+    /// The default bindings, with the keys of the actions in `overrides`
+    /// replaced. A key given to an action is taken from the other actions of
+    /// its contexts, so two actions can swap keys. Returns every key that does
+    /// not parse or is given to two actions of one context.
+    pub fn with_overrides(
+        overrides: &BTreeMap<Action, Vec<String>>,
+    ) -> Result<Keymap, Vec<String>> {
+        let mut errors = vec![];
+        let mut overridden = vec![];
+        for (action, keys) in overrides {
+            for key in keys {
+                match key.parse::<Key>() {
+                    Ok(key) => overridden.push((*action, key)),
+                    Err(e) => errors.push(format!("keybindings.{action}: {e}")),
+                }
+            }
+        }
+        for (i, (action, key)) in overridden.iter().enumerate() {
+            if let Some((other, _)) = overridden[..i]
+                .iter()
+                .find(|(other, taken)| taken == key && share_context(*action, *other))
+            {
+                errors.push(format!(
+                    "keybindings.{action}: '{key}' is also bound to {other}"
+                ));
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+        let mut bindings: Vec<(Action, Key)> = Action::ALL
+            .iter()
+            .filter(|action| !overrides.contains_key(action))
+            .flat_map(|action| {
+                action
+                    .default_keys()
+                    .iter()
+                    .map(|key| (*action, key.parse().expect("default keys parse")))
+            })
+            .filter(|(action, key)| {
+                !overridden
+                    .iter()
+                    .any(|(other, taken)| taken == key && share_context(*action, *other))
+            })
+            .collect();
+        bindings.extend(overridden);
+        Ok(Keymap { bindings })
+    }
+
+    /// The action `event` triggers in `context`, if any.
+    pub fn action(&self, context: Context, event: &KeyEvent) -> Option<Action> {
+        let bound = |actions: &[(Action, &str)]| {
+            self.bindings
+                .iter()
+                .find(|(action, key)| {
+                    key.matches(event) && actions.iter().any(|(a, _)| a == action)
+                })
+                .map(|(action, _)| *action)
+        };
+        bound(context.actions()).or_else(|| bound(context.dashboard_actions()))
+    }
+
+    pub fn keys(&self, action: Action) -> impl Iterator<Item = &Key> {
+        self.bindings
+            .iter()
+            .filter(move |(a, _)| *a == action)
+            .map(|(_, key)| key)
+    }
+
+    /// The keys of `action` as shown in pane titles, e.g. `[s]` or `[j]/[DOWN]`.
+    pub fn label(&self, action: Action) -> String {
+        let keys: Vec<String> = self.keys(action).map(|key| format!("[{key}]")).collect();
+        if keys.is_empty() {
+            "[unbound]".to_string()
+        } else {
+            keys.join("/")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(code, modifiers)
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(
+            "j".parse(),
+            Ok(Key {
+                code: KeyCode::Char('j'),
+                ctrl: false
+            })
+        );
+        assert_eq!(
+            "PgDn".parse(),
+            Ok(Key {
+                code: KeyCode::PageDown,
+                ctrl: false
+            })
+        );
+        assert_eq!(
+            "ctrl+s".parse(),
+            Ok(Key {
+                code: KeyCode::Char('s'),
+                ctrl: true
+            })
+        );
+        assert_eq!("f5".parse::<Key>().unwrap().to_string(), "F5");
+        assert!("ctrl+".parse::<Key>().is_err());
+        assert!("hyper".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn test_overrides_take_keys_from_other_actions() {
+        let overrides = BTreeMap::from([
+            (Action::StartStopReplica, vec!["S".to_string()]),
+            (
+                Action::SearchLogs,
+                vec!["s".to_string(), "ctrl+f".to_string()],
+            ),
+        ]);
+        let keymap = Keymap::with_overrides(&overrides).unwrap();
+        let s = press(KeyCode::Char('s'), KeyModifiers::NONE);
+        assert_eq!(
+            keymap.action(Context::Canisters, &s),
+            Some(Action::SearchLogs)
+        );
+        let shift_s = press(KeyCode::Char('S'), KeyModifiers::SHIFT);
+        assert_eq!(
+            keymap.action(Context::Canisters, &shift_s),
+            Some(Action::StartStopReplica)
+        );
+        let ctrl_f = press(KeyCode::Char('f'), KeyModifiers::CONTROL);
+        assert_eq!(
+            keymap.action(Context::Canisters, &ctrl_f),
+            Some(Action::SearchLogs)
+        );
+        assert_eq!(keymap.label(Action::SearchLogs), "[s]/[Ctrl+f]");
+
+        let bad = BTreeMap::from([(Action::Quit, vec!["hyper".to_string()])]);
+        assert_eq!(
+            Keymap::with_overrides(&bad),
+            Err(vec!["keybindings.quit: unknown key 'hyper'".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_keys_depend_on_context() {
+        let keymap = Keymap::default();
+        let key = |code| press(code, KeyModifiers::NONE);
+        let j = key(KeyCode::Char('j'));
+        assert_eq!(keymap.action(Context::Canisters, &j), Some(Action::Down));
+        assert_eq!(keymap.action(Context::FilePicker, &j), Some(Action::Down));
+        let enter = key(KeyCode::Enter);
+        assert_eq!(
+            keymap.action(Context::NetworkMenu, &enter),
+            Some(Action::Select)
+        );
+        assert_eq!(
+            keymap.action(Context::FilePicker, &enter),
+            Some(Action::PickerOpen)
+        );
+        let left = key(KeyCode::Left);
+        assert_eq!(
+            keymap.action(Context::Caller, &left),
+            Some(Action::PreviousLogs)
+        );
+        assert_eq!(
+            keymap.action(Context::FilePicker, &left),
+            Some(Action::PickerParent)
+        );
+        let b = key(KeyCode::Char('b'));
+        assert_eq!(keymap.action(Context::Projects, &b), Some(Action::Build));
+        assert_eq!(keymap.action(Context::Canisters, &b), None);
+        let question = press(KeyCode::Char('?'), KeyModifiers::SHIFT);
+        assert_eq!(
+            keymap.action(Context::Projects, &question),
+            Some(Action::Help)
+        );
+
+        // every action is reachable somewhere
+        for action in Action::ALL {
+            assert!(Context::ALL.iter().any(|c| c.accepts(action)), "{action}");
+        }
+
+        // giving "enter" to select only takes it from the picker's actions
+        // if they ever meet in one context, which they don't
+        let overrides =
+            BTreeMap::from([(Action::Select, vec!["enter".to_string(), "l".to_string()])]);
+        let keymap = Keymap::with_overrides(&overrides).unwrap();
+        assert_eq!(
+            keymap.action(Context::FilePicker, &enter),
+            Some(Action::PickerOpen)
+        );
+        let clash = BTreeMap::from([
+            (Action::Build, vec!["x".to_string()]),
+            (Action::Help, vec!["x".to_string()]),
+        ]);
+        assert_eq!(
+            Keymap::with_overrides(&clash),
+            Err(vec![
+                "keybindings.build: 'x' is also bound to help".to_string()
+            ])
+        );
+    }
+}
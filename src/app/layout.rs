@@ -1,3 +1,4 @@
+use crate::config::LayoutConfig;
 use ratatui::prelude::*;
 
 ///  +-----------------------------+------------+
@@ -10,25 +11,33 @@ use ratatui::prelude::*;
 ///  |  CANISTER INFO     |                     |
 ///  |                    |                     |
 ///  +--------------------+---------------------+
-pub fn get_layout(frame_size: Rect) -> (Rect, Rect, Rect, Rect, Rect) {
+///
+/// The proportions come from the config's `layout`.
+pub fn get_layout(frame_size: Rect, config: &LayoutConfig) -> (Rect, Rect, Rect, Rect, Rect) {
+    let split = |percent: u16| {
+        [
+            Constraint::Percentage(percent),
+            Constraint::Percentage(100 - percent),
+        ]
+    };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(25), Constraint::Percentage(75)])
+        .constraints(split(config.top_percent))
         .split(frame_size);
 
     let top_chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(25), Constraint::Percentage(75)])
+        .constraints(split(config.network_percent))
         .split(chunks[0]);
 
     let bottom_chunks = Layout::default()
         .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(35), Constraint::Percentage(65)])
+        .constraints(split(config.canisters_percent))
         .split(chunks[1]);
 
     let left_chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Percentage(82), Constraint::Percentage(18)])
+        .constraints(split(config.canister_list_percent))
         .split(bottom_chunks[0]);
 
     return (
@@ -39,3 +48,35 @@ pub fn get_layout(frame_size: Rect) -> (Rect, Rect, Rect, Rect, Rect) {
         bottom_chunks[1], // logs
     );
 }
+
+/// Splits the canister info pane into the info text and `charts` sparkline
+/// panels stacked below it, three rows each.
+pub fn split_canister_info(area: Rect, charts: usize) -> (Rect, Vec<Rect>) {
+    let mut constraints = vec![Constraint::Min(0)];
+    constraints.extend((0..charts).map(|_| Constraint::Length(3)));
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(constraints)
+        .split(area);
+    (chunks[0], chunks[1..].to_vec())
+}
+
+/// A `percent_x` wide, `height` rows tall area in the middle of `area`, for dialogs.
+pub fn centered_rect(percent_x: u16, height: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Min(0),
+            Constraint::Length(height),
+            Constraint::Min(0),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
@@ -0,0 +1,321 @@
+use crate::{
+    app::log_store::LogStore,
+    logs::{LogLevel, LogLine, LogStream},
+};
+use std::{
+    fs::{self, File, Metadata},
+    io::{self, Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::Duration,
+};
+
+/// How much of an existing file is shown when it is first opened.
+const INITIAL_TAIL_BYTES: u64 = 64 * 1024;
+/// How often followed files are checked for new lines.
+const TAIL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Reads the lines appended to a file, like `tail -F`: a truncated file is
+/// read again from the start, and when the path is rotated to a new file the
+/// rest of the old one is read before switching over.
+#[derive(Debug)]
+pub struct FileTail {
+    path: PathBuf,
+    file: Option<(File, FileId)>,
+    offset: u64,
+    /// Bytes after the last newline, waiting for the rest of their line.
+    partial: Vec<u8>,
+}
+
+/// Identifies the file behind a path, to notice when the path is rotated.
+type FileId = (u64, u64);
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> FileId {
+    use std::os::unix::fs::MetadataExt;
+    (metadata.dev(), metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> FileId {
+    (0, 0)
+}
+
+impl FileTail {
+    pub fn new(path: PathBuf) -> Self {
+        FileTail {
+            path,
+            file: None,
+            offset: 0,
+            partial: vec![],
+        }
+    }
+
+    /// Complete lines written since the last call.
+    pub fn read_lines(&mut self) -> io::Result<Vec<String>> {
+        let metadata = fs::metadata(&self.path)?;
+        let id = file_id(&metadata);
+        let mut lines = vec![];
+        match &self.file {
+            None => {
+                // start near the end, at the beginning of a line
+                self.offset = metadata.len().saturating_sub(INITIAL_TAIL_BYTES);
+                self.open(id)?;
+                if self.offset > 0 {
+                    self.read_available(&mut lines)?;
+                    if !lines.is_empty() {
+                        lines.remove(0);
+                    }
+                }
+            }
+            Some((_, old_id)) if *old_id != id => {
+                self.read_available(&mut lines)?;
+                lines.extend(self.take_partial());
+                self.offset = 0;
+                self.open(id)?;
+            }
+            Some(_) if metadata.len() < self.offset => {
+                self.offset = 0;
+                self.partial.clear();
+            }
+            Some(_) => {}
+        }
+        self.read_available(&mut lines)?;
+        Ok(lines)
+    }
+
+    fn open(&mut self, id: FileId) -> io::Result<()> {
+        self.file = Some((File::open(&self.path)?, id));
+        Ok(())
+    }
+
+    fn read_available(&mut self, lines: &mut Vec<String>) -> io::Result<()> {
+        let Some((file, _)) = self.file.as_mut() else {
+            return Ok(());
+        };
+        file.seek(SeekFrom::Start(self.offset))?;
+        let read = file.read_to_end(&mut self.partial)?;
+        self.offset += read as u64;
+        while let Some(end) = self.partial.iter().position(|b| *b == b'\n') {
+            let line: Vec<u8> = self.partial.drain(..=end).collect();
+            lines.push(to_line(&line[..end]));
+        }
+        Ok(())
+    }
+
+    fn take_partial(&mut self) -> Option<String> {
+        if self.partial.is_empty() {
+            return None;
+        }
+        let line = to_line(&self.partial);
+        self.partial.clear();
+        Some(line)
+    }
+}
+
+fn to_line(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_end_matches('\r')
+        .to_string()
+}
+
+/// A followed log file, shown as its own tab in the logs pane.
+#[derive(Debug)]
+pub struct FileSource {
+    pub name: String,
+    pub path: PathBuf,
+    pub lines: LogStore,
+    receiver: Receiver<Result<Vec<String>, String>>,
+    last_error: Option<String>,
+}
+
+impl FileSource {
+    /// Follows `path` on a background thread until the source is dropped.
+    pub fn follow(name: String, path: PathBuf) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let mut tail = FileTail::new(path.clone());
+        thread::spawn(move || loop {
+            let lines = tail.read_lines().map_err(|e| e.to_string());
+            let nothing_new = matches!(&lines, Ok(lines) if lines.is_empty());
+            if !nothing_new && sender.send(lines).is_err() {
+                return;
+            }
+            thread::sleep(TAIL_INTERVAL);
+        });
+        FileSource {
+            name,
+            path,
+            lines: LogStore::default(),
+            receiver,
+            last_error: None,
+        }
+    }
+
+    /// Moves new lines into `lines`; each distinct read error is logged once.
+    pub fn collect_lines(&mut self) {
+        while let Ok(result) = self.receiver.try_recv() {
+            match result {
+                Ok(lines) => {
+                    self.last_error = None;
+                    self.lines.extend(
+                        lines
+                            .into_iter()
+                            .map(|line| LogLine::new(LogStream::File, line)),
+                    );
+                }
+                Err(e) if self.last_error.as_ref() != Some(&e) => {
+                    self.lines.push(LogLine::dfx_top(
+                        LogLevel::Warn,
+                        format!("[dfx-top] {}: {e}", self.path.display()),
+                    ));
+                    self.last_error = Some(e);
+                }
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+/// Directory listing to pick a log file to follow.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FilePicker {
+    pub dir: PathBuf,
+    /// Subdirectories first, then files, each sorted by name.
+    pub entries: Vec<PathBuf>,
+    pub selected_index: usize,
+    pub error: Option<String>,
+}
+
+impl FilePicker {
+    pub fn open(dir: PathBuf) -> Self {
+        let mut picker = FilePicker {
+            dir,
+            entries: vec![],
+            selected_index: 0,
+            error: None,
+        };
+        picker.list();
+        picker
+    }
+
+    fn list(&mut self) {
+        self.selected_index = 0;
+        match fs::read_dir(&self.dir) {
+            Ok(entries) => {
+                let mut entries: Vec<PathBuf> =
+                    entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+                entries
+                    .sort_by_key(|path| (!path.is_dir(), path.file_name().map(|n| n.to_owned())));
+                self.entries = entries;
+                self.error = None;
+            }
+            Err(e) => {
+                self.entries = vec![];
+                self.error = Some(e.to_string());
+            }
+        }
+    }
+
+    pub fn selected(&self) -> Option<&Path> {
+        self.entries.get(self.selected_index).map(PathBuf::as_path)
+    }
+
+    pub fn up(&mut self) {
+        let len = self.entries.len();
+        if len > 0 {
+            self.selected_index = (self.selected_index + len - 1) % len;
+        }
+    }
+
+    pub fn down(&mut self) {
+        let len = self.entries.len();
+        if len > 0 {
+            self.selected_index = (self.selected_index + 1) % len;
+        }
+    }
+
+    pub fn parent(&mut self) {
+        if let Some(parent) = self.dir.parent() {
+            let child = self.dir.clone();
+            self.dir = parent.to_path_buf();
+            self.list();
+            // keep the directory we came from selected
+            if let Some(index) = self.entries.iter().position(|e| *e == child) {
+                self.selected_index = index;
+            }
+        }
+    }
+
+    /// Enters the selected directory, or returns the selected file.
+    pub fn enter(&mut self) -> Option<PathBuf> {
+        let selected = self.selected()?.to_path_buf();
+        if selected.is_dir() {
+            self.dir = selected;
+            self.list();
+            None
+        } else {
+            Some(selected)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::io::Write;
+
+    fn append(path: &Path, text: &str) {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(text.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_tail_follows_truncation_and_rotation() {
+        let dir = TempDir::new("tail");
+        let path = dir.join("replica.log");
+        append(&path, "old line\n");
+        let mut tail = FileTail::new(path.clone());
+        assert_eq!(tail.read_lines().unwrap(), vec!["old line"]);
+
+        append(&path, "first\nsec");
+        assert_eq!(tail.read_lines().unwrap(), vec!["first"]);
+        append(&path, "ond\r\n");
+        assert_eq!(tail.read_lines().unwrap(), vec!["second"]);
+
+        fs::write(&path, "after truncation\n").unwrap();
+        assert_eq!(tail.read_lines().unwrap(), vec!["after truncation"]);
+
+        append(&path, "last of old file");
+        fs::rename(&path, dir.join("replica.log.1")).unwrap();
+        append(&path, "new file\n");
+        assert_eq!(
+            tail.read_lines().unwrap(),
+            vec!["last of old file", "new file"]
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(tail.read_lines().is_err());
+    }
+
+    #[test]
+    fn test_picker_lists_directories_first() {
+        let dir = TempDir::new("picker");
+        fs::create_dir_all(dir.join("logs")).unwrap();
+        append(&dir.join("a.log"), "");
+        let mut picker = FilePicker::open(dir.to_path_buf());
+        assert_eq!(picker.entries, vec![dir.join("logs"), dir.join("a.log")]);
+        picker.down();
+        assert_eq!(picker.enter(), Some(dir.join("a.log")));
+        picker.up();
+        assert_eq!(picker.enter(), None);
+        assert_eq!(picker.dir, dir.join("logs"));
+        picker.parent();
+        assert_eq!(picker.selected(), Some(dir.join("logs").as_path()));
+    }
+}
@@ -0,0 +1,266 @@
+use crate::logs::{strip_ansi, LogLine};
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+/// How many lines are kept in memory. Older lines move to the spill file.
+pub const LOG_BUFFER_LEN: usize = 10_000;
+/// Size at which the spill file is rotated.
+pub const SPILL_FILE_LEN: u64 = 10 * 1024 * 1024;
+/// How many rotated spill files (`replica.log.1` ...) are kept.
+pub const SPILL_FILES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ExportFormat {
+    #[default]
+    Text,
+    JsonLines,
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Text => "log",
+            ExportFormat::JsonLines => "jsonl",
+        }
+    }
+
+    pub fn toggle(&self) -> ExportFormat {
+        match self {
+            ExportFormat::Text => ExportFormat::JsonLines,
+            ExportFormat::JsonLines => ExportFormat::Text,
+        }
+    }
+
+    /// One line of the file, without the newline. Colors are stripped.
+    pub fn format(&self, line: &LogLine) -> String {
+        let text = strip_ansi(&line.text);
+        match self {
+            ExportFormat::Text => format!(
+                "{} {:<7} {:<5} {text}",
+                line.at.format("%Y-%m-%d %H:%M:%S%.3f"),
+                line.stream.to_string(),
+                line.level.map(|l| l.to_string()).unwrap_or_default(),
+            ),
+            ExportFormat::JsonLines => serde_json::json!({
+                "at": line.at.to_rfc3339(),
+                "stream": line.stream.to_string(),
+                "level": line.level.map(|l| l.to_string()),
+                "text": text,
+            })
+            .to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ExportFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportFormat::Text => write!(f, "plain text"),
+            ExportFormat::JsonLines => write!(f, "JSON lines"),
+        }
+    }
+}
+
+/// Writes `lines` to `path`, returning how many were written.
+pub fn export<'a>(
+    lines: impl IntoIterator<Item = &'a LogLine>,
+    path: &Path,
+    format: ExportFormat,
+) -> io::Result<usize> {
+    let mut file = io::BufWriter::new(File::create(path)?);
+    let mut count = 0;
+    for line in lines {
+        writeln!(file, "{}", format.format(line))?;
+        count += 1;
+    }
+    file.flush()?;
+    Ok(count)
+}
+
+/// Append-only log file that is rotated once it reaches [`SPILL_FILE_LEN`].
+#[derive(Debug)]
+struct SpillFile {
+    path: PathBuf,
+    file: Option<File>,
+    len: u64,
+}
+
+impl SpillFile {
+    fn write(&mut self, line: &LogLine) -> io::Result<()> {
+        let text = ExportFormat::Text.format(line) + "\n";
+        if self.file.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            self.len = file.metadata()?.len();
+            self.file = Some(file);
+        }
+        if self.len > 0 && self.len + text.len() as u64 > SPILL_FILE_LEN {
+            self.rotate()?;
+            return self.write(line);
+        }
+        self.file
+            .as_mut()
+            .expect("opened above")
+            .write_all(text.as_bytes())?;
+        self.len += text.len() as u64;
+        Ok(())
+    }
+
+    /// `replica.log` becomes `replica.log.1`, `.1` becomes `.2` and so on.
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        let rotated = |n: usize| PathBuf::from(format!("{}.{n}", self.path.display()));
+        for n in (1..SPILL_FILES).rev() {
+            if rotated(n).exists() {
+                fs::rename(rotated(n), rotated(n + 1))?;
+            }
+        }
+        fs::rename(&self.path, rotated(1))
+    }
+}
+
+/// The most recent [`LOG_BUFFER_LEN`] log lines, with older ones appended to
+/// a spill file when one is configured.
+#[derive(Debug)]
+pub struct LogStore {
+    lines: VecDeque<LogLine>,
+    capacity: usize,
+    spill: Option<SpillFile>,
+    /// Set when writing the spill file failed; spilling stops after that.
+    pub spill_error: Option<String>,
+    /// Traps seen per canister id, spilled lines included.
+    traps: HashMap<String, usize>,
+}
+
+impl Default for LogStore {
+    fn default() -> Self {
+        LogStore {
+            lines: VecDeque::new(),
+            capacity: LOG_BUFFER_LEN,
+            spill: None,
+            spill_error: None,
+            traps: HashMap::new(),
+        }
+    }
+}
+
+impl LogStore {
+    /// Keeps `capacity` lines in memory and appends older ones to `path`.
+    pub fn with_spill_file(capacity: usize, path: PathBuf) -> Self {
+        LogStore {
+            capacity,
+            spill: Some(SpillFile {
+                path,
+                file: None,
+                len: 0,
+            }),
+            ..Default::default()
+        }
+    }
+
+    pub fn spill_path(&self) -> Option<&Path> {
+        self.spill.as_ref().map(|spill| spill.path.as_path())
+    }
+
+    pub fn push(&mut self, line: LogLine) {
+        if let Some(canister) = line.canister.as_ref().filter(|_| line.is_trap()) {
+            *self.traps.entry(canister.canister_id.clone()).or_default() += 1;
+        }
+        if self.lines.len() == self.capacity {
+            if let Some(oldest) = self.lines.pop_front() {
+                self.spill(&oldest);
+            }
+        }
+        self.lines.push_back(line);
+    }
+
+    fn spill(&mut self, line: &LogLine) {
+        let Some(spill) = self.spill.as_mut() else {
+            return;
+        };
+        if let Err(e) = spill.write(line) {
+            self.spill_error = Some(format!("{}: {e}", spill.path.display()));
+            self.spill = None;
+        }
+    }
+
+    pub fn trap_count(&self, canister_id: &str) -> usize {
+        self.traps.get(canister_id).copied().unwrap_or_default()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &LogLine> {
+        self.lines.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.lines.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+}
+
+impl Extend<LogLine> for LogStore {
+    fn extend<T: IntoIterator<Item = LogLine>>(&mut self, lines: T) {
+        for line in lines {
+            self.push(line);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logs::{LogLevel, LogStream};
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_overflow_spills_to_rotating_file() {
+        let dir = TempDir::new("log-store");
+        let path = dir.join("replica.log");
+        let mut store = LogStore::with_spill_file(2, path.clone());
+        for i in 0..5 {
+            store.push(LogLine::new(LogStream::Stdout, format!("line {i}")));
+        }
+        let kept: Vec<_> = store.iter().map(|l| l.text.as_str()).collect();
+        assert_eq!(kept, vec!["line 3", "line 4"]);
+        let spilled = fs::read_to_string(&path).unwrap();
+        assert_eq!(spilled.lines().count(), 3);
+        assert!(spilled
+            .lines()
+            .next()
+            .unwrap()
+            .ends_with("stdout        line 0"));
+
+        store.push(LogLine::new(
+            LogStream::Stderr,
+            "[Canister bkyz2-fmaaa-aaaaa-qaaaq-cai] Panicked at 'boom'".to_string(),
+        ));
+        assert_eq!(store.trap_count("bkyz2-fmaaa-aaaaa-qaaaq-cai"), 1);
+
+        store.spill.as_mut().unwrap().rotate().unwrap();
+        assert!(!path.exists());
+        assert!(dir.join("replica.log.1").exists());
+    }
+
+    #[test]
+    fn test_export_formats() {
+        let line = LogLine::dfx_top(LogLevel::Error, "\x1b[31mboom\x1b[0m".to_string());
+        let json: serde_json::Value =
+            serde_json::from_str(&ExportFormat::JsonLines.format(&line)).unwrap();
+        assert_eq!(json["stream"], "dfx-top");
+        assert_eq!(json["level"], "ERROR");
+        assert_eq!(json["text"], "boom");
+        assert!(ExportFormat::Text
+            .format(&line)
+            .ends_with(" dfx-top ERROR boom"));
+    }
+}
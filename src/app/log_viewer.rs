@@ -0,0 +1,245 @@
+use crate::logs::{strip_ansi, LogLevel, LogLine};
+use regex::Regex;
+use std::{cell::Cell, ops::Range};
+
+/// Which prompt is open at the bottom of the logs pane.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogPrompt {
+    /// `/`: highlights the text and jumps between lines containing it.
+    Search,
+    /// `&`: only shows lines matching a regex.
+    Filter,
+}
+
+/// Lines of one canister: its own output and traps, and other lines that
+/// mention it by id or by its name in a registered project.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanisterFilter {
+    pub canister_id: String,
+    pub name: Option<String>,
+}
+
+impl CanisterFilter {
+    pub fn matches(&self, line: &LogLine) -> bool {
+        match &line.canister {
+            Some(log) => log.canister_id == self.canister_id,
+            None => self.mentioned_in(&line.text),
+        }
+    }
+
+    fn mentioned_in(&self, text: &str) -> bool {
+        text.contains(&self.canister_id)
+            || self
+                .name
+                .as_ref()
+                .is_some_and(|name| contains_word(text, name))
+    }
+}
+
+/// Scrollback, search and filters of the logs pane.
+#[derive(Debug, Default)]
+pub struct LogViewer {
+    /// Prompt being typed into, with its text.
+    pub prompt: Option<(LogPrompt, String)>,
+    /// Highlighted text; case insensitive unless it contains upper case.
+    pub search: String,
+    pub filter: Option<Regex>,
+    pub filter_error: Option<String>,
+    /// Lines below this level are hidden; lines without a level count as info.
+    pub min_level: Option<LogLevel>,
+    /// First visible line while scrolled back, `None` while following the tail.
+    pub offset: Option<usize>,
+    /// Number of shown lines and pane height at the last draw, for scrolling.
+    pub viewport: Cell<(usize, usize)>,
+}
+
+impl LogViewer {
+    /// Whether `line` passes the level and regex filters.
+    pub fn shows(&self, line: &LogLine) -> bool {
+        if let Some(min_level) = self.min_level {
+            if line.level.unwrap_or(LogLevel::Info) < min_level {
+                return false;
+            }
+        }
+        match &self.filter {
+            Some(filter) => filter.is_match(&strip_ansi(&line.text)),
+            None => true,
+        }
+    }
+
+    /// Lines to draw out of `total` in a pane `height` lines high.
+    pub fn visible_range(&self, total: usize, height: usize) -> Range<usize> {
+        let last_page = total.saturating_sub(height);
+        let start = self
+            .offset
+            .map_or(last_page, |offset| offset.min(last_page));
+        start..total.min(start + height)
+    }
+
+    pub fn is_following(&self) -> bool {
+        self.offset.is_none()
+    }
+
+    pub fn follow(&mut self) {
+        self.offset = None;
+    }
+
+    pub fn scroll_to_top(&mut self) {
+        self.offset = Some(0);
+    }
+
+    /// Scrolls back `lines` lines, stopping at the first one.
+    pub fn scroll_up(&mut self, lines: usize) {
+        let (total, height) = self.viewport.get();
+        let start = self.visible_range(total, height).start;
+        self.offset = Some(start.saturating_sub(lines));
+    }
+
+    /// Scrolls forward `lines` lines, following the tail again at the end.
+    pub fn scroll_down(&mut self, lines: usize) {
+        let (total, height) = self.viewport.get();
+        let start = self.visible_range(total, height).start + lines;
+        self.offset = if start + height >= total {
+            None
+        } else {
+            Some(start)
+        };
+    }
+
+    /// Cycles the minimum level: all, info, warn, error.
+    pub fn next_level(&mut self) {
+        self.min_level = match self.min_level {
+            None => Some(LogLevel::Info),
+            Some(LogLevel::Trace | LogLevel::Debug | LogLevel::Info) => Some(LogLevel::Warn),
+            Some(LogLevel::Warn) => Some(LogLevel::Error),
+            Some(LogLevel::Error | LogLevel::Critical) => None,
+        };
+        self.follow();
+    }
+
+    /// Compiles the filter prompt's text; an empty one removes the filter.
+    pub fn set_filter(&mut self, pattern: &str) -> Result<(), regex::Error> {
+        self.filter = if pattern.is_empty() {
+            None
+        } else {
+            Some(Regex::new(pattern)?)
+        };
+        self.follow();
+        Ok(())
+    }
+
+    /// Byte ranges of the search text in `text`.
+    pub fn search_matches(&self, text: &str) -> Vec<Range<usize>> {
+        if self.search.is_empty() {
+            return vec![];
+        }
+        let case_sensitive = self.search.chars().any(char::is_uppercase);
+        let (haystack, needle) = if case_sensitive {
+            (text.to_string(), self.search.clone())
+        } else {
+            // ASCII lowering keeps byte offsets valid for `text`
+            (text.to_ascii_lowercase(), self.search.to_ascii_lowercase())
+        };
+        haystack
+            .match_indices(&needle)
+            .map(|(start, m)| start..start + m.len())
+            .collect()
+    }
+
+    /// Scrolls to the closest line before (`backwards`) or after the middle
+    /// of the pane that contains the search text, or to the latest one while
+    /// following the tail. `lines` are the shown lines.
+    pub fn jump_to_match(&mut self, lines: &[&str], backwards: bool) {
+        let (total, height) = (lines.len(), self.viewport.get().1);
+        let middle = match self.offset {
+            None => total,
+            Some(_) => self.visible_range(total, height).start + height / 2,
+        };
+        let is_match = |i: &usize| !self.search_matches(lines[*i]).is_empty();
+        let found = if backwards {
+            (0..middle.min(total)).rev().find(is_match)
+        } else {
+            (middle + 1..total).find(is_match)
+        };
+        if let Some(line) = found {
+            self.offset = Some(line.saturating_sub(height / 2));
+        }
+    }
+}
+
+/// Text of `line` without colors, as searched and highlighted. `clean` gives
+/// only the message of canister output, as shown in the canister logs tab.
+pub fn plain_text(line: &LogLine, clean: bool) -> String {
+    match &line.canister {
+        Some(log) if clean => log.message.clone(),
+        _ => strip_ansi(&line.text),
+    }
+}
+
+/// Whether `word` occurs in `text` not surrounded by identifier characters,
+/// so that `backend` does not match `backend_v2`.
+fn contains_word(text: &str, word: &str) -> bool {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    text.match_indices(word).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + word.len()..].chars().next();
+        !before.is_some_and(is_ident) && !after.is_some_and(is_ident)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logs::LogStream;
+
+    #[test]
+    fn test_filters() {
+        let mut viewer = LogViewer::default();
+        let info = LogLine::new(LogStream::Stderr, "Jan 15 INFO replica started".into());
+        let error = LogLine::new(LogStream::Stderr, "Jan 15 ERRO http handler failed".into());
+        let plain = LogLine::new(LogStream::Stdout, "[Canister bkyz2] hello".into());
+        viewer.next_level();
+        viewer.next_level();
+        assert_eq!(viewer.min_level, Some(LogLevel::Warn));
+        assert!(!viewer.shows(&info) && viewer.shows(&error) && !viewer.shows(&plain));
+
+        viewer.min_level = None;
+        viewer.set_filter(r"^\[Canister \w+\]").unwrap();
+        assert!(!viewer.shows(&info) && viewer.shows(&plain));
+        assert!(viewer.set_filter("(unclosed").is_err());
+    }
+
+    #[test]
+    fn test_canister_filter_recognises_names() {
+        let filter = CanisterFilter {
+            canister_id: "bkyz2-fmaaa-aaaaa-qaaaq-cai".into(),
+            name: Some("backend".into()),
+        };
+        let line = |text: &str| LogLine::new(LogStream::Stderr, text.to_string());
+        assert!(filter.matches(&line("[Canister bkyz2-fmaaa-aaaaa-qaaaq-cai] hi")));
+        assert!(!filter.matches(&line("[Canister be2us-64aaa-aaaaa-qaabq-cai] backend")));
+        assert!(filter.matches(&line("Installing code for canister backend, with ...")));
+        assert!(!filter.matches(&line("Installing code for canister backend_v2")));
+    }
+
+    #[test]
+    fn test_scrolling_and_search() {
+        let mut viewer = LogViewer::default();
+        viewer.viewport.set((100, 10));
+        assert_eq!(viewer.visible_range(100, 10), 90..100);
+        viewer.scroll_up(25);
+        assert_eq!(viewer.visible_range(100, 10), 65..75);
+        viewer.scroll_down(30);
+        assert!(viewer.is_following());
+
+        let mut lines: Vec<String> = (0..100).map(|i| format!("line {i}")).collect();
+        lines[20] = "Replica Panicked".into();
+        let lines: Vec<&str> = lines.iter().map(String::as_str).collect();
+        viewer.search = "panicked".into();
+        viewer.jump_to_match(&lines, true);
+        assert!(viewer.visible_range(100, 10).contains(&20));
+        assert_eq!(viewer.search_matches(lines[20]), vec![8..16]);
+        viewer.search = "Panicked".into();
+        assert!(viewer.search_matches("panicked").is_empty());
+    }
+}
@@ -1,4 +1,15 @@
+pub mod actions;
+pub mod caller;
+pub mod canister_tree;
+pub mod history;
 pub mod input;
+pub mod keymap;
 pub mod layout;
+pub mod log_files;
+pub mod log_store;
+pub mod log_viewer;
+pub mod poller;
+pub mod project_view;
+pub mod start_dialog;
 pub mod state;
 pub mod widgets;
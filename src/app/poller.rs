@@ -0,0 +1,331 @@
+use crate::{
+    config::{DiscoveryConfig, RefreshConfig},
+    dfx_commands::{dfx_run, Balance, DfxContext, DfxError, IcpBalance, PingOutput},
+    dfx_project::ProjectDatabase,
+    discovery::{self, DiscoveredProject},
+    networks::{self, NetworkInfo},
+    parse_replica_dashboard::ReplicaInfo,
+    replica_status::{FallbackSource, StatusRequest},
+};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Condvar, Mutex},
+    thread,
+};
+
+/// A data source polled by its own background worker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Source {
+    Identity,
+    Identities,
+    Replica,
+    Projects,
+    Discovery,
+}
+
+impl Source {
+    pub const ALL: [Source; 5] = [
+        Source::Identity,
+        Source::Identities,
+        Source::Replica,
+        Source::Projects,
+        Source::Discovery,
+    ];
+}
+
+/// What the workers need to know about the UI selection.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PollContext {
+    pub dfx: DfxContext,
+    /// URL of the selected network's replica, e.g. `https://icp0.io`.
+    pub provider: String,
+    pub refresh: RefreshConfig,
+    pub discovery: DiscoveryConfig,
+}
+
+/// Typed snapshot produced by one of the workers.
+#[derive(Debug)]
+pub enum Update {
+    Identity(Box<IdentitySnapshot>),
+    Identities(Vec<String>),
+    Replica(Box<ReplicaSnapshot>),
+    Projects(ProjectDatabase),
+    Networks(Vec<NetworkInfo>),
+    Discovered(Vec<DiscoveredProject>),
+}
+
+/// An update tagged with the context generation it was computed for, so
+/// results for a network or identity that is no longer selected can be dropped.
+#[derive(Debug)]
+pub struct Message {
+    pub generation: u64,
+    pub update: Update,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IdentitySnapshot {
+    pub principal: Result<String, DfxError>,
+    pub icp: Result<Balance<IcpBalance>, DfxError>,
+    pub cycles: Result<Balance<u128>, DfxError>,
+    pub wallet_cycles: Result<Balance<u128>, DfxError>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplicaSnapshot {
+    pub ping: Result<PingOutput, DfxError>,
+    pub webserver_port: String,
+    pub replica_port: String,
+    pub webserver_url: String,
+    pub replica_url: String,
+    pub replica_revision_url: String,
+    pub info: Option<ReplicaInfo>,
+    /// Which [`ReplicaStatusSource`](crate::replica_status::ReplicaStatusSource)
+    /// produced `info`, or why none of them could.
+    pub status_source: Result<&'static str, String>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    context: PollContext,
+    generation: u64,
+    pending: HashSet<Source>,
+    stopped: bool,
+}
+
+/// Runs every [`Source`] on its own thread and schedule. The UI only drains
+/// [`Message`]s, so a slow or unreachable dfx never blocks rendering.
+#[derive(Debug)]
+pub struct Poller {
+    shared: Arc<(Mutex<Shared>, Condvar)>,
+    receiver: mpsc::Receiver<Message>,
+}
+
+impl Poller {
+    pub fn spawn(context: PollContext, db_path: PathBuf) -> Self {
+        let shared = Arc::new((
+            Mutex::new(Shared {
+                context,
+                generation: 0,
+                pending: HashSet::new(),
+                stopped: false,
+            }),
+            Condvar::new(),
+        ));
+        let (sender, receiver) = mpsc::channel();
+        for source in Source::ALL {
+            let shared = shared.clone();
+            let sender = sender.clone();
+            let db_path = db_path.clone();
+            thread::spawn(move || worker(source, shared, sender, db_path));
+        }
+        Poller { shared, receiver }
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.shared.0.lock().unwrap().generation
+    }
+
+    /// Pushes the latest UI selection to the workers. If the identity or
+    /// network changed, every worker wakes up and polls right away.
+    pub fn set_context(&self, context: PollContext) {
+        let (lock, condvar) = &*self.shared;
+        let mut shared = lock.lock().unwrap();
+        if shared.context == context {
+            return;
+        }
+        if shared.context.dfx != context.dfx {
+            shared.generation += 1;
+        }
+        shared.context = context;
+        condvar.notify_all();
+    }
+
+    /// Asks the worker of `source` to poll now instead of waiting for its next tick.
+    pub fn request_refresh(&self, source: Source) {
+        let (lock, condvar) = &*self.shared;
+        lock.lock().unwrap().pending.insert(source);
+        condvar.notify_all();
+    }
+
+    pub fn try_recv(&self) -> Option<Message> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+impl Drop for Poller {
+    fn drop(&mut self) {
+        let (lock, condvar) = &*self.shared;
+        if let Ok(mut shared) = lock.lock() {
+            shared.stopped = true;
+        }
+        condvar.notify_all();
+    }
+}
+
+fn worker(
+    source: Source,
+    shared: Arc<(Mutex<Shared>, Condvar)>,
+    sender: mpsc::Sender<Message>,
+    db_path: PathBuf,
+) {
+    let (lock, condvar) = &*shared;
+    let mut networks_json = None;
+    loop {
+        let (context, generation) = {
+            let mut shared = lock.lock().unwrap();
+            if shared.stopped {
+                return;
+            }
+            shared.pending.remove(&source);
+            (shared.context.clone(), shared.generation)
+        };
+
+        let updates = match source {
+            Source::Identity => vec![poll_identity(&context.dfx)],
+            Source::Identities => poll_identities(&context.dfx).into_iter().collect(),
+            Source::Replica => vec![Update::Replica(Box::new(poll_replica(&context, &db_path)))],
+            Source::Projects => poll_projects(&context.dfx, &db_path, &mut networks_json),
+            Source::Discovery => poll_discovery(&context.discovery, &db_path)
+                .into_iter()
+                .collect(),
+        };
+        for update in updates {
+            if sender.send(Message { generation, update }).is_err() {
+                return; // UI is gone
+            }
+        }
+
+        let shared = lock.lock().unwrap();
+        let timeout = shared.context.refresh.interval(source);
+        let _ = condvar
+            .wait_timeout_while(shared, timeout, |s| {
+                !s.stopped && s.generation == generation && !s.pending.contains(&source)
+            })
+            .unwrap();
+    }
+}
+
+fn poll_identity(ctx: &DfxContext) -> Update {
+    Update::Identity(Box::new(IdentitySnapshot {
+        principal: dfx_run!(IdentityGetPrincipal, ctx, Principal),
+        icp: Balance::from_result(dfx_run!(LedgerBalance, ctx, Icp), &ctx.network),
+        cycles: Balance::from_result(dfx_run!(CyclesBalance, ctx, Cycles), &ctx.network),
+        wallet_cycles: Balance::from_result(dfx_run!(WalletBalance, ctx, Cycles), &ctx.network),
+    }))
+}
+
+fn poll_identities(ctx: &DfxContext) -> Option<Update> {
+    dfx_run!(IdentityList, ctx, Identities)
+        .ok()
+        .map(Update::Identities)
+}
+
+fn poll_replica(context: &PollContext, db_path: &Path) -> ReplicaSnapshot {
+    let ctx = &context.dfx;
+    let mut snapshot = ReplicaSnapshot {
+        ping: dfx_run!(Ping, ctx, Ping),
+        webserver_port: String::new(),
+        replica_port: String::new(),
+        webserver_url: String::new(),
+        replica_url: String::new(),
+        replica_revision_url: String::new(),
+        info: None,
+        status_source: Err("not queried".to_string()),
+    };
+    let port = |port: Result<u16, DfxError>| port.map(|p| p.to_string()).unwrap_or_default();
+    let mut status_url = context.provider.clone();
+    match ctx.network.as_str() {
+        "local" if snapshot.ping.is_ok() => {
+            snapshot.webserver_port = port(dfx_run!(InfoWebserverPort, ctx, Port));
+            snapshot.replica_port = port(dfx_run!(InfoReplicaPort, ctx, Port));
+            snapshot.webserver_url = format!("http://localhost:{}", snapshot.webserver_port);
+            snapshot.replica_revision_url = format!(
+                "https://dashboard.internetcomputer.org/release/{}",
+                dfx_run!(InfoReplicaRev, ctx, ReplicaRev).unwrap_or_default()
+            );
+            status_url = format!("http://localhost:{}", snapshot.replica_port);
+            snapshot.replica_url = format!("{status_url}/_/dashboard");
+        }
+        "local" => {
+            snapshot.replica_revision_url = "N/A".to_string();
+            snapshot.replica_url = "N/A".to_string();
+            snapshot.webserver_url = "N/A".to_string();
+        }
+        "ic" => {
+            snapshot.replica_revision_url =
+                "https://dashboard.internetcomputer.org/releases".to_string();
+            snapshot.replica_url = "http://ic0.app".to_string();
+            snapshot.webserver_url = "N/A".to_string();
+        }
+        _ => {
+            snapshot.replica_revision_url = "N/A".to_string();
+            snapshot.replica_url = context.provider.clone();
+            snapshot.webserver_url = "N/A".to_string();
+        }
+    }
+    if snapshot.ping.is_ok() {
+        let canister_ids = ProjectDatabase::load(db_path)
+            .map(|db| db.canister_ids_on(&ctx.network))
+            .unwrap_or_default();
+        let request = StatusRequest {
+            dfx: ctx,
+            replica_url: &status_url,
+            canister_ids: &canister_ids,
+        };
+        match FallbackSource::for_network(&ctx.network).fetch_with_source(&request) {
+            Ok((source, info)) => {
+                snapshot.info = Some(info);
+                snapshot.status_source = Ok(source);
+            }
+            Err(e) => snapshot.status_source = Err(e.to_string()),
+        }
+    }
+    snapshot
+}
+
+/// Reloads the database from disk so projects registered from the CLI show up
+/// without restarting, then refreshes every project's dfx.json and rebuilds
+/// the network list from networks.json and the projects' dfx.json files.
+fn poll_projects(
+    ctx: &DfxContext,
+    db_path: &Path,
+    networks_json: &mut Option<PathBuf>,
+) -> Vec<Update> {
+    let mut updates = vec![];
+    let db = ProjectDatabase::update(db_path, |db| {
+        db.refresh();
+        Ok(())
+    })
+    .map(|(db, ())| db)
+    .ok();
+    // a database that can't be saved still lists its networks
+    let loaded = db.clone().or_else(|| ProjectDatabase::load(db_path).ok());
+    if networks_json.is_none() {
+        let ctx = DfxContext {
+            cwd: None,
+            ..ctx.clone()
+        };
+        *networks_json = dfx_run!(InfoNetworksJsonPath, &ctx, NetworksJsonPath)
+            .ok()
+            .or_else(networks::default_networks_json_path);
+    }
+    let networks = networks::discover(
+        networks_json.as_deref(),
+        loaded.as_ref().unwrap_or(&ProjectDatabase::default()),
+    );
+    updates.push(Update::Networks(networks));
+    if let Some(db) = db {
+        updates.push(Update::Projects(db));
+    }
+    updates
+}
+
+/// Scans the discovery roots for projects that are not registered yet.
+fn poll_discovery(config: &DiscoveryConfig, db_path: &Path) -> Option<Update> {
+    if config.roots.is_empty() {
+        return None;
+    }
+    let db = ProjectDatabase::load(db_path).ok()?;
+    Some(Update::Discovered(discovery::discover(&db, config)))
+}
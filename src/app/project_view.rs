@@ -0,0 +1,183 @@
+use crate::{
+    dfx_commands::{DfxCommands, InstallMode},
+    dfx_project::ProjectDatabase,
+    discovery::DiscoveredProject,
+    logs::{LogLine, LogStream},
+};
+use std::{
+    fmt,
+    path::PathBuf,
+    sync::mpsc::{self, TryRecvError},
+};
+
+/// A row of the project view: a whole project, or one of its canisters.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectEntry {
+    pub project: PathBuf,
+    pub canister: Option<String>,
+    /// False for a project found by discovery that can be registered.
+    pub registered: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProjectTask {
+    Build,
+    Deploy,
+    Generate,
+}
+
+impl fmt::Display for ProjectTask {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProjectTask::Build => write!(f, "build"),
+            ProjectTask::Deploy => write!(f, "deploy"),
+            ProjectTask::Generate => write!(f, "generate"),
+        }
+    }
+}
+
+impl ProjectTask {
+    pub fn command(&self, entry: &ProjectEntry, mode: Option<InstallMode>) -> DfxCommands {
+        let canister = entry.canister.clone();
+        match self {
+            ProjectTask::Build => DfxCommands::Build { canister },
+            ProjectTask::Deploy => DfxCommands::Deploy { canister, mode },
+            ProjectTask::Generate => DfxCommands::Generate { canister },
+        }
+    }
+}
+
+/// State of the project view shown in place of the canister info pane, and
+/// of the build/deploy/generate task running in it.
+#[derive(Debug, Default)]
+pub struct ProjectView {
+    pub active: bool,
+    pub selected_index: usize,
+    /// `--mode` passed to the next deploy.
+    pub mode: Option<InstallMode>,
+    /// Output of the running task, disconnected once dfx exits.
+    pub task: Option<mpsc::Receiver<String>>,
+    /// Output of every task run so far, shown in the project logs tab.
+    pub logs: Vec<LogLine>,
+}
+
+impl ProjectView {
+    /// Every registered project followed by its canisters, sorted by path and
+    /// name, then the discovered projects that are not registered.
+    pub fn entries(db: &ProjectDatabase, discovered: &[DiscoveredProject]) -> Vec<ProjectEntry> {
+        let mut projects: Vec<_> = db.projects.iter().collect();
+        projects.sort_by(|a, b| a.0.cmp(b.0));
+        let mut entries = vec![];
+        for (path, data) in projects {
+            entries.push(ProjectEntry {
+                project: path.clone(),
+                canister: None,
+                registered: true,
+            });
+            let mut canisters: Vec<_> = data.canisters.keys().collect();
+            canisters.sort();
+            entries.extend(canisters.into_iter().map(|name| ProjectEntry {
+                project: path.clone(),
+                canister: Some(name.clone()),
+                registered: true,
+            }));
+        }
+        entries.extend(
+            discovered
+                .iter()
+                .filter(|project| !db.projects.contains_key(&project.path))
+                .map(|project| ProjectEntry {
+                    project: project.path.clone(),
+                    canister: None,
+                    registered: false,
+                }),
+        );
+        entries
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.task.is_some()
+    }
+
+    /// Cycles the deploy mode: auto, upgrade, reinstall.
+    pub fn next_mode(&mut self) {
+        self.mode = match self.mode {
+            None => Some(InstallMode::Upgrade),
+            Some(InstallMode::Upgrade) => Some(InstallMode::Reinstall),
+            Some(InstallMode::Reinstall) => None,
+        };
+    }
+
+    /// Moves the running task's output into `logs`. Returns true once the
+    /// task has finished.
+    pub fn collect_output(&mut self) -> bool {
+        let Some(task) = self.task.as_ref() else {
+            return false;
+        };
+        loop {
+            match task.try_recv() {
+                Ok(line) => self.logs.push(LogLine::new(LogStream::Stdout, line)),
+                Err(TryRecvError::Empty) => return false,
+                Err(TryRecvError::Disconnected) => {
+                    self.task = None;
+                    return true;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dfx_project::DfxProjectData;
+
+    #[test]
+    fn test_entries_list_projects_then_canisters() {
+        let mut db = ProjectDatabase::default();
+        let project = |canisters: &str| -> DfxProjectData {
+            serde_json::from_str(&format!(
+                r#"{{ "canisters": {{ {canisters} }}, "canister_ids": {{}} }}"#
+            ))
+            .unwrap()
+        };
+        db.projects.insert(
+            PathBuf::from("/b"),
+            project(r#""web": { "type": "assets" }, "api": { "type": "rust" }"#),
+        );
+        db.projects.insert(
+            PathBuf::from("/a"),
+            project(r#""backend": { "type": "motoko" }"#),
+        );
+        let entries: Vec<_> = ProjectView::entries(&db, &[])
+            .into_iter()
+            .map(|e| (e.project, e.canister))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (PathBuf::from("/a"), None),
+                (PathBuf::from("/a"), Some("backend".to_string())),
+                (PathBuf::from("/b"), None),
+                (PathBuf::from("/b"), Some("api".to_string())),
+                (PathBuf::from("/b"), Some("web".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collect_output_until_disconnected() {
+        let (sender, receiver) = mpsc::channel();
+        let mut view = ProjectView {
+            task: Some(receiver),
+            ..Default::default()
+        };
+        sender.send("Building canisters...".to_string()).unwrap();
+        assert!(!view.collect_output());
+        drop(sender);
+        assert!(view.collect_output());
+        assert!(!view.is_running());
+        assert_eq!(view.logs.len(), 1);
+        assert_eq!(view.logs[0].text, "Building canisters...");
+    }
+}
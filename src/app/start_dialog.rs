@@ -0,0 +1,212 @@
+use crate::{config::StartProfile, replica_supervisor::StartOptions};
+use std::{fmt, path::PathBuf};
+
+/// Rows of the start dialog, top to bottom.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartField {
+    Profile,
+    Project,
+    Clean,
+    Background,
+    Host,
+    ArtificialDelay,
+    EnableBitcoin,
+    EnableCanisterHttp,
+}
+
+pub const START_FIELDS: [StartField; 8] = [
+    StartField::Profile,
+    StartField::Project,
+    StartField::Clean,
+    StartField::Background,
+    StartField::Host,
+    StartField::ArtificialDelay,
+    StartField::EnableBitcoin,
+    StartField::EnableCanisterHttp,
+];
+
+impl fmt::Display for StartField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StartField::Profile => write!(f, "Profile"),
+            StartField::Project => write!(f, "Start in"),
+            StartField::Clean => write!(f, "--clean"),
+            StartField::Background => write!(f, "--background"),
+            StartField::Host => write!(f, "--host"),
+            StartField::ArtificialDelay => write!(f, "--artificial-delay"),
+            StartField::EnableBitcoin => write!(f, "--enable-bitcoin"),
+            StartField::EnableCanisterHttp => write!(f, "--enable-canister-http"),
+        }
+    }
+}
+
+/// The `dfx start` options being edited in the start dialog. Text fields are
+/// kept as typed and only checked when starting or saving.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StartDialog {
+    pub options: StartOptions,
+    /// Directories to start in; `None` is the shared local network.
+    pub projects: Vec<Option<PathBuf>>,
+    pub selected_index: usize,
+    /// Name the options are saved under.
+    pub name: String,
+    pub host: String,
+    pub artificial_delay: String,
+    pub error: Option<String>,
+}
+
+impl StartDialog {
+    pub fn new(options: StartOptions, mut projects: Vec<Option<PathBuf>>) -> Self {
+        if !projects.contains(&options.project_dir) {
+            projects.push(options.project_dir.clone());
+        }
+        StartDialog {
+            host: options.host.clone().unwrap_or_default(),
+            artificial_delay: options
+                .artificial_delay
+                .map(|delay| delay.to_string())
+                .unwrap_or_default(),
+            options,
+            projects,
+            selected_index: 0,
+            name: String::new(),
+            error: None,
+        }
+    }
+
+    pub fn field(&self) -> StartField {
+        START_FIELDS[self.selected_index]
+    }
+
+    pub fn up(&mut self) {
+        self.selected_index = (self.selected_index + START_FIELDS.len() - 1) % START_FIELDS.len();
+    }
+
+    pub fn down(&mut self) {
+        self.selected_index = (self.selected_index + 1) % START_FIELDS.len();
+    }
+
+    /// Whether the selected row takes typed text.
+    pub fn is_text_field(&self) -> bool {
+        matches!(
+            self.field(),
+            StartField::Profile | StartField::Host | StartField::ArtificialDelay
+        )
+    }
+
+    pub fn toggle(&mut self) {
+        let flag = match self.field() {
+            StartField::Clean => &mut self.options.clean,
+            StartField::Background => &mut self.options.background,
+            StartField::EnableBitcoin => &mut self.options.enable_bitcoin,
+            StartField::EnableCanisterHttp => &mut self.options.enable_canister_http,
+            _ => return,
+        };
+        *flag = !*flag;
+    }
+
+    pub fn push(&mut self, c: char) {
+        match self.field() {
+            StartField::Profile => self.name.push(c),
+            StartField::Host => self.host.push(c),
+            StartField::ArtificialDelay => self.artificial_delay.push(c),
+            _ => {}
+        }
+    }
+
+    pub fn pop(&mut self) {
+        match self.field() {
+            StartField::Profile => self.name.pop(),
+            StartField::Host => self.host.pop(),
+            StartField::ArtificialDelay => self.artificial_delay.pop(),
+            _ => None,
+        };
+    }
+
+    /// Left/Right: switches to the previous or next saved profile on the
+    /// profile row, and between directories on the project row.
+    pub fn cycle(&mut self, forward: bool, profiles: &[StartProfile]) {
+        let step = |index: Option<usize>, len: usize| match (index, forward) {
+            (None, true) => 0,
+            (None, false) => len - 1,
+            (Some(i), true) => (i + 1) % len,
+            (Some(i), false) => (i + len - 1) % len,
+        };
+        match self.field() {
+            StartField::Profile if !profiles.is_empty() => {
+                let current = profiles.iter().position(|p| p.name == self.name);
+                let profile = &profiles[step(current, profiles.len())];
+                let selected_index = self.selected_index;
+                *self = StartDialog::new(profile.options.clone(), self.projects.clone());
+                self.name = profile.name.clone();
+                self.selected_index = selected_index;
+            }
+            StartField::Project => {
+                let current = self
+                    .projects
+                    .iter()
+                    .position(|p| *p == self.options.project_dir);
+                self.options.project_dir =
+                    self.projects[step(current, self.projects.len())].clone();
+            }
+            _ => {}
+        }
+    }
+
+    /// The options to start with, or what is wrong with the text fields.
+    pub fn options(&self) -> Result<StartOptions, String> {
+        let host = self.host.trim();
+        let delay = self.artificial_delay.trim();
+        let artificial_delay = if delay.is_empty() {
+            None
+        } else {
+            Some(
+                delay
+                    .parse()
+                    .map_err(|_| format!("'{delay}' is not a delay in milliseconds"))?,
+            )
+        };
+        Ok(StartOptions {
+            host: (!host.is_empty()).then(|| host.to_string()),
+            artificial_delay,
+            ..self.options.clone()
+        })
+    }
+}
+
+#[test]
+fn test_start_dialog_edits_options() {
+    let profiles = vec![StartProfile {
+        name: "clean with bitcoin".to_string(),
+        options: StartOptions {
+            clean: true,
+            enable_bitcoin: true,
+            ..Default::default()
+        },
+    }];
+    let project = Some(PathBuf::from("/projects/hello"));
+    let mut dialog = StartDialog::new(StartOptions::default(), vec![None, project.clone()]);
+    dialog.cycle(true, &profiles);
+    assert_eq!(dialog.name, "clean with bitcoin");
+    assert!(dialog.options.clean && dialog.options.enable_bitcoin);
+
+    dialog.down();
+    dialog.cycle(true, &profiles);
+    assert_eq!(dialog.options.project_dir, project);
+    dialog.down();
+    dialog.toggle();
+    assert!(!dialog.options.clean);
+
+    while dialog.field() != StartField::ArtificialDelay {
+        dialog.down();
+    }
+    "20x".chars().for_each(|c| dialog.push(c));
+    assert!(dialog.options().is_err());
+    dialog.pop();
+    let options = dialog.options().unwrap();
+    assert_eq!(options.artificial_delay, Some(20));
+    assert_eq!(
+        options.args(),
+        ["start", "--enable-bitcoin", "--artificial-delay", "20"]
+    );
+}
@@ -1,45 +1,117 @@
-use crate::{dfx_commands::*, dfx_project::ProjectDatabase, parse_replica_dashboard::ReplicaInfo};
-use serde::Deserialize;
-use std::{sync::mpsc, time::Duration};
+use crate::{
+    app::{
+        actions::{ActionEvent, Actions, Dialog},
+        caller::{CallKind, Caller},
+        canister_tree::{CanisterTree, TreeRow},
+        history::History,
+        keymap::{Context, Keymap},
+        log_files::{FilePicker, FileSource},
+        log_store::{self, ExportFormat, LogStore, LOG_BUFFER_LEN},
+        log_viewer::{CanisterFilter, LogViewer},
+        poller::{PollContext, Poller, Source, Update},
+        project_view::ProjectView,
+        start_dialog::StartDialog,
+    },
+    config::{Config, RefreshConfig},
+    dfx_commands::*,
+    dfx_project::ProjectDatabase,
+    discovery::DiscoveredProject,
+    logs::{LogLevel, LogLine},
+    networks::NetworkInfo,
+    parse_replica_dashboard::{CanisterInfo, ReplicaInfo},
+    replica_supervisor::{
+        AutoRestart, Crash, ProcessState, ReplicaSupervisor, RunningReplica, StartOptions,
+        MAX_RESTARTS,
+    },
+};
+use chrono::Local;
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant},
+};
 
 #[derive(Debug, Default)]
 pub struct AppState {
-    pub collected_logs: Vec<String>,
+    pub actions: Actions,
+    pub auto_restart: AutoRestart,
+    pub caller: Caller,
+    pub canister_tree: CanisterTree,
+    /// Output of the replica and of canister actions, oldest lines spilled to disk.
+    pub collected_logs: LogStore,
+    pub config: Config,
     pub db: ProjectDatabase,
+    pub dialog: Option<Dialog>,
+    /// Projects below the discovery roots that are not registered.
+    pub discovered: Vec<DiscoveredProject>,
+    /// Open while showing the keys of the context it was opened in.
+    pub help: Option<Context>,
+    /// Cycles, memory and execution round of every canister over the last refreshes.
+    pub history: History,
     pub identities: Vec<String>,
     pub identity_selection_menu_active: bool,
-    // pub logfile_selection_menu_active: Option<PathBuf>,
+    pub keymap: Keymap,
+    /// The last unexpected exit of the supervised replica, until it is started again.
+    pub last_crash: Option<Crash>,
+    /// Open while choosing a log file to follow.
+    pub file_picker: Option<FilePicker>,
+    /// Log files followed in their own tabs.
+    pub file_sources: Vec<FileSource>,
+    pub log_viewer: LogViewer,
     pub logs_pane: LogsPane,
-    // pub logs_source: LogsSource,
     pub network_selection_menu_active: bool,
-    pub networks: Vec<String>,
+    pub networks: Vec<NetworkInfo>,
     pub path_to_dfx: String,
+    pub poller: Option<Poller>,
+    pub project_view: ProjectView,
     pub refresh_interval: Duration,
     pub replica: Replica,
-    pub replica_logs_reciver: Option<mpsc::Receiver<String>>,
-    pub selected_canister_index: usize,
-    // pub selected_fs_index: usize,
+    /// The `dfx start` process started from the dashboard, if any.
+    pub replica_process: Option<ReplicaSupervisor>,
     pub selected_identity: String,
-    pub selected_identity_cycles: Option<String>,
-    pub selected_identity_icp: Option<String>,
+    pub selected_identity_cycles: Option<Result<Balance<u128>, DfxError>>,
+    pub selected_identity_icp: Option<Result<Balance<IcpBalance>, DfxError>>,
     pub selected_identity_index: usize,
-    pub selected_identity_principal: Option<String>,
+    pub selected_identity_principal: Option<Result<String, DfxError>>,
+    pub selected_identity_wallet_cycles: Option<Result<Balance<u128>, DfxError>>,
     pub selected_network: String,
     pub selected_network_index: usize,
+    /// Set when the selected network is defined in a project's dfx.json.
+    pub selected_network_project: Option<PathBuf>,
 }
 
-#[derive(Default, PartialEq, Debug)]
-pub enum LogsSource {
+/// Tabs of the logs pane.
+#[derive(Default, PartialEq, Debug, Clone, Copy)]
+pub enum LogsPane {
     #[default]
     Replica,
-    // File(Option<PathBuf>),
+    Canister,
+    Project,
+    /// One of [`AppState::file_sources`].
+    File(usize),
 }
 
-#[derive(Default, PartialEq, Debug)]
-pub enum LogsPane {
-    #[default]
-    ReplicaLogs,
-    CanisterLogs,
+impl LogsPane {
+    /// The tab to the right, given how many files are followed.
+    pub fn next(&self, files: usize) -> LogsPane {
+        match self {
+            LogsPane::Replica => LogsPane::Canister,
+            LogsPane::Canister => LogsPane::Project,
+            LogsPane::Project if files > 0 => LogsPane::File(0),
+            LogsPane::File(index) if index + 1 < files => LogsPane::File(index + 1),
+            LogsPane::Project | LogsPane::File(_) => LogsPane::Replica,
+        }
+    }
+
+    pub fn previous(&self, files: usize) -> LogsPane {
+        match self {
+            LogsPane::Replica if files > 0 => LogsPane::File(files - 1),
+            LogsPane::Replica => LogsPane::Project,
+            LogsPane::Canister => LogsPane::Replica,
+            LogsPane::Project => LogsPane::Canister,
+            LogsPane::File(0) => LogsPane::Project,
+            LogsPane::File(index) => LogsPane::File(index - 1),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Default, Clone)]
@@ -63,111 +135,617 @@ pub struct Replica {
     pub replica_revision_url: String,
     pub replica_url: String,
     pub ping: Option<PingOutput>,
+    pub ping_error: Option<DfxError>,
     pub webserver_url: String,
     pub state: LocalReplicaState,
     pub info: Option<ReplicaInfo>,
+    pub status_source: Option<Result<&'static str, String>>,
     pub webserver_port: String,
     pub replica_port: String,
 }
 
-#[derive(Deserialize, Debug, PartialEq, Default, Clone)]
-pub struct PingOutput {
-    pub ic_api_version: String,
-    pub replica_health_status: String,
-    pub root_key: Vec<u8>,
-    pub certified_height: Option<u64>,
-    pub impl_hash: Option<String>,
-}
-
 impl AppState {
-    pub fn new(path_to_dfx: &str, db: ProjectDatabase) -> Self {
+    pub fn new(path_to_dfx: &str, db: ProjectDatabase, config: Config) -> Self {
         let mut s = Self::default();
-        s.selected_identity = DfxCommands::IdentityWhoami.run(&s, path_to_dfx);
-        s.selected_network = "local".to_string();
-        s.refresh_interval = Duration::from_millis(1500);
-        s.networks = vec!["local".to_string(), "ic".to_string()];
         s.path_to_dfx = path_to_dfx.to_string();
+        s.selected_network = config.default_network.clone();
+        s.selected_identity = match &config.default_identity {
+            Some(identity) => identity.clone(),
+            None => dfx_run!(IdentityWhoami, &s.dfx_context(), Identity)
+                .unwrap_or_else(|_| "default".to_string()),
+        };
+        s.refresh_interval = Duration::from_millis(config.refresh.replica_ms);
+        s.keymap = config.keymap().unwrap_or_default();
+        s.auto_restart.enabled = config.auto_restart;
+        s.config = config;
+        s.networks = NetworkInfo::builtin();
+        s.poller = Some(Poller::spawn(s.poll_context(), db.db_path.clone()));
+        s.collected_logs =
+            LogStore::with_spill_file(LOG_BUFFER_LEN, db.db_path.with_file_name("replica.log"));
         s.db = db;
-        if DfxCommands::Ping.run(&s, path_to_dfx).contains("Error") {
-            s.replica.state = LocalReplicaState::NotRunning;
-        } else {
-            s.replica.state = LocalReplicaState::Running;
-        }
+        s.attach_replica();
         s
     }
 
-    pub fn refresh(&mut self, path_to_dfx: &str) {
-        if self.replica_logs_reciver.is_some() {
-            while let Ok(log) = self.replica_logs_reciver.as_ref().unwrap().try_recv() {
-                self.collected_logs.push(log);
+    pub fn dfx_context(&self) -> DfxContext {
+        DfxContext {
+            path_to_dfx: self.path_to_dfx.clone(),
+            identity: self.selected_identity.trim().to_string(),
+            network: self.selected_network.clone(),
+            timeout: DEFAULT_TIMEOUT,
+            cwd: self.selected_network_project.clone(),
+        }
+    }
+
+    pub fn poll_context(&self) -> PollContext {
+        PollContext {
+            dfx: self.dfx_context(),
+            provider: self
+                .selected_network_info()
+                .and_then(|n| n.provider.split(", ").next())
+                .unwrap_or_default()
+                .to_string(),
+            refresh: RefreshConfig {
+                replica_ms: self.refresh_interval.as_millis() as u64,
+                ..self.config.refresh
+            },
+            discovery: self.config.discovery.clone(),
+        }
+    }
+
+    pub fn selected_network_info(&self) -> Option<&NetworkInfo> {
+        self.networks.iter().find(|n| {
+            n.name == self.selected_network && n.project_dir() == self.selected_network_project
+        })
+    }
+
+    /// Applies whatever the background workers produced since the last call.
+    /// Never blocks, so it is safe to call on every frame.
+    pub fn refresh(&mut self) {
+        self.supervise_replica();
+        for source in self.file_sources.iter_mut() {
+            source.collect_lines();
+        }
+        if let Some(e) = self.collected_logs.spill_error.take() {
+            self.collected_logs.push(LogLine::dfx_top(
+                LogLevel::Warn,
+                format!("[dfx-top] no longer writing old log lines to {e}"),
+            ));
+        }
+        self.caller.collect_replies();
+        while let Some(event) = self.actions.try_recv() {
+            match event {
+                ActionEvent::Log(line) => self
+                    .collected_logs
+                    .push(LogLine::dfx_top(LogLevel::Info, line)),
+                ActionEvent::Finished => self.request_refresh(Source::Replica),
+            }
+        }
+        if self.project_view.collect_output() {
+            // pick up new canister ids, then their status
+            self.request_refresh(Source::Projects);
+            self.request_refresh(Source::Replica);
+        }
+        let Some(poller) = self.poller.as_ref() else {
+            return;
+        };
+        poller.set_context(self.poll_context());
+        let generation = poller.generation();
+        let mut messages = vec![];
+        while let Some(message) = poller.try_recv() {
+            messages.push(message);
+        }
+        for message in messages {
+            self.apply_update(message.update, message.generation == generation);
+        }
+    }
+
+    /// Collects the output of the `dfx start` process, records a crash when
+    /// it exits without being asked to and restarts it if enabled.
+    fn supervise_replica(&mut self) {
+        if self.auto_restart.due() {
+            self.restart_after_crash();
+        }
+        let Some(process) = self.replica_process.as_mut() else {
+            return;
+        };
+        self.collected_logs.extend(process.lines());
+        let status = match process.state() {
+            Ok(ProcessState::Running { .. }) => {
+                self.auto_restart.running_for(process.uptime());
+                return;
+            }
+            Ok(ProcessState::Exited(Some(status)))
+                if status.success() && process.options.background =>
+            {
+                let project_dirs: Vec<PathBuf> =
+                    process.options.project_dir.iter().cloned().collect();
+                if let Some(running) = RunningReplica::find(&project_dirs) {
+                    process.detach(running.pid);
+                    self.collected_logs.push(LogLine::dfx_top(
+                        LogLevel::Info,
+                        format!(
+                            "[dfx-top] dfx start --background returned, the replica keeps running as pid {}",
+                            running.pid
+                        ),
+                    ));
+                    return;
+                }
+                self.collected_logs.push(LogLine::dfx_top(
+                    LogLevel::Warn,
+                    "[dfx-top] dfx start --background returned but no running replica was found"
+                        .to_string(),
+                ));
+                Some(status)
+            }
+            Ok(ProcessState::Exited(status)) => status,
+            Err(e) => {
+                self.collected_logs.push(LogLine::dfx_top(
+                    LogLevel::Error,
+                    format!("[dfx-top] lost track of dfx start: {e}"),
+                ));
+                None
+            }
+        };
+        // the reader threads may still hold the last lines
+        self.collected_logs.extend(process.lines());
+        let crash = process.crash(status);
+        self.collected_logs.push(LogLine::dfx_top(
+            LogLevel::Error,
+            format!(
+                "[dfx-top] dfx start (pid {}) crashed: {}",
+                crash.pid,
+                crash.reason()
+            ),
+        ));
+        self.last_crash = Some(crash);
+        self.schedule_restart();
+        self.replica_process = None;
+        self.replica.state = LocalReplicaState::NotRunning;
+        self.request_refresh(Source::Replica);
+    }
+
+    fn schedule_restart(&mut self) {
+        let message = match self.auto_restart.schedule() {
+            Some(backoff) => format!(
+                "[dfx-top] restarting dfx start in {}s (attempt {} of {MAX_RESTARTS})",
+                backoff.as_secs(),
+                self.auto_restart.restarts
+            ),
+            None if self.auto_restart.enabled => {
+                format!("[dfx-top] not restarting dfx start after {MAX_RESTARTS} crashes in a row")
+            }
+            None => return,
+        };
+        self.collected_logs
+            .push(LogLine::dfx_top(LogLevel::Warn, message));
+    }
+
+    /// Starts the crashed replica again with its options, except `--clean`.
+    fn restart_after_crash(&mut self) {
+        let Some(crash) = &self.last_crash else {
+            return;
+        };
+        let options = StartOptions {
+            clean: false,
+            ..crash.options.clone()
+        };
+        if !self.launch_replica(options) {
+            self.schedule_restart();
+        }
+    }
+
+    /// Turns restarting crashed replicas on or off.
+    pub fn toggle_auto_restart(&mut self) {
+        self.auto_restart.enabled = !self.auto_restart.enabled;
+        if !self.auto_restart.enabled {
+            self.auto_restart.cancel();
+        }
+    }
+
+    /// Adopts a local replica started outside dfx-top, for its logs and to
+    /// stop or restart it. Returns whether one was found.
+    pub fn attach_replica(&mut self) -> bool {
+        if self.replica_process.is_some() || self.selected_network != "local" {
+            return false;
+        }
+        let mut project_dirs: Vec<PathBuf> =
+            self.selected_network_project.iter().cloned().collect();
+        project_dirs.extend(std::env::current_dir());
+        let mut registered: Vec<PathBuf> = self.db.projects.keys().cloned().collect();
+        registered.sort();
+        project_dirs.extend(registered);
+        let Some(running) = RunningReplica::find(&project_dirs) else {
+            return false;
+        };
+        self.replica_process = Some(ReplicaSupervisor::adopt(&running));
+        self.replica.state = LocalReplicaState::Running;
+        true
+    }
+
+    /// Opens the start dialog for the project of the selected network.
+    pub fn open_start_dialog(&mut self) {
+        let mut projects: Vec<Option<PathBuf>> =
+            self.db.projects.keys().cloned().map(Some).collect();
+        projects.sort();
+        projects.insert(0, None);
+        let options = StartOptions {
+            project_dir: self.selected_network_project.clone(),
+            ..Default::default()
+        };
+        self.dialog = Some(Dialog::StartReplica(StartDialog::new(options, projects)));
+    }
+
+    /// Starts the saved profile at `index`, if there is one.
+    pub fn start_profile(&mut self, index: usize) {
+        if let Some(profile) = self.config.start_profiles.get(index) {
+            let options = profile.options.clone();
+            self.start_replica(options);
+        }
+    }
+
+    /// Starts `dfx start` under supervision. The logs of previous runs stay,
+    /// an earlier crash is dismissed.
+    pub fn start_replica(&mut self, options: StartOptions) {
+        self.last_crash = None;
+        self.auto_restart.cancel();
+        self.launch_replica(options);
+    }
+
+    fn launch_replica(&mut self, options: StartOptions) -> bool {
+        let started = match ReplicaSupervisor::start(&self.dfx_context(), options) {
+            Ok(process) => {
+                self.replica_process = Some(process);
+                self.replica.state = LocalReplicaState::Running;
+                true
+            }
+            Err(e) => {
+                self.collected_logs.push(LogLine::dfx_top(
+                    LogLevel::Error,
+                    format!("[dfx-top] failed to run dfx start: {e}"),
+                ));
+                false
             }
+        };
+        self.request_refresh(Source::Replica);
+        started
+    }
+
+    /// Stops the supervised replica, or any local replica when dfx-top did not
+    /// start it, and keeps its last lines.
+    pub fn stop_replica(&mut self) {
+        self.auto_restart.cancel();
+        if self.replica_process.is_none() {
+            self.attach_replica();
         }
-        self.selected_identity_principal =
-            Some(DfxCommands::IdentityGetPrincipal.run(self, path_to_dfx));
-        self.selected_identity_icp = Some(DfxCommands::LedgerBalance.run(self, path_to_dfx));
-        self.selected_identity_cycles = Some(DfxCommands::CyclesBalance.run(self, path_to_dfx));
-        if self.identity_selection_menu_active {
-            self.identities = DfxCommands::IdentityList
-                .run(self, &self.path_to_dfx)
-                .split("\n")
-                .map(|s| s.to_string())
-                .collect();
+        match self.replica_process.take() {
+            Some(process) => self
+                .collected_logs
+                .extend(process.stop(&self.dfx_context())),
+            None => {
+                // stopping blindly could hit a replica of another project
+                self.collected_logs.push(LogLine::dfx_top(
+                    LogLevel::Warn,
+                    "[dfx-top] no local replica process found, nothing to stop".to_string(),
+                ));
+                return;
+            }
+        }
+        self.replica.state = LocalReplicaState::NotRunning;
+        self.request_refresh(Source::Replica);
+    }
+
+    /// Restarts the replica with the options it was started with, except
+    /// that its state is kept.
+    pub fn restart_replica(&mut self) {
+        let options = match &self.replica_process {
+            Some(process) => StartOptions {
+                clean: false,
+                ..process.options.clone()
+            },
+            None => StartOptions {
+                project_dir: self.selected_network_project.clone(),
+                ..Default::default()
+            },
+        };
+        self.stop_replica();
+        self.start_replica(options);
+    }
+
+    /// Registers the discovered project selected in the project view.
+    pub fn register_discovered(&mut self) {
+        let Some(entry) = ProjectView::entries(&self.db, &self.discovered)
+            .into_iter()
+            .nth(self.project_view.selected_index)
+            .filter(|entry| !entry.registered)
+        else {
+            return;
+        };
+        // the poller may have saved since the last update
+        let registered =
+            ProjectDatabase::update(&self.db.db_path, |db| db.register(&entry.project));
+        match registered {
+            Ok((db, (path, _))) => {
+                self.db = db;
+                self.discovered
+                    .retain(|project| project.path != entry.project);
+                self.collected_logs.push(LogLine::dfx_top(
+                    LogLevel::Info,
+                    format!("[dfx-top] registered the project at {}", path.display()),
+                ));
+                self.request_refresh(Source::Projects);
+                self.request_refresh(Source::Replica);
+            }
+            Err(e) => self.collected_logs.push(LogLine::dfx_top(
+                LogLevel::Warn,
+                format!(
+                    "[dfx-top] could not register {}: {e:#}",
+                    entry.project.display()
+                ),
+            )),
         }
+    }
+
+    /// Whether keys go to a text field, dialog or the help instead of the dashboard.
+    pub fn captures_keys(&self) -> bool {
+        self.caller.input.is_some()
+            || self.dialog.is_some()
+            || self.log_viewer.prompt.is_some()
+            || self.help.is_some()
+    }
 
-        let ping = &DfxCommands::Ping.run(self, path_to_dfx);
-        if ping.contains("Error")
-            && self.selected_network == "local"
-            && self.replica_logs_reciver.is_none()
-        {
-            self.replica.state = LocalReplicaState::NotRunning;
+    /// Where key presses go when nothing captures them.
+    pub fn context(&self) -> Context {
+        if self.file_picker.is_some() {
+            Context::FilePicker
+        } else if self.network_selection_menu_active {
+            Context::NetworkMenu
+        } else if self.identity_selection_menu_active {
+            Context::IdentityMenu
+        } else if self.project_view.active {
+            Context::Projects
+        } else if self.caller.active {
+            Context::Caller
         } else {
-            self.replica.state = LocalReplicaState::Running;
-        }
-        self.replica.ping = serde_json::from_str(ping).ok();
-        match (self.selected_network.clone(), self.replica.state.clone()) {
-            (network, LocalReplicaState::Running) if network == "local" => {
-                self.replica.webserver_port = DfxCommands::InfoWebserverPort
-                    .run(self, path_to_dfx)
-                    .trim()
-                    .to_string();
-                self.replica.replica_port = DfxCommands::InfoReplicaPort
-                    .run(self, path_to_dfx)
-                    .trim()
-                    .to_string();
-                self.replica.webserver_url =
-                    format!("http://localhost:{}", self.replica.webserver_port);
-                self.replica.replica_revision_url = format!(
-                    "https://dashboard.internetcomputer.org/release/{}",
-                    DfxCommands::InfoReplicaRev
-                        .run(self, path_to_dfx)
-                        .trim()
-                        .to_string()
-                );
-                self.replica.replica_url =
-                    format!("http://localhost:{}/_/dashboard", self.replica.replica_port);
-                if let Ok(data) = reqwest::blocking::get(self.replica.replica_url.as_str()) {
-                    let dashboard_html = data.text().unwrap();
-                    self.replica.info =
-                        ReplicaInfo::parse_from_html_dashboard(&dashboard_html).ok();
+            Context::Canisters
+        }
+    }
+
+    /// Lines of the current logs tab that pass the log viewer's filters.
+    pub fn shown_logs(&self) -> Vec<&LogLine> {
+        let canister = match self.logs_pane {
+            LogsPane::Canister => match self.canister_filter() {
+                Some(canister) => Some(canister),
+                None => return vec![],
+            },
+            _ => None,
+        };
+        self.pane_logs()
+            .filter(|line| canister.as_ref().is_none_or(|c| c.matches(line)))
+            .filter(|line| self.log_viewer.shows(line))
+            .collect()
+    }
+
+    /// Every line of the current logs tab, before any filter.
+    pub fn pane_logs(&self) -> Box<dyn Iterator<Item = &LogLine> + '_> {
+        match self.logs_pane {
+            LogsPane::Replica | LogsPane::Canister => Box::new(self.collected_logs.iter()),
+            LogsPane::Project => Box::new(self.project_view.logs.iter()),
+            LogsPane::File(index) => match self.file_sources.get(index) {
+                Some(source) => Box::new(source.lines.iter()),
+                None => Box::new(std::iter::empty()),
+            },
+        }
+    }
+
+    /// Name of a logs tab: a fixed one, or the name a file is followed as.
+    pub fn logs_pane_name(&self, pane: LogsPane) -> String {
+        match pane {
+            LogsPane::Replica => "replica".to_string(),
+            LogsPane::Canister => "canister".to_string(),
+            LogsPane::Project => "project".to_string(),
+            LogsPane::File(index) => self
+                .file_sources
+                .get(index)
+                .map_or_else(|| "file".to_string(), |source| source.name.clone()),
+        }
+    }
+
+    /// Follows `path` in a new tab named `name`, or the file name, and
+    /// switches to it. A file that is already followed is only switched to.
+    pub fn follow_file(&mut self, name: Option<String>, path: PathBuf) {
+        let index = match self.file_sources.iter().position(|s| s.path == path) {
+            Some(index) => index,
+            None => {
+                let name = name.unwrap_or_else(|| {
+                    path.file_name().map_or_else(
+                        || path.display().to_string(),
+                        |n| n.to_string_lossy().into(),
+                    )
+                });
+                self.file_sources.push(FileSource::follow(name, path));
+                self.file_sources.len() - 1
+            }
+        };
+        self.logs_pane = LogsPane::File(index);
+        self.log_viewer.follow();
+    }
+
+    /// Stops following the file of the current tab.
+    pub fn unfollow_file(&mut self) {
+        if let LogsPane::File(index) = self.logs_pane {
+            if index < self.file_sources.len() {
+                self.file_sources.remove(index);
+            }
+            self.logs_pane = self.logs_pane.previous(self.file_sources.len());
+            self.log_viewer.follow();
+        }
+    }
+
+    /// Writes the current logs tab to a new file next to the projects
+    /// database, only the lines passing the filters if `filtered`.
+    pub fn export_logs(&mut self, format: ExportFormat, filtered: bool) {
+        let name: String = self
+            .logs_pane_name(self.logs_pane)
+            .chars()
+            .map(|c| {
+                if c.is_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
                 }
+            })
+            .collect();
+        let path = self.db.db_path.with_file_name(format!(
+            "{name}-logs-{}.{}",
+            Local::now().format("%Y%m%d-%H%M%S"),
+            format.extension()
+        ));
+        let result = if filtered {
+            log_store::export(self.shown_logs(), &path, format)
+        } else {
+            log_store::export(self.pane_logs(), &path, format)
+        };
+        self.collected_logs.push(match result {
+            Ok(count) => LogLine::dfx_top(
+                LogLevel::Info,
+                format!("[dfx-top] exported {count} lines to {}", path.display()),
+            ),
+            Err(e) => LogLine::dfx_top(
+                LogLevel::Error,
+                format!("[dfx-top] failed to export logs to {}: {e}", path.display()),
+            ),
+        });
+    }
+
+    /// The selected canister's id and, if a registered project has it, its name.
+    fn canister_filter(&self) -> Option<CanisterFilter> {
+        let canister_id = self.selected_canister()?.canister_id.clone();
+        let name = self
+            .db
+            .get_info(&canister_id, &self.selected_network)
+            .map(|(_, name, _)| name);
+        Some(CanisterFilter { canister_id, name })
+    }
+
+    /// Rows of the canister tree for the replica's canisters.
+    pub fn canister_rows(&self) -> Vec<TreeRow> {
+        match &self.replica.info {
+            Some(info) => {
+                self.canister_tree
+                    .rows(&info.canisters, &self.db, &self.selected_network)
             }
+            None => vec![],
+        }
+    }
+
+    /// The canister selected in the tree, `None` while a section is selected.
+    pub fn selected_canister(&self) -> Option<&CanisterInfo> {
+        let index = self
+            .canister_tree
+            .selected_canister(&self.canister_rows())?;
+        self.replica.info.as_ref()?.canisters.get(index)
+    }
+
+    /// Whether the selected canister shows no exports because the status source
+    /// couldn't read them (no dashboard and no `candid:service` metadata).
+    pub fn exports_unavailable(&self) -> bool {
+        let from_dashboard = matches!(self.replica.status_source, Some(Ok("dashboard")));
+        !from_dashboard && self.callable_methods().is_empty()
+    }
+
+    /// Exported functions of the selected canister that can be called, queries first.
+    pub fn callable_methods(&self) -> Vec<(String, CallKind)> {
+        let Some(canister) = self.selected_canister() else {
+            return vec![];
+        };
+        let exports = &canister.exports;
+        let queries = exports
+            .exported_query_functions
+            .iter()
+            .map(|m| (m.clone(), CallKind::Query));
+        let updates = exports
+            .exported_update_functions
+            .iter()
+            .map(|m| (m.clone(), CallKind::Update));
+        queries.chain(updates).collect()
+    }
 
-            (network, LocalReplicaState::NotRunning) if network == "local" => {
-                self.replica.replica_revision_url = "N/A".to_string();
-                self.replica.replica_url = "N/A".to_string();
-                self.replica.webserver_url = "N/A".to_string();
-                self.replica.info = None;
+    fn apply_update(&mut self, update: Update, current: bool) {
+        match update {
+            Update::Identity(snapshot) if current => {
+                self.selected_identity_principal = Some(snapshot.principal);
+                self.selected_identity_icp = Some(snapshot.icp);
+                self.selected_identity_cycles = Some(snapshot.cycles);
+                self.selected_identity_wallet_cycles = Some(snapshot.wallet_cycles);
+            }
+            Update::Identities(identities) => {
+                self.selected_identity_index = self
+                    .selected_identity_index
+                    .min(identities.len().saturating_sub(1));
+                self.identities = identities;
+            }
+            Update::Replica(snapshot) if current => {
+                // a replica started elsewhere came up
+                if snapshot.ping.is_ok() && self.replica_process.is_none() {
+                    self.attach_replica();
+                }
+                if snapshot.ping.is_err()
+                    && self.selected_network == "local"
+                    && self.replica_process.is_none()
+                {
+                    self.replica.state = LocalReplicaState::NotRunning;
+                } else {
+                    self.replica.state = LocalReplicaState::Running;
+                }
+                let snapshot = *snapshot;
+                match snapshot.ping {
+                    Ok(ping) => {
+                        self.replica.ping = Some(ping);
+                        self.replica.ping_error = None;
+                    }
+                    Err(e) => {
+                        self.replica.ping = None;
+                        self.replica.ping_error = Some(e);
+                    }
+                }
+                self.replica.webserver_port = snapshot.webserver_port;
+                self.replica.replica_port = snapshot.replica_port;
+                self.replica.webserver_url = snapshot.webserver_url;
+                self.replica.replica_url = snapshot.replica_url;
+                self.replica.replica_revision_url = snapshot.replica_revision_url;
+                self.replica.info = snapshot.info;
+                if let Some(info) = &self.replica.info {
+                    self.history.record(info, Instant::now());
+                }
+                self.replica.status_source = Some(snapshot.status_source);
+            }
+            Update::Projects(db) => {
+                self.db = db;
+            }
+            Update::Discovered(discovered) => {
+                self.discovered = discovered;
             }
-            (network, _) if network == "ic" => {
-                self.replica.replica_revision_url =
-                    "https://dashboard.internetcomputer.org/releases".to_string();
-                self.replica.replica_url = "http://ic0.app".to_string();
-                self.replica.webserver_url = "N/A".to_string();
-                self.replica.info = None;
+            // don't reshuffle the menu while the user is browsing it
+            Update::Networks(networks) if !self.network_selection_menu_active => {
+                self.networks = networks;
+                self.selected_network_index = self
+                    .networks
+                    .iter()
+                    .position(|n| {
+                        n.name == self.selected_network
+                            && n.project_dir() == self.selected_network_project
+                    })
+                    .unwrap_or(0);
             }
-            _ => {}
+            _ => {} // computed for a selection that is no longer active
+        }
+    }
+
+    /// Asks the background workers to poll `source` right away.
+    pub fn request_refresh(&self, source: Source) {
+        if let Some(poller) = self.poller.as_ref() {
+            poller.request_refresh(source);
         }
-        self.db.refresh().unwrap();
-        self.db.save().unwrap();
     }
 }
@@ -0,0 +1,94 @@
+use crate::app::caller::CallerFocus;
+use crate::app::state::AppState;
+use crate::app::widgets::style::ListItem::{Empty, Header, Item, Last};
+use ratatui::{prelude::*, text::Span, widgets::*};
+
+use super::style::WidgetStyle;
+
+impl AppState {
+    pub fn widget_caller(&self) -> Paragraph<'_> {
+        let Some(canister) = self.selected_canister() else {
+            return Paragraph::new(Span::raw("No canister selected."))
+                .block(self.apply_style(WidgetStyle::Caller));
+        };
+        let mut lines = vec![];
+        lines.push(Header.build("Canister ID: ", &canister.canister_id));
+        lines.push(Empty.build("", ""));
+
+        let methods = self.callable_methods();
+        lines.push(Header.build("Methods", ""));
+        if self.exports_unavailable() {
+            lines.push(Last.build("", "exports unavailable from this source"));
+        } else if methods.is_empty() {
+            lines.push(Last.build("", "no exported functions"));
+        }
+        for (idx, (method, kind)) in methods.iter().enumerate() {
+            let style = if self.caller.focus == CallerFocus::Methods
+                && idx == self.caller.selected_method_index
+            {
+                self.style_selected()
+            } else {
+                self.style_unselected()
+            };
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {kind:<7}"), Style::default().fg(Color::DarkGray)),
+                Span::styled(method.clone(), style),
+            ]));
+        }
+        lines.push(Empty.build("", ""));
+
+        if let Some(input) = &self.caller.input {
+            let method = methods
+                .get(self.caller.selected_method_index)
+                .map(|(m, _)| m.as_str())
+                .unwrap_or_default();
+            lines.push(Header.build(&format!("Arguments for {method}: "), &format!("{input}█")));
+            lines.push(Last.build("", "Candid, e.g. (\"world\", 42) [Enter] call [Esc] cancel"));
+            lines.push(Empty.build("", ""));
+        }
+
+        let history = self.caller.history_of(&canister.canister_id);
+        if let Some(latest) = history.first() {
+            lines.push(Header.build("Result of ", &latest.method));
+            match &latest.reply {
+                None => lines.push(Last.build("", "waiting for reply...")),
+                Some(Ok(reply)) => {
+                    for line in reply.lines() {
+                        lines.push(Item.build("", line));
+                    }
+                }
+                Some(Err(e)) => lines.push(Last.build_error("Rejected: ", e)),
+            }
+            lines.push(Empty.build("", ""));
+        }
+
+        if !history.is_empty() {
+            lines.push(Header.build("History", ""));
+        }
+        for (idx, record) in history.iter().enumerate() {
+            let status = match &record.reply {
+                None => "…",
+                Some(Ok(_)) => "ok",
+                Some(Err(_)) => "err",
+            };
+            let style = if self.caller.focus == CallerFocus::History
+                && idx == self.caller.selected_history_index
+            {
+                self.style_selected()
+            } else {
+                self.style_unselected()
+            };
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!("  {} {status:<3} ", record.started.format("%H:%M:%S")),
+                    Style::default().fg(Color::DarkGray),
+                ),
+                Span::styled(format!("{} {}", record.method, record.args), style),
+            ]));
+        }
+
+        Paragraph::new(Text::from(lines))
+            .wrap(Wrap { trim: false })
+            .block(self.apply_style(WidgetStyle::Caller))
+    }
+}
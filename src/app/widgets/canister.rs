@@ -1,72 +1,123 @@
+use crate::app::canister_tree::{Group, SortBy, TreeRow};
+use crate::app::keymap::Action;
 use crate::app::state::AppState;
 use crate::app::widgets::style::ListItem::{Empty, Header, Item, Last};
+use crate::metrics::{display, Bytes, Cycles, Seconds};
 use ratatui::{prelude::*, text::Span, widgets::*};
 
 use super::style::WidgetStyle;
+use std::path::Path;
 
 impl AppState {
-    pub fn widget_canisters_list(&self) -> Paragraph {
+    pub fn widget_canisters_list(&self) -> Paragraph<'_> {
         let mut lines = vec![];
         if self.replica.info.is_none() {
             // return all canisters
             return Paragraph::new(Span::raw("Loading..."))
                 .block(self.apply_style(WidgetStyle::Canisters));
         }
-        if self.replica.info.clone().unwrap().canisters.len() == 0 {
+        if self.replica.info.clone().unwrap().canisters.is_empty() {
             // return all canisters
             return Paragraph::new(Span::raw("No canisters found, try deploying some first."))
                 .wrap(Wrap { trim: true })
                 .block(self.apply_style(WidgetStyle::Canisters));
         }
-        self.replica
-            .info
-            .clone()
-            .unwrap()
-            .canisters
-            .iter()
-            .enumerate()
-            .for_each(|(idx, c)| {
-                if let Some((_, canister_name, _)) =
-                    self.db.get_info(&c.canister_id, &self.selected_network)
-                {
-                    lines.push(Line::from(vec![Span::styled(
-                        format!(
-                            "Canister ID: {}, Canister name: {canister_name}",
-                            c.canister_id.clone()
+        let canisters = &self.replica.info.as_ref().unwrap().canisters;
+        let rows = self.canister_rows();
+        let selected = self.canister_tree.selected_row(&rows);
+        for (idx, row) in rows.iter().enumerate() {
+            let style = if Some(idx) == selected {
+                self.style_selected()
+            } else {
+                self.style_unselected()
+            };
+            let dim = Style::default().fg(Color::DarkGray);
+            let spans = match row {
+                TreeRow::Group {
+                    group,
+                    canisters,
+                    collapsed,
+                } => vec![
+                    Span::styled(if *collapsed { "▸ " } else { "▾ " }, style),
+                    Span::styled(group.to_string(), style),
+                    Span::styled(format!("  {canisters}"), dim),
+                ],
+                TreeRow::Canister {
+                    index,
+                    canister_id,
+                    name,
+                    dependencies,
+                    last,
+                } => {
+                    let canister = &canisters[*index];
+                    let mut spans = vec![
+                        Span::styled(
+                            if *last { "  ╰ " } else { "  ├ " },
+                            Style::default().fg(Color::LightGreen),
                         ),
-                        if idx == self.selected_canister_index {
-                            self.style_selected()
-                        } else {
-                            self.style_unselected()
-                        },
-                    )]));
-                } else {
-                    lines.push(Line::from(vec![Span::styled(
-                        format!("Canister ID: {}", c.canister_id.clone()),
-                        if idx == self.selected_canister_index {
-                            self.style_selected()
-                        } else {
-                            self.style_unselected()
-                        },
-                    )]));
+                        Span::styled(name.clone().unwrap_or(canister_id.clone()), style),
+                    ];
+                    if name.is_some() {
+                        spans.push(Span::styled(format!("  {canister_id}"), dim));
+                    }
+                    if !dependencies.is_empty() {
+                        spans.push(Span::styled(
+                            format!("  → {}", dependencies.join(", ")),
+                            Style::default().fg(Color::Cyan),
+                        ));
+                    }
+                    let metric = match self.canister_tree.sort_by {
+                        SortBy::Name => None,
+                        SortBy::Cycles => Some(display(canister.cycles_balance)),
+                        SortBy::Memory => Some(display(canister.memory_usage)),
+                    };
+                    if let Some(metric) = metric {
+                        spans.push(Span::styled(
+                            format!("  {metric}"),
+                            Style::default().fg(Color::Yellow),
+                        ));
+                    }
+                    match self.collected_logs.trap_count(canister_id) {
+                        0 => {}
+                        1 => spans.push(Span::styled(
+                            "  1 trap",
+                            Style::default().fg(Color::LightRed),
+                        )),
+                        traps => spans.push(Span::styled(
+                            format!("  {traps} traps"),
+                            Style::default().fg(Color::LightRed),
+                        )),
+                    }
+                    spans
                 }
-            });
+            };
+            lines.push(Line::from(spans));
+        }
 
         let text = Text::from(lines);
         Paragraph::new(text)
-            .wrap(Wrap { trim: true })
+            .wrap(Wrap { trim: false })
             .block(self.apply_style(WidgetStyle::Canisters))
     }
 
-    pub fn widget_canister_info(&self) -> Paragraph {
+    pub fn widget_canister_info(&self) -> Paragraph<'_> {
         let mut lines = vec![];
         if self.replica.info.is_none() {
             // return all canisters
             return Paragraph::new(Span::raw("Loading..."))
                 .block(self.apply_style(WidgetStyle::CanisterInfo));
         }
-        let binding = self.replica.info.clone().unwrap();
-        let canister = binding.canisters.get(self.selected_canister_index).clone();
+        let rows = self.canister_rows();
+        if let Some(TreeRow::Group { group, .. }) = self
+            .canister_tree
+            .selected_row(&rows)
+            .and_then(|row| rows.get(row))
+        {
+            return Paragraph::new(Text::from(self.group_info_lines(group)))
+                .wrap(Wrap { trim: true })
+                .block(self.apply_style(WidgetStyle::CanisterInfo));
+        }
+        let canister = self.selected_canister();
         if canister.is_none() {
             // return all canisters
             return Paragraph::new(Span::raw("No canisters found, try deploying some first."))
@@ -80,6 +131,32 @@ impl AppState {
         {
             lines.push(Header.build("DFX project: ", &project_name));
             lines.push(Item.build("Canister name: ", &canister_name));
+            if let Some(origin) = self
+                .db
+                .projects
+                .get(Path::new(&project_name))
+                .and_then(|p| p.id_origin(&canister_name, &self.selected_network))
+            {
+                lines.push(Item.build("Id from: ", &origin.display().to_string()));
+            }
+            lines.push(Item.build("Language: ", canister_info.language()));
+            if let Some(build) = canister_info.build_command() {
+                lines.push(Item.build("Build: ", &build));
+            }
+            if let Some(candid) = canister_info.candid_file() {
+                lines.push(Item.build("Candid: ", candid));
+            }
+            if let Some(remote) = &canister_info.remote {
+                let ids: Vec<String> = remote
+                    .id
+                    .iter()
+                    .map(|(network, id)| format!("{network}: {id}"))
+                    .collect();
+                lines.push(Item.build("Remote: ", &ids.join(", ")));
+            }
+            if let Some(id) = &canister_info.id {
+                lines.push(Item.build("Pulled from: ", &format!("ic: {id}")));
+            }
             if let Some(dependencies) = canister_info.dependencies {
                 lines.push(Item.build("Dependencies: ", &format!("{:?}", dependencies)));
             }
@@ -87,10 +164,29 @@ impl AppState {
                 lines.push(Item.build("Source: ", &format!("{:?}", source)));
             }
             if let Some(frontend) = canister_info.frontend {
-                lines.push(Item.build("Frontend: ", &format!("{:?}", frontend)));
+                let frontend = serde_json::to_string(&frontend).unwrap_or_default();
+                lines.push(Item.build("Frontend: ", &frontend));
             }
             lines.push(Last.build("Type: ", &canister_info.canister_type));
             lines.push(Empty.build("", ""));
+        } else if let Some(project) = self
+            .discovered
+            .iter()
+            .find(|p| p.owns(&canister.canister_id, &self.selected_network))
+        {
+            lines.push(Header.build(
+                "Unregistered project: ",
+                &project.path.display().to_string(),
+            ));
+            lines.push(Last.build(
+                "",
+                &format!(
+                    "register it in the projects {} with {}",
+                    self.keymap.label(Action::ToggleProjects),
+                    self.keymap.label(Action::RegisterProject)
+                ),
+            ));
+            lines.push(Empty.build("", ""));
         }
         lines.push(Header.build("Canister ID: ", canister.canister_id.as_str()));
         if canister
@@ -104,54 +200,177 @@ impl AppState {
             );
             lines.push(Item.build("HTTP Endpoint: ", &canister_url));
         }
-        lines.push(Item.build("Memory Allocation: ", &canister.memory_allocation.clone()));
-        lines.push(Item.build("Last Execution Round: ", &canister.last_execution_round));
+        lines.push(Item.build("Memory Allocation: ", &display(canister.memory_allocation)));
+        lines.push(Item.build(
+            "Last Execution Round: ",
+            &display(canister.last_execution_round),
+        ));
         lines.push(Item.build("Controllers: ", &canister.controllers));
-        lines.push(Item.build("Certified Data Length: ", &canister.certified_data_length));
+        lines.push(Item.build(
+            "Certified Data Length: ",
+            &display(canister.certified_data_length),
+        ));
         lines.push(Item.build(
             "Canister History Memory Usage: ",
-            &canister.canister_history_memory_usage,
+            &display(canister.canister_history_memory_usage),
         ));
         lines.push(Item.build("Execution State: ", &canister.execution_state));
         lines.push(Item.build(
             "Last Full Execution Round: ",
-            &canister.last_full_execution_round,
+            &display(canister.last_full_execution_round),
         ));
-        lines.push(Item.build("Compute Allocation: ", &canister.compute_allocation));
-        lines.push(Item.build("Freeze Threshold: ", &canister.freeze_threshold));
-        lines.push(Item.build("Memory Usage: ", &canister.memory_usage));
-        lines.push(Item.build("Accumulated Priority: ", &canister.accumulated_priority));
-        lines.push(Item.build("Cycles Balance: ", &canister.cycles_balance.to_string()));
-        if !canister.exports.exported_query_functions.is_empty() {
-            lines.push(Item.build(
-                "Exported Query functions: ",
-                &canister.exports.exported_query_functions.join(", "),
-            ));
+        lines.push(Item.build(
+            "Compute Allocation: ",
+            &display(canister.compute_allocation),
+        ));
+        lines.push(Item.build("Freeze Threshold: ", &display(canister.freeze_threshold)));
+        lines.push(Item.build("Memory Usage: ", &display(canister.memory_usage)));
+        lines.push(Item.build(
+            "Accumulated Priority: ",
+            &display(canister.accumulated_priority),
+        ));
+        lines.push(Item.build("Cycles Balance: ", &display(canister.cycles_balance)));
+        if let Some(history) = self.history.get(&canister.canister_id) {
+            if let Some(burn_rate) = history.burn_rate() {
+                lines.push(Item.build(
+                    "Burn Rate: ",
+                    &format!("{}/h", Cycles((burn_rate * 3600.0) as u128)),
+                ));
+            }
+            if let Some(runway) = canister
+                .freeze_threshold
+                .and_then(|threshold| history.time_until_frozen(threshold.0))
+            {
+                lines.push(Item.build(
+                    "Freezes In: ",
+                    &format!("~{} until freeze threshold", Seconds(runway)),
+                ));
+            }
+            if let Some(growth) = history
+                .rate(|s| s.memory.map(|m| u128::from(m.0)))
+                .filter(|rate| *rate > 0.0)
+            {
+                lines.push(Item.build(
+                    "Memory Growth: ",
+                    &format!("{}/min", Bytes((growth * 60.0) as u64)),
+                ));
+            }
         }
-        if !canister.exports.exported_update_functions.is_empty() {
+        if self.exports_unavailable() {
+            lines.push(Last.build("Exports: ", "unavailable from this source"));
+        } else {
+            if !canister.exports.exported_query_functions.is_empty() {
+                lines.push(Item.build(
+                    "Exported Query functions: ",
+                    &canister.exports.exported_query_functions.join(", "),
+                ));
+            }
+            if !canister.exports.exported_update_functions.is_empty() {
+                lines.push(Item.build(
+                    "Exported Update functions: ",
+                    &canister.exports.exported_update_functions.join(", "),
+                ));
+            }
+            if !canister.exports.exported_system_functions.is_empty() {
+                lines.push(Item.build(
+                    "Exported System functions: ",
+                    &canister.exports.exported_system_functions.join(", "),
+                ));
+            }
             lines.push(Item.build(
-                "Exported Update functions: ",
-                &canister.exports.exported_update_functions.join(", "),
+                "Exports heartbeat: ",
+                &canister.exports.exports_heartbeat.to_string(),
             ));
-        }
-        if !canister.exports.exported_system_functions.is_empty() {
-            lines.push(Item.build(
-                "Exported System functions: ",
-                &canister.exports.exported_system_functions.join(", "),
+            lines.push(Last.build(
+                "Exports global timer: ",
+                &canister.exports.exports_global_timer.to_string(),
             ));
         }
-        lines.push(Item.build(
-            "Exports heartbeat: ",
-            &canister.exports.exports_heartbeat.to_string(),
-        ));
-        lines.push(Last.build(
-            "Exports global timer: ",
-            &canister.exports.exports_global_timer.to_string(),
-        ));
 
         let text = Text::from(lines);
         Paragraph::new(text)
             .wrap(Wrap { trim: true })
             .block(self.apply_style(WidgetStyle::CanisterInfo))
     }
+
+    /// Sparkline series of the selected canister: cycles, memory and execution
+    /// rounds, each with its panel title. Empty until there are two samples.
+    pub fn canister_history_series(&self) -> Vec<(String, Vec<u64>)> {
+        let Some(canister) = self.selected_canister() else {
+            return vec![];
+        };
+        let Some(history) = self
+            .history
+            .get(&canister.canister_id)
+            .filter(|h| h.samples().count() > 1)
+        else {
+            return vec![];
+        };
+        vec![
+            (
+                format!("cycles {}", display(canister.cycles_balance)),
+                history.sparkline(|s| s.cycles.map(|c| c.0)),
+            ),
+            (
+                format!("memory {}", display(canister.memory_usage)),
+                history.sparkline(|s| s.memory.map(|m| u128::from(m.0))),
+            ),
+            (
+                format!("round {}", display(canister.last_execution_round)),
+                history.sparkline(|s| s.last_execution_round.map(|r| u128::from(r.0))),
+            ),
+        ]
+    }
+
+    pub fn widget_canister_chart<'a>(&'a self, title: &str, data: &'a [u64]) -> Sparkline<'a> {
+        Sparkline::default()
+            .data(data)
+            .style(Style::default().fg(self.config.theme.canister_info))
+            .block(self.apply_style(WidgetStyle::CanisterChart(title.to_string())))
+    }
+
+    /// Canister info pane while a section of the canister tree is selected.
+    fn group_info_lines(&self, group: &Group) -> Vec<Line<'static>> {
+        let mut lines = vec![];
+        match group {
+            Group::Project(path) => {
+                lines.push(Header.build("DFX project: ", &path.display().to_string()));
+                if let Some(project) = self.db.projects.get(path) {
+                    lines.push(Item.build("Health: ", &project.health.to_string()));
+                    lines.push(Item.build(
+                        "Canisters in dfx.json: ",
+                        &project.canisters.len().to_string(),
+                    ));
+                    if let Some(env_file) = &project.output_env_file {
+                        lines.push(Item.build("Env file: ", &env_file.display().to_string()));
+                    }
+                    let mut networks: Vec<&String> = project.networks.keys().collect();
+                    networks.sort();
+                    if !networks.is_empty() {
+                        let networks: Vec<&str> = networks.iter().map(|n| n.as_str()).collect();
+                        lines.push(Item.build("Networks: ", &networks.join(", ")));
+                    }
+                }
+                lines.push(Last.build(
+                    "",
+                    &format!(
+                        "build, deploy and generate in the projects {}",
+                        self.keymap.label(Action::ToggleProjects)
+                    ),
+                ));
+            }
+            Group::Unregistered => {
+                lines.push(Header.build("Unknown / unregistered canisters", ""));
+                lines.push(Last.build(
+                    "",
+                    "No registered project has their ids. Register the project, or set discovery.roots in the config to find it.",
+                ));
+            }
+            Group::System => {
+                lines.push(Header.build("System canisters", ""));
+                lines.push(Last.build("", "Cycles wallets, the Candid UI and the NNS canisters."));
+            }
+        }
+        lines
+    }
 }
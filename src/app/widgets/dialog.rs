@@ -0,0 +1,144 @@
+use crate::app::actions::Dialog;
+use crate::app::start_dialog::{StartDialog, StartField, START_FIELDS};
+use crate::app::state::AppState;
+use crate::app::widgets::style::ListItem::{Empty, Header, Item};
+use ratatui::{prelude::*, widgets::*};
+
+use super::style::WidgetStyle;
+
+impl AppState {
+    /// Height of the open dialog, borders included.
+    pub fn dialog_height(&self) -> u16 {
+        match self.dialog {
+            Some(Dialog::StartReplica(_)) => START_FIELDS.len() as u16 + 6,
+            _ => 7,
+        }
+    }
+
+    pub fn widget_dialog(&self) -> Option<Paragraph<'_>> {
+        let mut lines = vec![];
+        match self.dialog.as_ref()? {
+            Dialog::Confirm {
+                action,
+                canister_id,
+            } => {
+                lines.push(Header.build(&format!("Really {action} canister "), canister_id));
+                lines.push(Empty.build("", ""));
+                lines.push(Header.build("[y] yes  [n] no", ""));
+            }
+            Dialog::ConfirmReinstall { entry } => {
+                let target = match &entry.canister {
+                    Some(canister) => canister.clone(),
+                    None => format!("every canister of {}", entry.project.display()),
+                };
+                lines.push(Header.build("Really reinstall ", &target));
+                lines.push(Header.build("This wipes their state.", ""));
+                lines.push(Header.build("[y] yes  [n] no", ""));
+            }
+            Dialog::DepositAmount {
+                canister_id,
+                input,
+                error,
+            } => {
+                lines.push(Header.build("Deposit cycles into ", canister_id));
+                lines.push(Header.build("Amount: ", &format!("{input}█")));
+                if let Some(error) = error {
+                    lines.push(Line::styled(
+                        error.clone(),
+                        Style::default().fg(Color::LightRed),
+                    ));
+                } else {
+                    lines.push(Empty.build("", ""));
+                }
+                lines.push(Header.build(
+                    "e.g. 1.5T or 500_000_000  [Enter] deposit  [Esc] cancel",
+                    "",
+                ));
+            }
+            Dialog::StartReplica(dialog) => lines.extend(self.start_dialog_lines(dialog)),
+            Dialog::ExportLogs { format, filtered } => {
+                lines.push(Header.build(
+                    "Export ",
+                    &format!("{} logs", self.logs_pane_name(self.logs_pane)),
+                ));
+                lines.push(Header.build("Format: ", &format!("{format}  [Tab] change")));
+                lines.push(Header.build(
+                    "Lines: ",
+                    &format!(
+                        "{}  [f] change",
+                        if *filtered {
+                            "shown by the filters"
+                        } else {
+                            "all"
+                        }
+                    ),
+                ));
+                lines.push(Header.build("[Enter] export  [Esc] cancel", ""));
+            }
+        }
+        Some(
+            Paragraph::new(Text::from(lines))
+                .wrap(Wrap { trim: true })
+                .block(self.apply_style(WidgetStyle::Dialog)),
+        )
+    }
+
+    fn start_dialog_lines(&self, dialog: &StartDialog) -> Vec<Line<'static>> {
+        let mut lines = vec![Header.build("Start the local replica", "")];
+        for (index, field) in START_FIELDS.iter().enumerate() {
+            let selected = index == dialog.selected_index;
+            let text = |text: &str, empty: &str| match (text.is_empty(), selected) {
+                (_, true) => format!("{text}█"),
+                (true, false) => empty.to_string(),
+                (false, false) => text.to_string(),
+            };
+            let checkbox = |on: bool| if on { "[x]" } else { "[ ]" }.to_string();
+            let value = match field {
+                StartField::Profile => text(&dialog.name, "unsaved"),
+                StartField::Project => match &dialog.options.project_dir {
+                    Some(dir) => dir.display().to_string(),
+                    None => "shared network".to_string(),
+                },
+                StartField::Clean => checkbox(dialog.options.clean),
+                StartField::Background => checkbox(dialog.options.background),
+                StartField::Host => text(&dialog.host, "default"),
+                StartField::ArtificialDelay => text(&dialog.artificial_delay, "default") + " ms",
+                StartField::EnableBitcoin => checkbox(dialog.options.enable_bitcoin),
+                StartField::EnableCanisterHttp => checkbox(dialog.options.enable_canister_http),
+            };
+            let mut line = Item.build(&format!("{field}: "), &value);
+            if selected {
+                line.patch_style(Style::default().add_modifier(Modifier::REVERSED));
+            }
+            lines.push(line);
+        }
+        match &dialog.error {
+            Some(error) => lines.push(Line::styled(
+                error.clone(),
+                Style::default().fg(Color::LightRed),
+            )),
+            None => lines.push(Empty.build("", "")),
+        }
+        let profiles: Vec<String> = self
+            .config
+            .start_profiles
+            .iter()
+            .take(9)
+            .enumerate()
+            .map(|(i, profile)| format!("[{}] {}", i + 1, profile.name))
+            .collect();
+        lines.push(Header.build(
+            "Profiles: ",
+            &if profiles.is_empty() {
+                "none saved".to_string()
+            } else {
+                profiles.join("  ")
+            },
+        ));
+        lines.push(Header.build(
+            "[Enter] start  [Space] toggle  [←/→] choose  [Ctrl+S] save  [Ctrl+D] delete  [Esc] cancel",
+            "",
+        ));
+        lines
+    }
+}
@@ -0,0 +1,44 @@
+use crate::app::keymap::Action;
+use crate::app::state::AppState;
+use crate::app::widgets::style::ListItem::{Empty, Header, Item};
+use ratatui::{prelude::*, widgets::*};
+
+use super::style::WidgetStyle;
+
+impl AppState {
+    /// Lines of the help: the keys of its context, then the dashboard-wide ones.
+    fn help_lines(&self) -> Vec<Line<'static>> {
+        let Some(context) = self.help else {
+            return vec![];
+        };
+        let mut lines = vec![];
+        let mut section = |title: String, actions: &[(Action, &str)]| {
+            if actions.is_empty() {
+                return;
+            }
+            if !lines.is_empty() {
+                lines.push(Empty.build("", ""));
+            }
+            lines.push(Header.build(&title, ""));
+            for (action, description) in actions {
+                lines.push(Item.build(&format!("{:<18}", self.keymap.label(*action)), description));
+            }
+        };
+        section(format!("In the {context}"), context.actions());
+        section("Everywhere".to_string(), context.dashboard_actions());
+        lines
+    }
+
+    /// Height of the help, borders included.
+    pub fn help_height(&self) -> u16 {
+        self.help_lines().len() as u16 + 2
+    }
+
+    pub fn widget_help(&self) -> Option<Paragraph<'_>> {
+        self.help?;
+        Some(
+            Paragraph::new(Text::from(self.help_lines()))
+                .block(self.apply_style(WidgetStyle::Help)),
+        )
+    }
+}
@@ -1,39 +1,50 @@
+use crate::app::state::AppState;
 use crate::app::widgets::style::ListItem::{Header, Item, Last};
-use crate::{app::state::AppState, dfx_commands::DfxCommands};
+use crate::dfx_commands::{Balance, DfxError};
+use crate::metrics::Cycles;
 use ratatui::{prelude::*, text::Span, widgets::*};
 
 use super::style::WidgetStyle;
 
 impl AppState {
-    pub fn widget_identity(&self) -> Paragraph {
+    pub fn widget_identity(&self) -> Paragraph<'_> {
         let mut lines = vec![];
         lines.push(Header.build("Selected DFX Identity: ", &self.selected_identity));
-        let principal = self.selected_identity_principal.as_ref();
-        lines.push(Item.build("Principal: ", &principal.unwrap_or(&"N/A".to_string())));
-        let icp = self.selected_identity_icp.as_ref();
-        lines.push(Item.build("ICP balance: ", &icp.unwrap_or(&"N/A".to_string())));
-        let cycles = self.selected_identity_cycles.as_ref();
-        lines.push(Last.build("Cycles: ", &cycles.unwrap_or(&"N/A".to_string())));
+        lines.push(Item.build_result("Principal: ", self.selected_identity_principal.as_ref()));
+        lines.push(Item.build_result(
+            &format!("ICP balance ({}): ", self.selected_network),
+            self.selected_identity_icp.as_ref(),
+        ));
+        let cycles = |balance: &Option<Result<Balance<u128>, DfxError>>| {
+            balance.as_ref().map(|b| match b {
+                Ok(Balance::Amount(c)) => Ok(Cycles(*c).to_string()),
+                Ok(other) => Ok(other.to_string()),
+                Err(e) => Err(e.clone()),
+            })
+        };
+        lines.push(Item.build_result(
+            "Cycles ledger: ",
+            cycles(&self.selected_identity_cycles).as_ref(),
+        ));
+        lines.push(Last.build_result(
+            "Cycles wallet: ",
+            cycles(&self.selected_identity_wallet_cycles).as_ref(),
+        ));
         let text = Text::from(lines);
         Paragraph::new(text)
             .wrap(Wrap { trim: true })
             .block(self.apply_style(WidgetStyle::Identity))
     }
 
-    pub fn widget_identity_selection_menu(&self) -> Paragraph {
+    pub fn widget_identity_selection_menu(&self) -> Paragraph<'_> {
         let mut lines = vec![];
-        DfxCommands::IdentityList
-            .run(self, &self.path_to_dfx)
-            .split("\n")
-            .map(|s| s.to_string())
-            .enumerate()
-            .for_each(|(idx, n)| {
-                lines.push(Line::from(vec![if idx == self.selected_identity_index {
-                    Span::styled(n.to_string(), self.style_selected())
-                } else {
-                    Span::styled(n.to_string(), self.style_unselected())
-                }]));
-            });
+        self.identities.iter().enumerate().for_each(|(idx, n)| {
+            lines.push(Line::from(vec![if idx == self.selected_identity_index {
+                Span::styled(n.to_string(), self.style_selected())
+            } else {
+                Span::styled(n.to_string(), self.style_unselected())
+            }]));
+        });
 
         let text = Text::from(lines);
         Paragraph::new(text)
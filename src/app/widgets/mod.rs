@@ -1,4 +1,8 @@
+pub mod caller;
 pub mod canister;
+pub mod dialog;
+pub mod help;
 pub mod identity;
+pub mod project;
 pub mod replica;
 pub mod style;
@@ -0,0 +1,115 @@
+use crate::app::project_view::ProjectView;
+use crate::app::state::AppState;
+use crate::app::widgets::style::ListItem::{Empty, Header, Item};
+use crate::dfx_project::ProjectHealth;
+use ratatui::{prelude::*, text::Span, widgets::*};
+use std::path::Path;
+
+use super::style::WidgetStyle;
+
+impl AppState {
+    pub fn widget_project_view(&self) -> Paragraph<'_> {
+        let entries = ProjectView::entries(&self.db, &self.discovered);
+        if entries.is_empty() {
+            return Paragraph::new(Span::raw(
+                "No projects registered, run `dfx-top register-dfx-project <path>` first.",
+            ))
+            .wrap(Wrap { trim: true })
+            .block(self.apply_style(WidgetStyle::Projects));
+        }
+        let mut lines = vec![];
+        lines.push(
+            Header.build(
+                "Deploy mode: ",
+                &self
+                    .project_view
+                    .mode
+                    .map_or("auto".to_string(), |m| m.to_string()),
+            ),
+        );
+        lines.push(Item.build(
+            "Task: ",
+            if self.project_view.is_running() {
+                "running, see the project logs"
+            } else {
+                "idle"
+            },
+        ));
+        let failing = self
+            .db
+            .projects
+            .values()
+            .filter(|p| p.health != ProjectHealth::Ok)
+            .count();
+        lines.push(Item.build(
+            "Health: ",
+            &match failing {
+                0 => "all projects ok".to_string(),
+                n => format!("{n} of {} need attention", self.db.projects.len()),
+            },
+        ));
+        lines.push(Empty.build("", ""));
+        for (idx, entry) in entries.iter().enumerate() {
+            let style = if idx == self.project_view.selected_index {
+                self.style_selected()
+            } else {
+                self.style_unselected()
+            };
+            if !entry.registered {
+                lines.push(self.discovered_line(&entry.project, style));
+                continue;
+            }
+            lines.push(match &entry.canister {
+                None => {
+                    let mut line =
+                        Line::from(Span::styled(entry.project.display().to_string(), style));
+                    match self.db.projects.get(&entry.project).map(|p| &p.health) {
+                        Some(ProjectHealth::Ok) | None => {}
+                        Some(health) => line.spans.push(Span::styled(
+                            format!("  {health}"),
+                            Style::default().fg(match health {
+                                ProjectHealth::NotDeployed => Color::Yellow,
+                                _ => Color::LightRed,
+                            }),
+                        )),
+                    }
+                    line
+                }
+                Some(canister) => Line::from(vec![
+                    Span::styled("  ├ ", Style::default().fg(Color::LightGreen)),
+                    Span::styled(canister.clone(), style),
+                ]),
+            });
+        }
+        Paragraph::new(Text::from(lines))
+            .wrap(Wrap { trim: false })
+            .block(self.apply_style(WidgetStyle::Projects))
+    }
+
+    /// A discovered project, with how many of its canisters the replica runs.
+    fn discovered_line(&self, path: &Path, style: Style) -> Line<'static> {
+        let running = self
+            .discovered
+            .iter()
+            .find(|project| project.path == path)
+            .map_or(0, |project| {
+                self.replica.info.as_ref().map_or(0, |info| {
+                    info.canisters
+                        .iter()
+                        .filter(|c| project.owns(&c.canister_id, &self.selected_network))
+                        .count()
+                })
+            });
+        let mut hint = "  not registered".to_string();
+        if running > 0 {
+            hint += &format!(
+                ", {running} of its canisters run on {}",
+                self.selected_network
+            );
+        }
+        Line::from(vec![
+            Span::styled(path.display().to_string(), style),
+            Span::styled(hint, Style::default().fg(Color::DarkGray)),
+        ])
+    }
+}
@@ -1,15 +1,50 @@
+use crate::app::keymap::Action;
 use crate::app::state::{AppState, LocalReplicaState, LogsPane};
 use crate::app::widgets::style::ListItem::{Header, Item, Last};
+use crate::app::{
+    log_store::LOG_BUFFER_LEN,
+    log_viewer::{plain_text, LogPrompt},
+};
+use crate::logs::{strip_ansi, LogLevel, LogLine};
+use crate::replica_supervisor::MAX_RESTARTS;
 use ansi_to_tui::IntoText;
-use ratatui::{prelude::*, text::Span, widgets::*};
+use ratatui::{
+    prelude::*,
+    text::Span,
+    widgets::{
+        block::{Position, Title},
+        *,
+    },
+};
+use std::{ops::Range, time::Instant};
 
 use super::style::WidgetStyle;
 
+/// How many of a crash's last log lines the network pane shows.
+const CRASH_LINES_SHOWN: usize = 5;
+
 impl AppState {
-    pub fn widget_network(&self) -> Paragraph {
+    pub fn widget_network(&self) -> Paragraph<'_> {
         let mut lines = vec![];
         lines.push(Header.build("Local replica: ", &self.replica.state.to_string()));
+        if let Some(process) = &self.replica_process {
+            let origin = if process.is_adopted() {
+                "attached"
+            } else {
+                "started by dfx-top"
+            };
+            lines.push(Item.build(
+                "Process: ",
+                &format!("dfx start, pid {} ({origin})", process.pid()),
+            ));
+        }
+        lines.extend(self.crash_lines());
         lines.push(Header.build("Network: ", &self.selected_network));
+        if let Some(network) = self.selected_network_info() {
+            lines.push(Item.build("Provider: ", &network.provider));
+            lines.push(Item.build("Type: ", &network.network_type.to_string()));
+            lines.push(Item.build("Defined in: ", &network.origin.to_string()));
+        }
         if (self.replica.state == LocalReplicaState::Running || self.selected_network == "ic")
             && self.replica.ping.is_some()
         {
@@ -17,15 +52,25 @@ impl AppState {
             lines.push(Item.build("Replica dashboard URL: ", &self.replica.replica_url.clone()));
             lines.push(Item.build("Revision: ", &self.replica.replica_revision_url.clone()));
             lines.push(Item.build("Webserver: ", &self.replica.webserver_url));
+            match &self.replica.status_source {
+                Some(Ok(source)) => lines.push(Item.build("Status source: ", source)),
+                Some(Err(e)) => lines.push(Item.build("Status source: ", e)),
+                None => {}
+            }
             lines.push(Item.build("IC API version: ", &ping.ic_api_version));
             lines.push(Item.build("Replica health status: ", &ping.replica_health_status));
             if let Some(certified_height) = ping.certified_height {
                 lines.push(Item.build("Certified height: ", &certified_height.to_string()));
             }
             if let Some(impl_hash) = &ping.impl_hash {
-                lines.push(Item.build("Impl hash: ", &impl_hash));
+                lines.push(Item.build("Impl hash: ", impl_hash));
             }
             lines.push(Last.build("Root key: ", &format!("{:?}", ping.root_key)));
+        } else if let Some(e) = &self.replica.ping_error {
+            if self.replica.state == LocalReplicaState::Running || self.selected_network != "local"
+            {
+                lines.push(Last.build_error("Unreachable: ", e));
+            }
         }
 
         let text = Text::from(lines);
@@ -34,14 +79,65 @@ impl AppState {
             .block(self.apply_style(WidgetStyle::Replica))
     }
 
-    pub fn widget_network_selection_menu(&self) -> Paragraph {
+    /// The last crash of the replica with the last lines it printed, in red
+    /// so it is not missed, and what auto-restart is doing about it.
+    fn crash_lines(&self) -> Vec<Line<'static>> {
+        let Some(crash) = &self.last_crash else {
+            return vec![];
+        };
+        let alert = Style::default()
+            .fg(Color::LightRed)
+            .add_modifier(Modifier::BOLD);
+        let mut lines = vec![Line::styled(
+            format!(
+                "CRASHED at {}: dfx start (pid {}), {}",
+                crash.at.format("%H:%M:%S"),
+                crash.pid,
+                crash.reason()
+            ),
+            alert.add_modifier(Modifier::REVERSED),
+        )];
+        let restart = match self.auto_restart.next_at {
+            Some(at) => format!(
+                "restarting in {}s (attempt {} of {MAX_RESTARTS})",
+                at.saturating_duration_since(Instant::now()).as_secs(),
+                self.auto_restart.restarts
+            ),
+            None if self.replica_process.is_some() => format!(
+                "restarted (attempt {} of {MAX_RESTARTS})",
+                self.auto_restart.restarts
+            ),
+            None if self.auto_restart.enabled => format!("gave up after {MAX_RESTARTS} crashes"),
+            None => format!(
+                "off, turn on with {}",
+                self.keymap.label(Action::ToggleAutoRestart)
+            ),
+        };
+        lines.push(Item.build("Auto-restart: ", &restart));
+        let shown = crash.last_lines.len().saturating_sub(CRASH_LINES_SHOWN);
+        for line in &crash.last_lines[shown..] {
+            lines.push(Line::from(vec![
+                Span::styled("  │ ", alert),
+                Span::styled(strip_ansi(&line.text), Style::default().fg(Color::Gray)),
+            ]));
+        }
+        lines
+    }
+
+    pub fn widget_network_selection_menu(&self) -> Paragraph<'_> {
         let mut lines = vec![];
         self.networks.iter().enumerate().for_each(|(idx, n)| {
-            lines.push(Line::from(vec![if idx == self.selected_network_index {
-                Span::styled(n.to_string(), self.style_selected())
-            } else {
-                Span::styled(n.to_string(), self.style_unselected())
-            }]));
+            lines.push(Line::from(vec![
+                if idx == self.selected_network_index {
+                    Span::styled(n.name.clone(), self.style_selected())
+                } else {
+                    Span::styled(n.name.clone(), self.style_unselected())
+                },
+                Span::styled(
+                    format!("  {} ({}, {})", n.provider, n.network_type, n.origin),
+                    Style::default().fg(Color::DarkGray),
+                ),
+            ]));
         });
         let text = Text::from(lines);
         Paragraph::new(text)
@@ -49,73 +145,176 @@ impl AppState {
             .block(self.apply_style(WidgetStyle::Replica))
     }
 
-    pub fn widget_logs(&self) -> Paragraph {
-        let mut text = Text::from(Line::from(vec![]));
-        let collected_logs = &self.collected_logs;
-        // let collected_logs = match &self.logs_source {
-        //     LogsSource::Replica => self.collected_logs.clone(),
-        //     LogsSource::File(Some(v)) => std::fs::read_to_string(v)
-        //         .unwrap_or_default()
-        //         .lines()
-        //         .map(String::from)
-        //         .collect::<Vec<String>>(),
-        //     LogsSource::File(None) => vec![],
-        // };
+    /// The current logs tab, scrolled and filtered by the log viewer, in a
+    /// pane `height` rows high including its borders.
+    pub fn widget_logs(&self, height: u16) -> Paragraph<'_> {
+        let viewer = &self.log_viewer;
+        let prompt = viewer.prompt.as_ref().map(|(prompt, input)| {
+            let prefix = match prompt {
+                LogPrompt::Search => "/",
+                LogPrompt::Filter => "&",
+            };
+            let mut spans = vec![Span::styled(
+                format!("{prefix}{input}█"),
+                Style::default().fg(Color::LightCyan),
+            )];
+            if let Some(e) = &viewer.filter_error {
+                let e = e.lines().last().unwrap_or_default();
+                spans.push(Span::styled(
+                    format!("  {e}"),
+                    Style::default().fg(Color::LightRed),
+                ));
+            }
+            Line::from(spans)
+        });
+        let height =
+            usize::from(height.saturating_sub(2)).saturating_sub(usize::from(prompt.is_some()));
 
-        if collected_logs.is_empty() {
-            return Paragraph::new(Text::from(
-                "No logs available, press 's' to start the replica".to_string(),
-            ))
-            .block(self.apply_style(WidgetStyle::Logs));
+        let logs = self.shown_logs();
+        viewer.viewport.set((logs.len(), height));
+        let visible = viewer.visible_range(logs.len(), height);
+        let mut status = if viewer.is_following() {
+            format!("following, {} lines", logs.len())
         } else {
-            if self.logs_pane == LogsPane::CanisterLogs {
-                if let Some(selected_canister_id) = self
-                    .replica
-                    .info
-                    .as_ref()
-                    .and_then(|i| i.canisters.get(self.selected_canister_index))
-                    .and_then(|c| Some(c.canister_id.clone()))
-                {
-                    collected_logs.iter().for_each(|line| {
-                        if line.contains(&selected_canister_id) {
-                            text.extend(line.into_text().unwrap());
-                        }
-                    });
+            format!(
+                "lines {}-{} of {}",
+                visible.start + 1,
+                visible.end,
+                logs.len()
+            )
+        };
+        if matches!(self.logs_pane, LogsPane::Replica | LogsPane::Canister)
+            && self.collected_logs.len() == LOG_BUFFER_LEN
+        {
+            if let Some(path) = self.collected_logs.spill_path() {
+                status += &format!(", older in {}", path.display());
+            }
+        }
+        let status = format!("┤ {status} ├");
+
+        let mut text = if logs.is_empty() {
+            Text::from(match self.logs_pane {
+                LogsPane::Project if self.project_view.logs.is_empty() => {
+                    "No project tasks run yet, press 'P' to open the project view"
                 }
-            } else {
-                collected_logs.iter().for_each(|log| {
-                    text.extend(log.into_text().unwrap());
-                });
+                LogsPane::Canister if self.selected_canister().is_none() => "No canister selected",
+                LogsPane::Replica | LogsPane::Canister if self.collected_logs.is_empty() => {
+                    "No logs available, press 's' to start the replica"
+                }
+                LogsPane::File(_) if self.pane_logs().next().is_none() => "Waiting for lines...",
+                _ => "No lines match the filters",
+            })
+        } else {
+            let mut text = Text::default();
+            let clean = self.logs_pane == LogsPane::Canister;
+            for line in &logs[visible] {
+                let plain = plain_text(line, clean);
+                text.extend(log_line_text(
+                    line,
+                    clean,
+                    &plain,
+                    &viewer.search_matches(&plain),
+                ));
+            }
+            text
+        };
+        if let Some(prompt) = prompt {
+            text.lines.truncate(height);
+            text.lines.resize(height, Line::default());
+            text.lines.push(prompt);
+        }
+        Paragraph::new(text).block(
+            self.apply_style(WidgetStyle::Logs).title(
+                Title::from(status)
+                    .alignment(Alignment::Right)
+                    .position(Position::Top),
+            ),
+        )
+    }
+
+    /// Directory listing shown in place of the logs while picking a file.
+    pub fn widget_logs_file_selection(&self) -> Option<Paragraph<'_>> {
+        let picker = self.file_picker.as_ref()?;
+        let mut lines = vec![Line::from(Span::styled(
+            format!("{}/", picker.dir.display()),
+            Style::default().fg(Color::DarkGray),
+        ))];
+        if let Some(e) = &picker.error {
+            lines.push(Line::styled(
+                e.clone(),
+                Style::default().fg(Color::LightRed),
+            ));
+        }
+        for (idx, path) in picker.entries.iter().enumerate() {
+            let mut name = path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            if path.is_dir() {
+                name.push('/');
             }
+            lines.push(Line::from(Span::styled(
+                name,
+                if idx == picker.selected_index {
+                    self.style_selected()
+                } else {
+                    self.style_unselected()
+                },
+            )));
         }
-        Paragraph::new(text).block(self.apply_style(WidgetStyle::Logs))
+        // keep the selection on screen
+        let scroll = picker.selected_index.saturating_sub(5) as u16;
+        Some(
+            Paragraph::new(Text::from(lines))
+                .scroll((scroll, 0))
+                .block(self.apply_style(WidgetStyle::LogFiles)),
+        )
     }
+}
 
-    // pub fn widget_logs_file_selection(&self) -> Paragraph {
-    //     let mut text = Text::from(Line::from(vec![]));
-    //     let idx = self.selected_fs_index;
-    //     if let Some(current_dir) = self.logfile_selection_menu_active.clone() {
-    //         let dir_contents = std::fs::read_dir(current_dir);
-    //         if let Ok(dir_contents) = dir_contents {
-    //             dir_contents.enumerate().for_each(|(i, entry)| {
-    //                 if let Ok(entry) = entry {
-    //                     let path = entry.path();
-    //                     let path = path.to_str().unwrap_or_default();
-    //                     let path = path.replace(&self.path_to_dfx, "");
-    //                     if idx == i {
-    //                         text.extend(Text::from(Span::styled(
-    //                             path,
-    //                             Style::default()
-    //                                 .add_modifier(Modifier::BOLD)
-    //                                 .fg(Color::LightRed),
-    //                         )));
-    //                     } else {
-    //                         text.extend(Text::from(Span::styled(path, Style::default())));
-    //                     }
-    //                 }
-    //             });
-    //         }
-    //     }
-    //     Paragraph::new(text).block(self.apply_style(WidgetStyle::Logs))
-    // }
+/// A log line prefixed with the time it was read, colored by level when the
+/// line has no colors of its own. Lines with search `matches` in their
+/// `plain` text lose their own colors so the matches can be highlighted.
+/// `clean` shows canister messages without the replica's prefix.
+fn log_line_text(
+    line: &LogLine,
+    clean: bool,
+    plain: &str,
+    matches: &[Range<usize>],
+) -> Text<'static> {
+    let level_style = match line.level {
+        _ if line.is_trap() => Style::default().fg(Color::LightRed),
+        Some(LogLevel::Warn) => Style::default().fg(Color::Yellow),
+        Some(LogLevel::Error | LogLevel::Critical) => Style::default().fg(Color::LightRed),
+        _ => Style::default(),
+    };
+    let mut text = if matches.is_empty() && !(clean && line.canister.is_some()) {
+        line.text
+            .into_text()
+            .unwrap_or_else(|_| Text::raw(line.text.clone()))
+    } else {
+        let highlight = Style::default().fg(Color::Black).bg(Color::Yellow);
+        let mut spans = vec![];
+        let mut end = 0;
+        for m in matches {
+            spans.push(Span::raw(plain[end..m.start].to_string()));
+            spans.push(Span::styled(plain[m.clone()].to_string(), highlight));
+            end = m.end;
+        }
+        spans.push(Span::raw(plain[end..].to_string()));
+        Text::from(Line::from(spans))
+    };
+    for text_line in text.lines.iter_mut() {
+        for span in text_line.spans.iter_mut() {
+            span.style = level_style.patch(span.style);
+        }
+        text_line.spans.insert(
+            0,
+            Span::styled(
+                line.at.format("%H:%M:%S ").to_string(),
+                Style::default().fg(Color::DarkGray),
+            ),
+        );
+    }
+    text
 }
@@ -1,4 +1,7 @@
+use crate::app::caller::CallerFocus;
+use crate::app::keymap::Action;
 use crate::app::state::{AppState, LocalReplicaState, LogsPane};
+use crate::dfx_commands::DfxError;
 use chrono::Local;
 use ratatui::{
     prelude::*,
@@ -11,7 +14,16 @@ use ratatui::{
 
 pub enum WidgetStyle {
     Logs,
+    /// The file picker shown in place of the logs.
+    LogFiles,
     CanisterInfo,
+    /// One of the history charts below the canister info, with its title.
+    CanisterChart(String),
+    Caller,
+    Dialog,
+    /// The keys of the current context, over everything else.
+    Help,
+    Projects,
     Replica,
     Canisters,
     Identity,
@@ -37,27 +49,74 @@ impl ListItem {
                 Self::Last => Span::styled("  ╰ ".to_string(), style_bullet),
                 Self::Empty => Span::styled("".to_string(), Style::default()),
             },
-            Span::styled(format!("{key}"), style_key),
+            Span::styled(key.to_string(), style_key),
             Span::styled(value.to_string(), style_value),
         ])
         .to_owned()
     }
+
+    /// Like [`ListItem::build`], but renders `value` as "N/A" while it is
+    /// still loading and shows the failure reason if dfx failed.
+    pub fn build_result<T: std::fmt::Display>(
+        self,
+        key: &str,
+        value: Option<&Result<T, DfxError>>,
+    ) -> Line<'static> {
+        match value {
+            None => self.build(key, "N/A"),
+            Some(Ok(value)) => self.build(key, &value.to_string()),
+            Some(Err(e)) => self.build_error(key, e),
+        }
+    }
+
+    pub fn build_error(self, key: &str, error: &DfxError) -> Line<'static> {
+        let mut line = self.build(key, "");
+        line.spans.pop();
+        line.spans.push(Span::styled(
+            error.reason(),
+            Style::default().fg(Color::LightRed),
+        ));
+        line
+    }
 }
 
 impl AppState {
     pub fn style_selected(&self) -> Style {
         Style::default()
             .add_modifier(Modifier::BOLD)
-            .fg(Color::LightRed)
+            .fg(self.config.theme.selected)
     }
     pub fn style_unselected(&self) -> Style {
         Style::default().add_modifier(Modifier::BOLD)
     }
 
-    pub fn apply_style(&self, widget: WidgetStyle) -> Block {
+    /// Names of the logs pane's tabs, the current one highlighted.
+    fn logs_tabs(&self) -> Line<'_> {
+        let mut tabs = vec![LogsPane::Replica, LogsPane::Canister, LogsPane::Project];
+        tabs.extend((0..self.file_sources.len()).map(LogsPane::File));
+        let mut spans = vec![Span::raw("┤ ")];
+        for (i, tab) in tabs.into_iter().enumerate() {
+            if i > 0 {
+                spans.push(Span::raw(" │ "));
+            }
+            let style = if tab == self.logs_pane {
+                Style::default()
+                    .add_modifier(Modifier::BOLD)
+                    .fg(self.config.theme.logs)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+            spans.push(Span::styled(self.logs_pane_name(tab), style));
+        }
+        spans.push(Span::raw(" logs ├"));
+        Line::from(spans)
+    }
+
+    pub fn apply_style(&self, widget: WidgetStyle) -> Block<'_> {
+        let key = |action: Action| self.keymap.label(action);
         match widget {
             WidgetStyle::Logs => Block::default()
-                .border_style(Style::default().fg(Color::LightCyan))
+                .border_style(Style::default().fg(self.config.theme.logs))
                 .border_type(BorderType::Rounded)
                 .title(
                     Title::from(format!("┤ {} ├", Local::now().format("%H:%M:%S")))
@@ -65,67 +124,209 @@ impl AppState {
                 )
                 .title(
                     Title::from(format!(
-                        "┤ view {} logs [LEFT]/[RIGHT] ├",
-                        // "┤ view {} logs [LEFT]/[RIGHT], {} [f] ├",
-                        match self.logs_pane {
-                            LogsPane::CanisterLogs => "replica",
-                            LogsPane::ReplicaLogs => "canister",
-                        },
-                        // match self.logs_source {
-                        //     LogsSource::Replica => "load logs from file",
-                        //     LogsSource::File(Some(_)) => "detach from file",
-                        //     LogsSource::File(None) => "detach from file",
-                        // }
+                        "┤ view {} logs {}/{}, follow file {}{} ├",
+                        self.logs_pane_name(self.logs_pane.next(self.file_sources.len())),
+                        key(Action::PreviousLogs),
+                        key(Action::NextLogs),
+                        key(Action::FollowFile),
+                        if matches!(self.logs_pane, LogsPane::File(_)) {
+                            format!(", close {}", key(Action::CloseFile))
+                        } else {
+                            String::new()
+                        }
                     ))
                     .alignment(Alignment::Center)
                     .position(Position::Bottom),
                 )
-                .title(match self.logs_pane {
-                    LogsPane::CanisterLogs => "┤ canister logs ├",
-                    LogsPane::ReplicaLogs => "┤ replica logs ├",
-                })
+                .title(
+                    Title::from(format!(
+                        "┤ search {} filter {} level {}: {} scroll {}/{} tail {} export {} ├",
+                        key(Action::SearchLogs),
+                        key(Action::FilterLogs),
+                        key(Action::LogLevel),
+                        self.log_viewer
+                            .min_level
+                            .map_or("all".to_string(), |level| format!("{level}+")),
+                        key(Action::PageUp),
+                        key(Action::PageDown),
+                        key(Action::LogsTail),
+                        key(Action::ExportLogs),
+                    ))
+                    .alignment(Alignment::Left)
+                    .position(Position::Bottom),
+                )
+                .title(self.logs_tabs())
                 .borders(Borders::ALL),
             WidgetStyle::CanisterInfo => Block::default()
-                .border_style(Style::default().fg(Color::LightMagenta))
+                .border_style(Style::default().fg(self.config.theme.canister_info))
                 .border_type(BorderType::Rounded)
                 .borders(Borders::ALL)
-                .title("┤ canister info ├"),
-            WidgetStyle::Identity => Block::default()
-                .border_style(Style::default().fg(Color::Blue))
+                .title("┤ canister info ├")
+                .title(
+                    Title::from(format!(
+                        "┤ call method {} projects {} ├",
+                        key(Action::ToggleCaller),
+                        key(Action::ToggleProjects)
+                    ))
+                    .alignment(Alignment::Center)
+                    .position(Position::Bottom),
+                ),
+            WidgetStyle::Caller => Block::default()
+                .border_style(Style::default().fg(self.config.theme.canister_info))
                 .border_type(BorderType::Rounded)
-                .title("┤ dfx identity ├")
+                .borders(Borders::ALL)
+                .title("┤ call canister method ├")
                 .title(
-                    Title::from(format!("┤ select identity [i] ├",))
+                    Title::from(format!(
+                        "┤ {} {}, {} {}, close {} ├",
+                        match self.caller.focus {
+                            CallerFocus::Methods => "call",
+                            CallerFocus::History => "re-run",
+                        },
+                        key(Action::Select),
+                        match self.caller.focus {
+                            CallerFocus::Methods => "history",
+                            CallerFocus::History => "methods",
+                        },
+                        key(Action::SwitchFocus),
+                        key(Action::Close)
+                    ))
+                    .alignment(Alignment::Center)
+                    .position(Position::Bottom),
+                ),
+            WidgetStyle::CanisterChart(title) => Block::default()
+                .border_style(Style::default().fg(self.config.theme.canister_info))
+                .border_type(BorderType::Rounded)
+                .borders(Borders::ALL)
+                .title(format!("┤ {title} ├")),
+            WidgetStyle::Dialog => Block::default()
+                .border_style(Style::default().fg(self.config.theme.dialog))
+                .border_type(BorderType::Double)
+                .borders(Borders::ALL)
+                .title("┤ confirm ├"),
+            WidgetStyle::Help => Block::default()
+                .border_style(Style::default().fg(self.config.theme.dialog))
+                .border_type(BorderType::Double)
+                .borders(Borders::ALL)
+                .title("┤ keys ├")
+                .title(
+                    Title::from("┤ any key closes ├")
                         .alignment(Alignment::Center)
                         .position(Position::Bottom),
+                ),
+            WidgetStyle::Projects => Block::default()
+                .border_style(Style::default().fg(self.config.theme.canister_info))
+                .border_type(BorderType::Rounded)
+                .borders(Borders::ALL)
+                .title("┤ projects ├")
+                .title(
+                    Title::from(format!(
+                        "┤ build {} deploy {} generate {} mode {} register {} close {} ├",
+                        key(Action::Build),
+                        key(Action::Deploy),
+                        key(Action::Generate),
+                        key(Action::InstallMode),
+                        key(Action::RegisterProject),
+                        key(Action::Close)
+                    ))
+                    .alignment(Alignment::Center)
+                    .position(Position::Bottom),
+                ),
+            WidgetStyle::LogFiles => Block::default()
+                .border_style(Style::default().fg(self.config.theme.logs))
+                .border_type(BorderType::Rounded)
+                .title("┤ follow a log file ├")
+                .title(
+                    Title::from(format!(
+                        "┤ open {} parent {} cancel {} help {} ├",
+                        key(Action::PickerOpen),
+                        key(Action::PickerParent),
+                        key(Action::Close),
+                        key(Action::Help)
+                    ))
+                    .alignment(Alignment::Center)
+                    .position(Position::Bottom),
+                )
+                .borders(Borders::ALL),
+            WidgetStyle::Identity => Block::default()
+                .border_style(Style::default().fg(self.config.theme.identity))
+                .border_type(BorderType::Rounded)
+                .title("┤ dfx identity ├")
+                .title(
+                    Title::from(format!(
+                        "┤ select identity {} ├",
+                        key(Action::SelectIdentity)
+                    ))
+                    .alignment(Alignment::Center)
+                    .position(Position::Bottom),
                 )
                 .borders(Borders::ALL),
             WidgetStyle::Canisters => Block::default()
-                .border_style(Style::default().fg(Color::LightYellow))
+                .border_style(Style::default().fg(self.config.theme.canisters))
                 .border_type(BorderType::Rounded)
                 .title("┤ canisters ├")
+                .title(
+                    Title::from(format!(
+                        "┤ sort {}: {} ├",
+                        key(Action::SortCanisters),
+                        self.canister_tree.sort_by
+                    ))
+                    .alignment(Alignment::Right),
+                )
+                .title(
+                    Title::from(format!(
+                        "┤ start {} stop {} deposit {} uninstall {} delete {} ├",
+                        key(Action::StartCanister),
+                        key(Action::StopCanister),
+                        key(Action::DepositCycles),
+                        key(Action::UninstallCode),
+                        key(Action::DeleteCanister)
+                    ))
+                    .alignment(Alignment::Center)
+                    .position(Position::Bottom),
+                )
                 .borders(Borders::ALL),
             WidgetStyle::Replica => Block::default()
                 .title("┤ networks ├")
                 .title(
                     Title::from(format!(
-                        "┤ quit [q], {} replica [s], [-] {}ms [+] ├",
+                        "┤ help {}, quit {}, {} replica {}{}, auto-restart {}: {}, {} {}ms {} ├",
+                        key(Action::Help),
+                        key(Action::Quit),
                         if self.replica.state == LocalReplicaState::Running {
                             "stop"
                         } else {
                             "start"
                         },
-                        self.refresh_interval.as_millis()
+                        key(Action::StartStopReplica),
+                        match self.config.start_profiles.len().min(9) {
+                            _ if self.replica_process.is_some() => {
+                                format!(", restart {}", key(Action::RestartReplica))
+                            }
+                            _ if self.replica.state == LocalReplicaState::Running => String::new(),
+                            0 => String::new(),
+                            1 => ", profile [1]".to_string(),
+                            n => format!(", profiles [1-{n}]"),
+                        },
+                        key(Action::ToggleAutoRestart),
+                        if self.auto_restart.enabled {
+                            "on"
+                        } else {
+                            "off"
+                        },
+                        key(Action::RefreshFaster),
+                        self.refresh_interval.as_millis(),
+                        key(Action::RefreshSlower)
                     ))
                     .alignment(Alignment::Right)
                     .position(Position::Top),
                 )
                 .title(
-                    Title::from(format!("┤ select network [n] ├",))
+                    Title::from(format!("┤ select network {} ├", key(Action::SelectNetwork)))
                         .alignment(Alignment::Center)
                         .position(Position::Bottom),
                 )
-                .border_style(Style::default().fg(Color::Red))
+                .border_style(Style::default().fg(self.config.theme.network))
                 .border_type(BorderType::Rounded)
                 .borders(Borders::ALL),
         }
@@ -0,0 +1,389 @@
+use crate::{
+    app::{keymap::Action, keymap::Keymap, poller::Source},
+    discovery::expand_home,
+    replica_supervisor::StartOptions,
+};
+use anyhow::{Context, Result};
+use ratatui::style::Color;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Bounds of the configured refresh intervals.
+const MIN_REFRESH: Duration = Duration::from_millis(100);
+const MAX_REFRESH: Duration = Duration::from_secs(600);
+
+// Settings of the extension, kept in "config.json" next to the project database.
+// Every key is optional; unknown keys are rejected so typos are noticed
+// ```
+// {
+//   "refresh": { "replica_ms": 1500, "identity_ms": 5000 },
+//   "default_network": "local",
+//   "default_identity": "alice",
+//   "theme": { "logs": "lightcyan", "selected": "#ff8800" },
+//   "layout": { "top_percent": 30, "canisters_percent": 40 },
+//   "keybindings": { "search-logs": ["/", "ctrl+f"], "help": ["?", "f1"] },
+//   "auto_restart": true,
+//   "discovery": { "roots": ["~/code"], "max_depth": 3, "ignore": ["node_modules", "old-*"] },
+//   "start_profiles": [
+//     {
+//       "name": "clean with bitcoin",
+//       "clean": true,
+//       "enable_bitcoin": true
+//     }
+//   ]
+// }
+// ```
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub refresh: RefreshConfig,
+    /// Network selected on startup, built in or from networks.json.
+    pub default_network: String,
+    /// Identity selected on startup instead of dfx's current one.
+    pub default_identity: Option<String>,
+    pub theme: Theme,
+    pub layout: LayoutConfig,
+    /// Keys per action, replacing the action's default keys.
+    pub keybindings: BTreeMap<Action, Vec<String>>,
+    /// Saved `dfx start` options; the first nine start with their number key.
+    pub start_profiles: Vec<StartProfile>,
+    /// Whether a crashed replica is restarted, with backoff, by default.
+    pub auto_restart: bool,
+    pub discovery: DiscoveryConfig,
+    #[serde(skip)]
+    pub path: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            refresh: RefreshConfig::default(),
+            default_network: "local".to_string(),
+            default_identity: None,
+            theme: Theme::default(),
+            layout: LayoutConfig::default(),
+            keybindings: BTreeMap::new(),
+            start_profiles: vec![],
+            auto_restart: false,
+            discovery: DiscoveryConfig::default(),
+            path: PathBuf::new(),
+        }
+    }
+}
+
+/// How often each data source is polled, in milliseconds.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct RefreshConfig {
+    /// Starting value; `+` and `-` change it between 1 and 10 seconds.
+    pub replica_ms: u64,
+    pub identity_ms: u64,
+    pub identities_ms: u64,
+    pub projects_ms: u64,
+    /// How often the discovery roots are scanned for new projects.
+    pub discovery_ms: u64,
+}
+
+impl Default for RefreshConfig {
+    fn default() -> Self {
+        RefreshConfig {
+            replica_ms: 1500,
+            identity_ms: 5000,
+            identities_ms: 10_000,
+            projects_ms: 5000,
+            discovery_ms: 60_000,
+        }
+    }
+}
+
+impl RefreshConfig {
+    pub fn interval(&self, source: Source) -> Duration {
+        Duration::from_millis(match source {
+            Source::Replica => self.replica_ms,
+            Source::Identity => self.identity_ms,
+            Source::Identities => self.identities_ms,
+            Source::Projects => self.projects_ms,
+            Source::Discovery => self.discovery_ms,
+        })
+    }
+}
+
+/// Border colors of the panes and the color of selected list items, as
+/// color names (`lightcyan`), indices (`208`) or `#rrggbb`.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    #[serde(with = "color")]
+    pub network: Color,
+    #[serde(with = "color")]
+    pub identity: Color,
+    #[serde(with = "color")]
+    pub canisters: Color,
+    /// Canister info, caller, projects and charts.
+    #[serde(with = "color")]
+    pub canister_info: Color,
+    #[serde(with = "color")]
+    pub logs: Color,
+    #[serde(with = "color")]
+    pub dialog: Color,
+    #[serde(with = "color")]
+    pub selected: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            network: Color::Red,
+            identity: Color::Blue,
+            canisters: Color::LightYellow,
+            canister_info: Color::LightMagenta,
+            logs: Color::LightCyan,
+            dialog: Color::LightRed,
+            selected: Color::LightRed,
+        }
+    }
+}
+
+mod color {
+    use ratatui::style::Color;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(color)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse()
+            .map_err(|_| D::Error::custom(format!("unknown color '{name}'")))
+    }
+}
+
+/// Sizes of the panes, in percent, see [`get_layout`](crate::app::layout::get_layout).
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct LayoutConfig {
+    /// Height of the network and identity row.
+    pub top_percent: u16,
+    /// Width of the network pane within the top row.
+    pub network_percent: u16,
+    /// Width of the canister column below it; the logs take the rest.
+    pub canisters_percent: u16,
+    /// Height of the canister list within the canister column.
+    pub canister_list_percent: u16,
+}
+
+impl Default for LayoutConfig {
+    fn default() -> Self {
+        LayoutConfig {
+            top_percent: 25,
+            network_percent: 25,
+            canisters_percent: 35,
+            canister_list_percent: 82,
+        }
+    }
+}
+
+/// Where the background scanner looks for dfx projects that are not
+/// registered yet, see [`find_projects`](crate::discovery::find_projects).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DiscoveryConfig {
+    /// Directories to scan, `~` being the home directory; none turns the
+    /// scanner off.
+    pub roots: Vec<PathBuf>,
+    /// How many directories below a root a dfx.json is looked for.
+    pub max_depth: usize,
+    /// Names of directories never entered, `*` matching any characters.
+    pub ignore: Vec<String>,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        DiscoveryConfig {
+            roots: vec![],
+            max_depth: 4,
+            ignore: ["node_modules", "target", "dist", ".*"]
+                .map(String::from)
+                .to_vec(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StartProfile {
+    pub name: String,
+    #[serde(flatten)]
+    pub options: StartOptions,
+}
+
+impl Config {
+    /// Reads the config at `path`; a missing file gives the defaults.
+    pub fn load(path: &Path) -> Result<Config> {
+        let mut config = if path.exists() {
+            let content =
+                fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse config {:?}", path))?
+        } else {
+            Config::default()
+        };
+        config.path = path.to_path_buf();
+        Ok(config)
+    }
+
+    /// Everything wrong with the values, one message each.
+    pub fn validate(&self) -> Vec<String> {
+        let mut errors = vec![];
+        for source in Source::ALL {
+            let interval = self.refresh.interval(source);
+            if !(MIN_REFRESH..=MAX_REFRESH).contains(&interval) {
+                errors.push(format!(
+                    "refresh: {source:?} every {}ms is outside {}ms to {}s",
+                    interval.as_millis(),
+                    MIN_REFRESH.as_millis(),
+                    MAX_REFRESH.as_secs()
+                ));
+            }
+        }
+        if self.default_network.trim().is_empty() {
+            errors.push("default_network: must not be empty".to_string());
+        }
+        let layout = [
+            ("top_percent", self.layout.top_percent),
+            ("network_percent", self.layout.network_percent),
+            ("canisters_percent", self.layout.canisters_percent),
+            ("canister_list_percent", self.layout.canister_list_percent),
+        ];
+        for (name, percent) in layout {
+            if !(10..=90).contains(&percent) {
+                errors.push(format!("layout.{name}: {percent} is outside 10 to 90"));
+            }
+        }
+        if !(1..=16).contains(&self.discovery.max_depth) {
+            errors.push(format!(
+                "discovery.max_depth: {} is outside 1 to 16",
+                self.discovery.max_depth
+            ));
+        }
+        for root in &self.discovery.roots {
+            if !expand_home(root).is_dir() {
+                errors.push(format!(
+                    "discovery.roots: {} is not a directory",
+                    root.display()
+                ));
+            }
+        }
+        if let Err(keys) = self.keymap() {
+            errors.extend(keys);
+        }
+        let mut names: Vec<&str> = self
+            .start_profiles
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        names.sort();
+        names.dedup();
+        if names.len() != self.start_profiles.len() {
+            errors.push("start_profiles: profile names must be unique".to_string());
+        }
+        errors
+    }
+
+    pub fn keymap(&self) -> Result<Keymap, Vec<String>> {
+        Keymap::with_overrides(&self.keybindings)
+    }
+
+    /// Writes the start profiles to the file, leaving the rest of it as the
+    /// user wrote it. Overrides from the command line are not saved.
+    fn save_start_profiles(&self) -> Result<()> {
+        let mut on_disk: serde_json::Value = match fs::read_to_string(&self.path) {
+            Ok(content) => serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse config {:?}", self.path))?,
+            Err(_) => serde_json::json!({}),
+        };
+        on_disk["start_profiles"] = serde_json::to_value(&self.start_profiles)?;
+        let serialized =
+            serde_json::to_string_pretty(&on_disk).with_context(|| "Failed to serialize config")?;
+        fs::write(&self.path, serialized)
+            .with_context(|| format!("Failed to write config to {:?}", self.path))?;
+        Ok(())
+    }
+
+    /// Adds a start profile, or replaces the one with the same name, and saves.
+    pub fn save_start_profile(&mut self, name: &str, options: StartOptions) -> Result<()> {
+        let profile = StartProfile {
+            name: name.to_string(),
+            options,
+        };
+        match self.start_profiles.iter_mut().find(|p| p.name == name) {
+            Some(existing) => *existing = profile,
+            None => self.start_profiles.push(profile),
+        }
+        self.save_start_profiles()
+    }
+
+    /// Removes the start profile called `name`, if there is one, and saves.
+    pub fn remove_start_profile(&mut self, name: &str) -> Result<()> {
+        self.start_profiles.retain(|p| p.name != name);
+        self.save_start_profiles()
+    }
+}
+
+#[test]
+fn test_start_profiles_round_trip() {
+    let dir = crate::test_util::TempDir::new("config");
+    let path = dir.join("config.json");
+    let mut config = Config::load(&path).unwrap();
+    assert!(config.start_profiles.is_empty());
+
+    let clean = StartOptions {
+        clean: true,
+        enable_bitcoin: true,
+        ..Default::default()
+    };
+    config
+        .save_start_profile("btc", StartOptions::default())
+        .unwrap();
+    config.save_start_profile("btc", clean.clone()).unwrap();
+    let loaded = Config::load(&path).unwrap();
+    assert_eq!(loaded, config);
+    assert_eq!(loaded.start_profiles.len(), 1);
+    assert_eq!(loaded.start_profiles[0].options, clean);
+}
+
+#[test]
+fn test_validate_reports_every_error() {
+    let config: Config = serde_json::from_str(
+        r##"{
+            "refresh": { "identity_ms": 10 },
+            "theme": { "logs": "#00ffff" },
+            "layout": { "top_percent": 95 },
+            "keybindings": { "down": ["j", "ctrl+"] }
+        }"##,
+    )
+    .unwrap();
+    assert_eq!(config.theme.logs, Color::Rgb(0, 255, 255));
+    assert_eq!(config.refresh.replica_ms, 1500);
+    assert_eq!(
+        config.validate(),
+        vec![
+            "refresh: Identity every 10ms is outside 100ms to 600s",
+            "layout.top_percent: 95 is outside 10 to 90",
+            "keybindings.down: unknown key 'ctrl+'",
+        ]
+    );
+
+    let typo = serde_json::from_str::<Config>(r#"{ "auto_restrat": true }"#);
+    assert!(typo.unwrap_err().to_string().contains("unknown field"));
+    let color = serde_json::from_str::<Config>(r#"{ "theme": { "logs": "cyanish" } }"#);
+    assert!(color
+        .unwrap_err()
+        .to_string()
+        .contains("unknown color 'cyanish'"));
+}
@@ -1,12 +1,53 @@
-use crate::AppState;
-use std::io::BufRead;
-use std::process::{Command, Stdio};
+use serde::Deserialize;
+use std::io::{BufRead, Read};
+use std::path::PathBuf;
+use std::process::{Command, ExitStatus, Stdio};
 use std::sync::mpsc;
-use std::{io, thread};
+use std::time::{Duration, Instant};
+use std::{fmt, io, thread};
 
 #[allow(unreachable_code, unused_imports, unused_variables, dead_code)]
 pub enum DfxCommands {
-    CyclesBalance,         // Get the cycle balance of the selected Identity's cycles wallet
+    Build {
+        canister: Option<String>,
+    }, // Builds all or one of the project's canisters
+    CanisterCall {
+        canister_id: String,
+        method: String,
+        args: String,
+        query: bool,
+    }, // Calls a canister method with Candid arguments
+    CanisterDelete {
+        canister_id: String,
+    }, // Deletes a stopped canister and withdraws its cycles
+    CanisterDepositCycles {
+        canister_id: String,
+        cycles: u128,
+    }, // Deposits cycles from the wallet into a canister
+    CanisterStart {
+        canister_id: String,
+    }, // Starts a stopped canister
+    CanisterStop {
+        canister_id: String,
+    }, // Stops a running canister
+    CanisterUninstallCode {
+        canister_id: String,
+    }, // Removes a canister's code and state, keeping its id and cycles
+    CanisterMetadata {
+        canister_id: String,
+        name: String,
+    }, // Reads a public metadata section of a canister, e.g. candid:service
+    CanisterStatus {
+        canister_id: String,
+    }, // Returns the current status of a canister via the management canister
+    CyclesBalance, // Get the cycle balance of the selected Identity on the cycles ledger
+    Deploy {
+        canister: Option<String>,
+        mode: Option<InstallMode>,
+    }, // Deploys all or one of the project's canisters
+    Generate {
+        canister: Option<String>,
+    }, // Generates type declarations for the project's canisters
     IdentityGetPrincipal, // Shows the textual representation of the Principal associated with the current identity
     IdentityGetWallet, // Gets the canister ID for the wallet associated with your identity on a network
     IdentityList,      // Lists existing identities
@@ -21,210 +62,953 @@ pub enum DfxCommands {
     Ping,              // Ping the IC replica
     StopReplica,       // Stop a local replica
     Version,           // Prints the dfx version
+    WalletBalance,     // Get the cycle balance of the selected Identity's cycles wallet
 }
 
-impl DfxCommands {
-    pub fn run(&self, app_state: &AppState, path_to_dfx: &str) -> String {
-        let identity = app_state.selected_identity.clone().trim().to_string();
-        let network = app_state.selected_network.clone();
-        //dbg!(network.clone());
+/// `--mode` of `dfx deploy`; dfx picks install or upgrade when none is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallMode {
+    Upgrade,
+    Reinstall,
+}
+
+impl fmt::Display for InstallMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            DfxCommands::CyclesBalance => {
-                let output = Command::new(path_to_dfx)
-                    .args(["--identity", identity.as_str()])
-                    // .args(["cycles", "balance"]) // TODO
-                    .args(["wallet", "balance"])
-                    // .args(["--network", network.as_str()]) // TODO
-                    // .args(["--network", "ic"])
-                    .output()
-                    .expect("Failed to execute dfx cycles balance");
-                String::from_utf8_lossy(&output.stdout).to_string()
-            }
-            DfxCommands::IdentityGetPrincipal => {
-                let output = Command::new(path_to_dfx)
-                    .args(["--identity", identity.as_str()])
-                    .args(["identity", "get-principal"])
-                    .args(["--network", network.as_str()])
-                    .output()
-                    .expect("Failed to execute dfx identity get-principal");
-                String::from_utf8_lossy(&output.stdout).to_string()
-            }
-            DfxCommands::IdentityGetWallet => {
-                let output = Command::new(path_to_dfx)
-                    .args(["--identity", identity.as_str()])
-                    .args(["identity", "get-wallet"])
-                    .args(["--network", network.as_str()])
-                    .output()
-                    .expect("Failed to execute dfx identity get-wallet");
-                String::from_utf8_lossy(&output.stdout).to_string()
-            }
-            DfxCommands::IdentityList => {
-                let output = Command::new(path_to_dfx)
-                    .args(["identity", "list"])
-                    .output()
-                    .expect("Failed to execute dfx identity list");
-                String::from_utf8_lossy(&output.stdout).to_string()
-            }
-            DfxCommands::IdentityWhoami => {
-                let output = Command::new(path_to_dfx)
-                    .args(["identity", "whoami"])
-                    .args(["--network", network.as_str()])
-                    .output()
-                    .expect("Failed to execute dfx identity whoami");
-                String::from_utf8_lossy(&output.stdout).to_string()
+            InstallMode::Upgrade => write!(f, "upgrade"),
+            InstallMode::Reinstall => write!(f, "reinstall"),
+        }
+    }
+}
+
+/// How long a single dfx invocation may take before it is killed.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Everything a dfx invocation needs to know about the current selection.
+/// Cheap to clone, so background workers can take a snapshot of it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DfxContext {
+    pub path_to_dfx: String,
+    pub identity: String,
+    pub network: String,
+    pub timeout: Duration,
+    /// Directory to run dfx in, needed for networks only a project defines.
+    pub cwd: Option<PathBuf>,
+}
+
+impl Default for DfxContext {
+    fn default() -> Self {
+        DfxContext {
+            path_to_dfx: "dfx".to_string(),
+            identity: String::new(),
+            network: String::new(),
+            timeout: DEFAULT_TIMEOUT,
+            cwd: None,
+        }
+    }
+}
+
+/// Parsed output of a successful dfx invocation, one variant per command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DfxOutput {
+    CallReply(String),
+    /// Everything a canister lifecycle command printed, stdout and stderr.
+    CanisterAction(String),
+    /// Everything a build, deploy or generate printed, stdout and stderr.
+    ProjectTask(String),
+    CanisterStatus(CanisterStatus),
+    Metadata(String),
+    Cycles(u128),
+    Principal(String),
+    Wallet(String),
+    Identities(Vec<String>),
+    Identity(String),
+    NetworksJsonPath(PathBuf),
+    Port(u16),
+    ReplicaRev(String),
+    AccountId(String),
+    Icp(IcpBalance),
+    SubnetTypes(Vec<String>),
+    Ping(PingOutput),
+    Stopped(String),
+    Version(String),
+}
+
+#[derive(Deserialize, Debug, PartialEq, Default, Clone)]
+pub struct PingOutput {
+    pub ic_api_version: String,
+    pub replica_health_status: String,
+    pub root_key: Vec<u8>,
+    pub certified_height: Option<u64>,
+    pub impl_hash: Option<String>,
+}
+
+/// Result of the management canister's `canister_status`, as printed by
+/// `dfx canister status`. Values are kept verbatim.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CanisterStatus {
+    pub canister_id: String,
+    pub status: String,
+    pub controllers: String,
+    pub memory_allocation: String,
+    pub compute_allocation: String,
+    pub freezing_threshold: String,
+    pub memory_size: String,
+    pub balance: String,
+    pub module_hash: String,
+}
+
+/// Ledger balance in e8s (1 ICP = 10^8 e8s).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct IcpBalance {
+    pub e8s: u64,
+}
+
+impl fmt::Display for IcpBalance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{:08} ICP",
+            self.e8s / 100_000_000,
+            self.e8s % 100_000_000
+        )
+    }
+}
+
+/// A balance that may legitimately not exist on the selected network, e.g. the
+/// ICP ledger on a local replica without NNS, or a wallet that was never created.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Balance<T> {
+    Amount(T),
+    NotAvailable { network: String },
+}
+
+impl<T: fmt::Display> fmt::Display for Balance<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Balance::Amount(amount) => amount.fmt(f),
+            Balance::NotAvailable { network } => write!(f, "not available on '{network}'"),
+        }
+    }
+}
+
+impl<T> Balance<T> {
+    /// Turns "this ledger/wallet doesn't exist here" failures into
+    /// [`Balance::NotAvailable`] while keeping real errors as errors.
+    pub fn from_result(result: Result<T, DfxError>, network: &str) -> Result<Self, DfxError> {
+        match result {
+            Ok(amount) => Ok(Balance::Amount(amount)),
+            Err(e) if e.is_not_available() => Ok(Balance::NotAvailable {
+                network: network.to_string(),
+            }),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// Why a dfx invocation did not produce usable output.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DfxError {
+    /// The dfx binary could not be started at all (missing, not executable, ...).
+    Spawn { command: String, reason: String },
+    /// dfx ran but exited unsuccessfully.
+    Failed {
+        command: String,
+        exit_code: Option<i32>,
+        stderr: String,
+    },
+    /// dfx did not finish within [`DfxContext::timeout`] and was killed.
+    Timeout { command: String, after: Duration },
+    /// dfx succeeded but printed something we could not make sense of.
+    Parse {
+        command: String,
+        output: String,
+        reason: String,
+    },
+}
+
+impl DfxError {
+    /// One-line explanation suitable for a widget.
+    pub fn reason(&self) -> String {
+        match self {
+            DfxError::Spawn { reason, .. } => format!("cannot run dfx: {reason}"),
+            DfxError::Failed {
+                exit_code, stderr, ..
+            } => {
+                let message = stderr
+                    .lines()
+                    .map(str::trim)
+                    .rfind(|l| !l.is_empty())
+                    .unwrap_or("no output");
+                match exit_code {
+                    Some(code) => format!("exit code {code}: {message}"),
+                    None => format!("killed by signal: {message}"),
+                }
             }
-            DfxCommands::InfoNetworksJsonPath => {
-                let output = Command::new(path_to_dfx)
-                    .args(["--identity", identity.as_str()])
-                    .args(["info", "networks-json-path"])
-                    .args(["--network", network.as_str()])
-                    .output()
-                    .expect("Failed to execute dfx info networks-json-path");
-                String::from_utf8_lossy(&output.stdout).to_string()
+            DfxError::Timeout { after, .. } => format!("timed out after {}s", after.as_secs()),
+            DfxError::Parse { reason, .. } => format!("unexpected output: {reason}"),
+        }
+    }
+}
+
+impl DfxError {
+    /// Whether dfx failed because the ledger, wallet or subcommand being
+    /// queried doesn't exist on this network (or in this dfx version).
+    pub fn is_not_available(&self) -> bool {
+        let DfxError::Failed { stderr, .. } = self else {
+            return false;
+        };
+        let stderr = stderr.to_lowercase();
+        [
+            // The ledger/wallet canister isn't installed on this network.
+            "reject code destinationinvalid",
+            "ic0301",
+            "could not find wallet",
+            "no wallet",
+            "unrecognized subcommand",
+            "cannot find canister id",
+        ]
+        .iter()
+        .any(|pattern| stderr.contains(pattern))
+    }
+}
+
+impl fmt::Display for DfxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let command = match self {
+            DfxError::Spawn { command, .. }
+            | DfxError::Failed { command, .. }
+            | DfxError::Timeout { command, .. }
+            | DfxError::Parse { command, .. } => command,
+        };
+        write!(f, "`{command}` failed, {}", self.reason())
+    }
+}
+
+impl std::error::Error for DfxError {}
+
+impl DfxCommands {
+    pub fn run(&self, ctx: &DfxContext) -> Result<DfxOutput, DfxError> {
+        let args = self.args(ctx);
+        let (stdout, stderr) = execute(ctx, &args)?;
+        // older dfx versions print the canister status to stderr
+        let stdout = match self {
+            DfxCommands::CanisterStatus { .. } if stdout.trim().is_empty() => stderr,
+            // lifecycle commands report their progress on stderr
+            DfxCommands::CanisterDelete { .. }
+            | DfxCommands::CanisterDepositCycles { .. }
+            | DfxCommands::CanisterStart { .. }
+            | DfxCommands::CanisterStop { .. }
+            | DfxCommands::CanisterUninstallCode { .. }
+            | DfxCommands::Build { .. }
+            | DfxCommands::Deploy { .. }
+            | DfxCommands::Generate { .. } => format!("{stdout}{stderr}"),
+            _ => stdout,
+        };
+        self.parse(&stdout).map_err(|reason| DfxError::Parse {
+            command: command_line(&args),
+            output: stdout,
+            reason,
+        })
+    }
+
+    fn args(&self, ctx: &DfxContext) -> Vec<String> {
+        let identity = ctx.identity.trim();
+        let network = ctx.network.as_str();
+        let cycles = match self {
+            DfxCommands::CanisterDepositCycles { cycles, .. } => cycles.to_string(),
+            _ => String::new(),
+        };
+        let mode = match self {
+            DfxCommands::Deploy {
+                mode: Some(mode), ..
+            } => mode.to_string(),
+            _ => String::new(),
+        };
+        let args: Vec<&str> = match self {
+            DfxCommands::Build { canister } => {
+                let mut build = vec!["--identity", identity, "build"];
+                build.extend(canister.as_deref());
+                build.extend(["--network", network]);
+                build
             }
-            DfxCommands::InfoReplicaPort => {
-                let output = Command::new(path_to_dfx)
-                    .args(["info", "replica-port"])
-                    .output()
-                    .expect("Failed to execute dfx info replica-port");
-                String::from_utf8_lossy(&output.stdout).to_string()
+            DfxCommands::Deploy { canister, mode: m } => {
+                let mut deploy = vec!["--identity", identity, "deploy"];
+                deploy.extend(canister.as_deref());
+                if m.is_some() {
+                    deploy.extend(["--mode", &mode]);
+                }
+                if *m == Some(InstallMode::Reinstall) {
+                    // confirmed in the TUI already; dfx can't prompt without a tty
+                    deploy.push("--yes");
+                }
+                deploy.extend(["--network", network]);
+                deploy
             }
-            DfxCommands::InfoReplicaRev => {
-                let output = Command::new(path_to_dfx)
-                    .args(["info", "replica-rev"])
-                    .output()
-                    .expect("Failed to execute dfx info replica-rev");
-                String::from_utf8_lossy(&output.stdout).to_string()
+            DfxCommands::Generate { canister } => {
+                let mut generate = vec!["--identity", identity, "generate"];
+                generate.extend(canister.as_deref());
+                generate.extend(["--network", network]);
+                generate
             }
-            DfxCommands::InfoWebserverPort => {
-                let output = Command::new(path_to_dfx)
-                    .args(["info", "webserver-port"])
-                    .output()
-                    .expect("Failed to execute dfx info webserver-port");
-                String::from_utf8_lossy(&output.stdout).to_string()
+            DfxCommands::CanisterCall {
+                canister_id,
+                method,
+                args,
+                query,
+            } => {
+                let mut call = vec![
+                    "--identity",
+                    identity,
+                    "canister",
+                    "call",
+                    canister_id,
+                    method,
+                    if args.trim().is_empty() {
+                        "()"
+                    } else {
+                        args.trim()
+                    },
+                    "--network",
+                    network,
+                ];
+                if *query {
+                    call.push("--query");
+                }
+                call
             }
-            DfxCommands::LedgerAccountId => {
-                let output = Command::new(path_to_dfx)
-                    .args(["--identity", identity.as_str()])
-                    .args(["ledger", "account-id"])
-                    .args(["--network", network.as_str()])
-                    .output()
-                    .expect("Failed to execute dfx ledger account-id");
-                String::from_utf8_lossy(&output.stdout).to_string()
+            DfxCommands::CanisterDelete { canister_id } => vec![
+                "--identity",
+                identity,
+                "canister",
+                "delete",
+                canister_id,
+                // confirmed in the TUI already; dfx can't prompt without a tty
+                "--yes",
+                "--network",
+                network,
+            ],
+            DfxCommands::CanisterDepositCycles { canister_id, .. } => vec![
+                "--identity",
+                identity,
+                "canister",
+                "deposit-cycles",
+                &cycles,
+                canister_id,
+                "--network",
+                network,
+            ],
+            DfxCommands::CanisterStart { canister_id } => vec![
+                "--identity",
+                identity,
+                "canister",
+                "start",
+                canister_id,
+                "--network",
+                network,
+            ],
+            DfxCommands::CanisterStop { canister_id } => vec![
+                "--identity",
+                identity,
+                "canister",
+                "stop",
+                canister_id,
+                "--network",
+                network,
+            ],
+            DfxCommands::CanisterUninstallCode { canister_id } => vec![
+                "--identity",
+                identity,
+                "canister",
+                "uninstall-code",
+                canister_id,
+                "--network",
+                network,
+            ],
+            DfxCommands::CanisterMetadata { canister_id, name } => vec![
+                "--identity",
+                identity,
+                "canister",
+                "metadata",
+                canister_id,
+                name,
+                "--network",
+                network,
+            ],
+            DfxCommands::CanisterStatus { canister_id } => vec![
+                "--identity",
+                identity,
+                "canister",
+                "status",
+                canister_id,
+                "--network",
+                network,
+            ],
+            DfxCommands::CyclesBalance => vec![
+                "--identity",
+                identity,
+                "cycles",
+                "balance",
+                "--precise",
+                "--network",
+                network,
+            ],
+            DfxCommands::IdentityGetPrincipal => vec![
+                "--identity",
+                identity,
+                "identity",
+                "get-principal",
+                "--network",
+                network,
+            ],
+            DfxCommands::IdentityGetWallet => vec![
+                "--identity",
+                identity,
+                "identity",
+                "get-wallet",
+                "--network",
+                network,
+            ],
+            DfxCommands::IdentityList => vec!["identity", "list"],
+            DfxCommands::IdentityWhoami => vec!["identity", "whoami", "--network", network],
+            DfxCommands::InfoNetworksJsonPath => vec![
+                "--identity",
+                identity,
+                "info",
+                "networks-json-path",
+                "--network",
+                network,
+            ],
+            DfxCommands::InfoReplicaPort => vec!["info", "replica-port"],
+            DfxCommands::InfoReplicaRev => vec!["info", "replica-rev"],
+            DfxCommands::InfoWebserverPort => vec!["info", "webserver-port"],
+            DfxCommands::LedgerAccountId => vec![
+                "--identity",
+                identity,
+                "ledger",
+                "account-id",
+                "--network",
+                network,
+            ],
+            DfxCommands::LedgerBalance => vec![
+                "--identity",
+                identity,
+                "ledger",
+                "balance",
+                "--network",
+                network,
+            ],
+            DfxCommands::LedgerShowSubnetTypes => vec![
+                "--identity",
+                identity,
+                "ledger",
+                "show-subnet-types",
+                "--network",
+                network,
+            ],
+            DfxCommands::Ping => vec!["ping", network],
+            DfxCommands::StopReplica => vec!["stop"],
+            DfxCommands::Version => vec!["--version"],
+            DfxCommands::WalletBalance => vec![
+                "--identity",
+                identity,
+                "wallet",
+                "balance",
+                "--precise",
+                "--network",
+                network,
+            ],
+        };
+        args.into_iter().map(String::from).collect()
+    }
+
+    fn parse(&self, stdout: &str) -> Result<DfxOutput, String> {
+        let trimmed = stdout.trim();
+        let non_empty = || {
+            if trimmed.is_empty() {
+                Err("empty output".to_string())
+            } else {
+                Ok(trimmed.to_string())
             }
-            DfxCommands::LedgerBalance => {
-                let output = Command::new(path_to_dfx)
-                    .args(["--identity", identity.as_str()])
-                    .args(["ledger", "balance"])
-                    // .args(["--network", network.as_str()]) // TODO
-                    .args(["--network", "ic"])
-                    .output()
-                    .expect("Failed to execute dfx ledger balance");
-                String::from_utf8_lossy(&output.stdout).to_string()
+        };
+        match self {
+            // an empty reply is the unit value
+            DfxCommands::CanisterCall { .. } if trimmed.is_empty() => {
+                Ok(DfxOutput::CallReply("()".to_string()))
             }
-            DfxCommands::LedgerShowSubnetTypes => {
-                let output = Command::new(path_to_dfx)
-                    .args(["--identity", identity.as_str()])
-                    .args(["ledger", "show-subnet-types"])
-                    .args(["--network", network.as_str()])
-                    .output()
-                    .expect("Failed to execute dfx ledger show-subnet-types");
-                String::from_utf8_lossy(&output.stdout).to_string()
+            DfxCommands::CanisterCall { .. } => Ok(DfxOutput::CallReply(trimmed.to_string())),
+            DfxCommands::CanisterDelete { .. }
+            | DfxCommands::CanisterDepositCycles { .. }
+            | DfxCommands::CanisterStart { .. }
+            | DfxCommands::CanisterStop { .. }
+            | DfxCommands::CanisterUninstallCode { .. } => {
+                Ok(DfxOutput::CanisterAction(trimmed.to_string()))
             }
-            DfxCommands::Ping => {
-                let output = Command::new(path_to_dfx)
-                    .args(["ping", network.as_str()])
-                    .output()
-                    .expect("Failed to execute dfx ping ic");
-                if output.status.success() {
-                    String::from_utf8_lossy(&output.stdout)
-                        .to_string()
-                        .replace("  ", ", ")
-                        .replace("\n,", "")
-                } else {
-                    "Error".to_string()
-                }
+            DfxCommands::Build { .. }
+            | DfxCommands::Deploy { .. }
+            | DfxCommands::Generate { .. } => Ok(DfxOutput::ProjectTask(trimmed.to_string())),
+            DfxCommands::CanisterMetadata { .. } => non_empty().map(DfxOutput::Metadata),
+            DfxCommands::CanisterStatus { canister_id } => {
+                parse_canister_status(canister_id, trimmed).map(DfxOutput::CanisterStatus)
             }
-            DfxCommands::StopReplica => {
-                let output = Command::new(path_to_dfx)
-                    .args(["stop"])
-                    .output()
-                    .expect("Failed to execute dfx stop replica");
-                format!(
-                    "{}{}",
-                    String::from_utf8_lossy(&output.stdout).to_string(),
-                    String::from_utf8_lossy(&output.stderr).to_string()
-                )
+            DfxCommands::CyclesBalance | DfxCommands::WalletBalance => {
+                parse_cycles(trimmed).map(DfxOutput::Cycles)
             }
-            DfxCommands::Version => {
-                let output = Command::new(path_to_dfx)
-                    .args(["version"])
-                    .output()
-                    .expect("Failed to execute dfx --version");
-                String::from_utf8_lossy(&output.stdout).to_string()
+            DfxCommands::IdentityGetPrincipal => non_empty().map(DfxOutput::Principal),
+            DfxCommands::IdentityGetWallet => non_empty().map(DfxOutput::Wallet),
+            DfxCommands::IdentityList => Ok(DfxOutput::Identities(parse_identity_list(trimmed))),
+            DfxCommands::IdentityWhoami => non_empty().map(DfxOutput::Identity),
+            DfxCommands::InfoNetworksJsonPath => {
+                non_empty().map(|p| DfxOutput::NetworksJsonPath(PathBuf::from(p)))
             }
+            DfxCommands::InfoReplicaPort | DfxCommands::InfoWebserverPort => trimmed
+                .parse()
+                .map(DfxOutput::Port)
+                .map_err(|e| format!("invalid port {trimmed:?}: {e}")),
+            DfxCommands::InfoReplicaRev => non_empty().map(DfxOutput::ReplicaRev),
+            DfxCommands::LedgerAccountId => non_empty().map(DfxOutput::AccountId),
+            DfxCommands::LedgerBalance => parse_icp(trimmed).map(DfxOutput::Icp),
+            DfxCommands::LedgerShowSubnetTypes => Ok(DfxOutput::SubnetTypes(
+                trimmed
+                    .trim_matches(|c| c == '[' || c == ']')
+                    .split([',', '\n'])
+                    .map(|s| s.trim().trim_matches('"').to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect(),
+            )),
+            DfxCommands::Ping => parse_ping(stdout).map(DfxOutput::Ping),
+            DfxCommands::StopReplica => Ok(DfxOutput::Stopped(trimmed.to_string())),
+            DfxCommands::Version => Ok(DfxOutput::Version(
+                trimmed.trim_start_matches("dfx").trim().to_string(),
+            )),
         }
     }
-    pub fn start_replica_stream(path_to_dfx: String) -> mpsc::Receiver<String> {
+
+    /// Runs the command in `ctx.cwd` without a timeout and streams stdout and
+    /// stderr line by line, ending with the exit status. The receiver
+    /// disconnects once dfx has exited.
+    pub fn stream(&self, ctx: &DfxContext) -> mpsc::Receiver<String> {
         let (sender, receiver) = mpsc::channel();
-        // Spawn a new thread to run the command
-        let cloned_path_to_dfx = path_to_dfx.clone();
+        let args = self.args(ctx);
+        let command = command_line(&args);
+        let mut dfx = Command::new(&ctx.path_to_dfx);
+        if let Some(cwd) = &ctx.cwd {
+            dfx.current_dir(cwd);
+        }
+        dfx.args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
         thread::spawn(move || {
-            let mut child = Command::new(cloned_path_to_dfx)
-                .args(&["start"])
-                // .stdout(Stdio::piped())
-                .stderr(Stdio::piped()) // Capture stderr as well
-                .spawn()
-                .expect("Failed to start dfx start replica");
-
-            // Combine stdout and stderr into a single stream
-            // let stdout = child
-            //     .stdout
-            //     .take()
-            //     .expect("Failed to capture standard output.");
-            let stderr = child
-                .stderr
-                .take()
-                .expect("Failed to capture standard error.");
-            // let reader = io::BufReader::new(stdout);
-            let reader_err = io::BufReader::new(stderr);
-
-            // Use select to read from both stdout and stderr
-            // let stdout_lines = reader
-            //     .lines()
-            //     .map(|line| line.expect("Could not read line from stdout"));
-            let stderr_lines = reader_err
-                .lines()
-                .map(|line| line.expect("Could not read line from stderr"));
-
-            // for line in stdout_lines.chain(stderr_lines) {
-            for line in stderr_lines {
-                // dbg!(&line);
-                if sender.send(line).is_err() {
-                    break; // Stop if receiving end is dropped
+            let _ = sender.send(format!("$ {command}"));
+            let mut child = match dfx.spawn() {
+                Ok(child) => child,
+                Err(e) => {
+                    let _ = sender.send(format!("Failed to run {command}: {e}"));
+                    return;
                 }
+            };
+            let forward = |pipe: Option<Box<dyn Read + Send>>| {
+                let sender = sender.clone();
+                thread::spawn(move || {
+                    if let Some(pipe) = pipe {
+                        for line in io::BufReader::new(pipe).lines().map_while(Result::ok) {
+                            if sender.send(line).is_err() {
+                                break;
+                            }
+                        }
+                    }
+                })
+            };
+            let stdout = forward(child.stdout.take().map(|p| Box::new(p) as _));
+            let stderr = forward(child.stderr.take().map(|p| Box::new(p) as _));
+            let _ = stdout.join();
+            let _ = stderr.join();
+            let _ = sender.send(match child.wait() {
+                Ok(status) if status.success() => format!("{command}: done"),
+                Ok(status) => format!("{command}: failed with {status}"),
+                Err(e) => format!("{command}: {e}"),
+            });
+        });
+        receiver
+    }
+}
+
+/// Runs a command and unwraps the [`DfxOutput`] variant it always produces:
+/// `dfx_run!(IdentityGetPrincipal, &ctx, Principal)` is a `Result<String, DfxError>`.
+macro_rules! dfx_run {
+    ($command:ident, $ctx:expr, $variant:ident) => {
+        $crate::dfx_commands::DfxCommands::$command
+            .run($ctx)
+            .map(|output| match output {
+                $crate::dfx_commands::DfxOutput::$variant(value) => value,
+                other => unreachable!("{} produced {:?}", stringify!($command), other),
+            })
+    };
+}
+pub(crate) use dfx_run;
+
+fn command_line(args: &[String]) -> String {
+    format!("dfx {}", args.join(" "))
+}
+
+/// Runs dfx with `args`, capturing both streams and enforcing `ctx.timeout`.
+/// Returns stdout and stderr on a zero exit status.
+fn execute(ctx: &DfxContext, args: &[String]) -> Result<(String, String), DfxError> {
+    let command = command_line(args);
+    let mut dfx = Command::new(&ctx.path_to_dfx);
+    if let Some(cwd) = &ctx.cwd {
+        dfx.current_dir(cwd);
+    }
+    let mut child = dfx
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| DfxError::Spawn {
+            command: command.clone(),
+            reason: e.to_string(),
+        })?;
+
+    // Drain both pipes on their own threads so a chatty dfx can't fill a pipe
+    // buffer and block forever while we wait for it to exit.
+    let drain = |pipe: Option<Box<dyn Read + Send>>| {
+        thread::spawn(move || {
+            let mut buffer = Vec::new();
+            if let Some(mut pipe) = pipe {
+                let _ = pipe.read_to_end(&mut buffer);
             }
+            String::from_utf8_lossy(&buffer).to_string()
+        })
+    };
+    let stdout = drain(child.stdout.take().map(|p| Box::new(p) as _));
+    let stderr = drain(child.stderr.take().map(|p| Box::new(p) as _));
 
-            // kill the process forcefully
-            DfxCommands::StopReplica.run(&AppState::default(), &path_to_dfx);
-            // let _ = child.kill();
-            // Wait for the child process to finish, if needed
-            // let _ = child.wait();
+    let status = wait_with_timeout(&mut child, ctx.timeout).map_err(|e| DfxError::Spawn {
+        command: command.clone(),
+        reason: e.to_string(),
+    })?;
+    let Some(status) = status else {
+        return Err(DfxError::Timeout {
+            command,
+            after: ctx.timeout,
         });
+    };
 
-        receiver
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if status.success() {
+        Ok((stdout, stderr))
+    } else {
+        Err(DfxError::Failed {
+            command,
+            exit_code: status.code(),
+            stderr,
+        })
     }
+}
+
+/// Waits for `child` to exit; kills it and returns `None` once `timeout` passes.
+fn wait_with_timeout(
+    child: &mut std::process::Child,
+    timeout: Duration,
+) -> io::Result<Option<ExitStatus>> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if Instant::now() >= deadline {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+/// `dfx ping` prints a JSON-ish object whose fields are separated by double
+/// spaces instead of commas.
+fn parse_ping(stdout: &str) -> Result<PingOutput, String> {
+    let json = stdout.replace("  ", ", ").replace("\n,", "");
+    serde_json::from_str(&json).map_err(|e| e.to_string())
+}
 
-    // above function leaks output to my TUI app, which is not what I want, I want the Rx to swallow whole output and hold it until i use it
+/// Accepts both `dfx wallet balance --precise` ("93800000000000 cycles.") and
+/// the rounded form ("93.800 TC (trillion cycles).").
+fn parse_cycles(s: &str) -> Result<u128, String> {
+    let number = s
+        .split_whitespace()
+        .next()
+        .ok_or_else(|| "empty output".to_string())?
+        .replace(['_', ','], "");
+    if s.contains("TC") {
+        let tc = number
+            .parse::<f64>()
+            .map_err(|e| format!("invalid cycles {s:?}: {e}"))?;
+        Ok((tc * 1e12).round() as u128)
+    } else {
+        number
+            .parse()
+            .map_err(|e| format!("invalid cycles {s:?}: {e}"))
+    }
+}
+
+/// Parses "12.34500000 ICP" into e8s.
+fn parse_icp(s: &str) -> Result<IcpBalance, String> {
+    let amount = s
+        .strip_suffix("ICP")
+        .ok_or_else(|| format!("missing ICP suffix in {s:?}"))?
+        .trim();
+    let (whole, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if fraction.len() > 8 {
+        return Err(format!("too many decimals in {s:?}"));
+    }
+    let invalid = |e: std::num::ParseIntError| format!("invalid ICP amount {s:?}: {e}");
+    let whole: u64 = whole.parse().map_err(invalid)?;
+    let fraction: u64 = if fraction.is_empty() {
+        0
+    } else {
+        format!("{fraction:0<8}").parse().map_err(invalid)?
+    };
+    Ok(IcpBalance {
+        e8s: whole * 100_000_000 + fraction,
+    })
+}
+
+/// Parses the "Key: value" lines of `dfx canister status`:
+/// ```text
+/// Canister status call result for bkyz2-fmaaa-aaaaa-qaaaq-cai.
+/// Status: Running
+/// Controllers: bnz7o-iuaaa-aaaaa-qaaaa-cai trg6r-vqw4x-...
+/// Memory allocation: 0
+/// Compute allocation: 0
+/// Freezing threshold: 2_592_000
+/// Memory Size: Nat(2294162)
+/// Balance: 3_100_000_000_000 Cycles
+/// Module hash: 0x2e3a...
+/// ```
+fn parse_canister_status(canister_id: &str, s: &str) -> Result<CanisterStatus, String> {
+    let mut status = CanisterStatus {
+        canister_id: canister_id.to_string(),
+        ..Default::default()
+    };
+    for (key, value) in s.lines().filter_map(|l| l.split_once(':')) {
+        let value = value.trim().to_string();
+        match key.trim().to_lowercase().as_str() {
+            "status" => status.status = value,
+            "controllers" => status.controllers = value,
+            "memory allocation" => status.memory_allocation = value,
+            "compute allocation" => status.compute_allocation = value,
+            "freezing threshold" => status.freezing_threshold = value,
+            "memory size" => {
+                status.memory_size = value
+                    .trim_start_matches("Nat(")
+                    .trim_end_matches(')')
+                    .to_string()
+            }
+            "balance" => status.balance = value.trim_end_matches("Cycles").trim().to_string(),
+            "module hash" => status.module_hash = value,
+            _ => {}
+        }
+    }
+    if status.status.is_empty() {
+        return Err("no status line".to_string());
+    }
+    Ok(status)
+}
+
+/// `dfx identity list` marks the current identity with a trailing `*`.
+fn parse_identity_list(s: &str) -> Vec<String> {
+    s.lines()
+        .map(|l| l.trim().trim_end_matches('*').trim().to_string())
+        .filter(|l| !l.is_empty())
+        .collect()
 }
 
 #[test]
 fn test_ping_command() {
-    use serde_json::{from_str, Value};
-    use std::collections::HashMap;
-    let app_state = AppState::default();
-    let path_to_dfx = "dfx";
-    let output = DfxCommands::Ping.run(&app_state, path_to_dfx);
-    let json: HashMap<String, Value> = from_str(&output).unwrap();
-    assert!(json.contains_key("ic_api_version"));
+    let stdout = "{\n  \"ic_api_version\": \"0.18.0\"  \"impl_hash\": \"a1b2\"  \"replica_health_status\": \"healthy\"  \"root_key\": [48, 129, 130]\n}\n";
+    let output = DfxCommands::Ping.parse(stdout).unwrap();
+    let DfxOutput::Ping(ping) = output else {
+        panic!("expected ping output, got {output:?}");
+    };
+    assert_eq!(ping.ic_api_version, "0.18.0");
+    assert_eq!(ping.replica_health_status, "healthy");
+    assert_eq!(ping.root_key, vec![48, 129, 130]);
+    assert_eq!(ping.impl_hash, Some("a1b2".to_string()));
+}
+
+#[test]
+fn test_balances_parsing() {
+    assert_eq!(
+        parse_cycles("93800000000000 cycles."),
+        Ok(93_800_000_000_000)
+    );
+    assert_eq!(
+        parse_cycles("93.800 TC (trillion cycles)."),
+        Ok(93_800_000_000_000)
+    );
+    assert!(parse_cycles("").is_err());
+    assert_eq!(parse_icp("1.5 ICP").unwrap().e8s, 150_000_000);
+    assert_eq!(parse_icp("0.00000001 ICP").unwrap().e8s, 1);
+    assert_eq!(
+        parse_icp("12.30000000 ICP").unwrap().to_string(),
+        "12.30000000 ICP"
+    );
+    assert!(parse_icp("12.3").is_err());
+}
+
+#[test]
+fn test_balance_not_available_on_network() {
+    let missing_ledger = DfxError::Failed {
+        command: "dfx ledger balance --network local".to_string(),
+        exit_code: Some(255),
+        stderr: "Error: Failed to call canister: reject code DestinationInvalid, reject message Canister ryjl3-tyaaa-aaaaa-aaaba-cai not found".to_string(),
+    };
+    let balance = Balance::<IcpBalance>::from_result(Err(missing_ledger), "local").unwrap();
+    assert_eq!(balance.to_string(), "not available on 'local'");
+
+    let refused = DfxError::Failed {
+        command: "dfx ledger balance --network ic".to_string(),
+        exit_code: Some(255),
+        stderr: "Error: connection refused".to_string(),
+    };
+    assert!(Balance::<IcpBalance>::from_result(Err(refused), "ic").is_err());
+
+    for stderr in [
+        "Error: Identity alice not found",
+        "Error: Network 'staging' not found",
+        "sh: dfx: command not found",
+    ] {
+        let unrelated = DfxError::Failed {
+            command: "dfx ledger balance --network ic".to_string(),
+            exit_code: Some(255),
+            stderr: stderr.to_string(),
+        };
+        assert!(!unrelated.is_not_available(), "{stderr}");
+    }
+    assert_eq!(
+        Balance::from_result(Ok(42u128), "ic").unwrap(),
+        Balance::Amount(42)
+    );
+}
+
+#[test]
+fn test_canister_call_args() {
+    let ctx = DfxContext {
+        identity: "default".to_string(),
+        network: "local".to_string(),
+        ..Default::default()
+    };
+    let call = |args: &str, query| DfxCommands::CanisterCall {
+        canister_id: "bkyz2-fmaaa-aaaaa-qaaaq-cai".to_string(),
+        method: "greet".to_string(),
+        args: args.to_string(),
+        query,
+    };
+    assert_eq!(
+        command_line(&call("(\"world\")", true).args(&ctx)),
+        "dfx --identity default canister call bkyz2-fmaaa-aaaaa-qaaaq-cai greet (\"world\") --network local --query"
+    );
+    assert!(command_line(&call(" ", false).args(&ctx)).ends_with("greet () --network local"));
+    assert_eq!(
+        call("", false).parse("\n").unwrap(),
+        DfxOutput::CallReply("()".to_string())
+    );
+}
+
+#[test]
+fn test_canister_action_args() {
+    let ctx = DfxContext {
+        identity: "default".to_string(),
+        network: "local".to_string(),
+        ..Default::default()
+    };
+    let deposit = DfxCommands::CanisterDepositCycles {
+        canister_id: "bkyz2-fmaaa-aaaaa-qaaaq-cai".to_string(),
+        cycles: 1_500_000_000_000,
+    };
+    assert_eq!(
+        command_line(&deposit.args(&ctx)),
+        "dfx --identity default canister deposit-cycles 1500000000000 bkyz2-fmaaa-aaaaa-qaaaq-cai --network local"
+    );
+    let delete = DfxCommands::CanisterDelete {
+        canister_id: "bkyz2-fmaaa-aaaaa-qaaaq-cai".to_string(),
+    };
+    assert!(command_line(&delete.args(&ctx))
+        .contains("canister delete bkyz2-fmaaa-aaaaa-qaaaq-cai --yes"));
+}
+
+#[test]
+fn test_deploy_args() {
+    let ctx = DfxContext {
+        identity: "default".to_string(),
+        network: "local".to_string(),
+        ..Default::default()
+    };
+    let deploy = |canister: Option<&str>, mode| DfxCommands::Deploy {
+        canister: canister.map(String::from),
+        mode,
+    };
+    assert_eq!(
+        command_line(&deploy(None, None).args(&ctx)),
+        "dfx --identity default deploy --network local"
+    );
+    assert_eq!(
+        command_line(&deploy(Some("backend"), Some(InstallMode::Reinstall)).args(&ctx)),
+        "dfx --identity default deploy backend --mode reinstall --yes --network local"
+    );
+}
+
+#[test]
+fn test_canister_status_parsing() {
+    let stdout = "Canister status call result for bkyz2-fmaaa-aaaaa-qaaaq-cai.
+Status: Running
+Controllers: bnz7o-iuaaa-aaaaa-qaaaa-cai trg6r-vqw4x-tcu5z-pgm4z-nmas4-ailxn-rjavv-zbzhi-jy2oy-wjrpf-hqe
+Memory allocation: 0
+Compute allocation: 0
+Freezing threshold: 2_592_000
+Memory Size: Nat(2294162)
+Balance: 3_100_000_000_000 Cycles
+Reserved: 0 Cycles
+Module hash: 0x2e3a
+";
+    let command = DfxCommands::CanisterStatus {
+        canister_id: "bkyz2-fmaaa-aaaaa-qaaaq-cai".to_string(),
+    };
+    let DfxOutput::CanisterStatus(status) = command.parse(stdout).unwrap() else {
+        panic!("expected canister status");
+    };
+    assert_eq!(status.status, "Running");
+    assert_eq!(status.memory_size, "2294162");
+    assert_eq!(status.balance, "3_100_000_000_000");
+    assert_eq!(status.freezing_threshold, "2_592_000");
+    assert_eq!(status.module_hash, "0x2e3a");
+}
+
+#[test]
+fn test_identity_list_parsing() {
+    assert_eq!(
+        parse_identity_list("anonymous\ndefault *\nalice\n"),
+        vec!["anonymous", "default", "alice"]
+    );
+}
+
+#[test]
+fn test_failed_command_reports_reason() {
+    let ctx = DfxContext {
+        path_to_dfx: "/nonexistent/dfx".to_string(),
+        ..Default::default()
+    };
+    let err = DfxCommands::Version.run(&ctx).unwrap_err();
+    assert!(matches!(err, DfxError::Spawn { .. }));
+
+    let err = DfxError::Failed {
+        command: "dfx ledger balance".to_string(),
+        exit_code: Some(255),
+        stderr: "Error: Failed to connect\nCaused by: connection refused\n".to_string(),
+    };
+    assert_eq!(err.reason(), "exit code 255: Caused by: connection refused");
 }
@@ -1,8 +1,17 @@
+use crate::networks::NetworkConfig;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt, fs, io,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
 
-// Structure matching the "canister_ids.json" file
+// Structure matching the "canister_ids.json" files: the one in the project
+// root holds the ids of persistent networks such as ic, and one per network
+// in .dfx holds the ids of the networks deployed to from this machine
 // ```
 // /path/to/project/.dfx/local/canister_ids.json
 // {
@@ -25,8 +34,12 @@ type CanisterName = String;
 type Network = String;
 type CanisterId = String;
 type CanisterIds = HashMap<CanisterName, HashMap<Network, CanisterId>>;
+/// The file, relative to the project, each of the canister ids was read from.
+type IdOrigins = HashMap<CanisterName, HashMap<Network, PathBuf>>;
 
-// Structure for relevant parts of "dfx.json"
+// Structure matching "dfx.json". Every field dfx knows is optional, and
+// fields this model does not know yet are kept in `extra`, so newer dfx.json
+// files still parse and nothing is lost when they are stored
 // ```
 // /path/to/project/dfx.json
 // {
@@ -35,6 +48,14 @@ type CanisterIds = HashMap<CanisterName, HashMap<Network, CanisterId>>;
 //       "main": "src/fff_backend/main.mo",
 //       "type": "motoko"
 //     },
+//     "ledger": {
+//       "type": "custom",
+//       "candid": "ledger.did",
+//       "wasm": "ledger.wasm.gz",
+//       "build": ["./download-ledger.sh"],
+//       "remote": { "id": { "ic": "ryjl3-tyaaa-aaaaa-aaaba-cai" } },
+//       "init_arg": "(record { minting_account = \"...\" })"
+//     },
 //     "fff_frontend": {
 //       "dependencies": [
 //         "fff_backend"
@@ -55,23 +76,202 @@ type CanisterIds = HashMap<CanisterName, HashMap<Network, CanisterId>>;
 //       "packtool": ""
 //     }
 //   },
+//   "networks": { "staging": { "providers": ["https://staging.example.com"] } },
 //   "output_env_file": ".env",
 //   "version": 1
 // }
 // ```
-#[derive(Serialize, Deserialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct DfxProject {
+    #[serde(default)]
     pub canisters: HashMap<CanisterName, CanisterInfo>,
+    /// dfx version the project is pinned to.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dfx: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    #[serde(default)]
+    pub networks: HashMap<Network, NetworkConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub defaults: Option<Defaults>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_env_file: Option<PathBuf>,
+    #[serde(flatten)]
+    pub extra: Extra,
 }
 
+/// Fields of a dfx.json object this model does not know.
+pub type Extra = BTreeMap<String, serde_json::Value>;
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct CanisterInfo {
-    pub dependencies: Option<Vec<String>>,
-    pub frontend: Option<HashMap<String, String>>,
-    pub source: Option<Vec<String>>,
-    #[serde(rename = "type")]
+    /// `motoko`, `rust`, `assets`, `custom` or `pull`; custom if left out.
+    #[serde(rename = "type", default = "custom")]
     pub canister_type: String,
+    /// Motoko source of `motoko` canisters.
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub main: Option<String>,
+    /// Cargo package of `rust` canisters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candid: Option<String>,
+    /// Wasm module of `custom` canisters, a path or URL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wasm: Option<String>,
+    /// Commands building `custom` canisters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build: Option<OneOrMany>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dependencies: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frontend: Option<BTreeMap<String, serde_json::Value>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<Vec<String>>,
+    /// Set for canisters that other parties deploy on some networks.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote: Option<Remote>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub declarations: Option<Declarations>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub init_arg: Option<String>,
+    /// Mainnet id of `pull` canisters.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<CanisterId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pullable: Option<Pullable>,
+    #[serde(default)]
+    pub metadata: Vec<CanisterMetadata>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+fn custom() -> String {
+    "custom".to_string()
+}
+
+/// A value dfx.json accepts either alone or as a list.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum OneOrMany {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl OneOrMany {
+    pub fn to_vec(&self) -> Vec<String> {
+        match self {
+            OneOrMany::One(value) => vec![value.clone()],
+            OneOrMany::Many(values) => values.clone(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Remote {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub candid: Option<String>,
+    /// The canister's id on each network it is not deployed to from here.
+    #[serde(default)]
+    pub id: BTreeMap<Network, CanisterId>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Declarations {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bindings: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env_override: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_compatibility: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Pullable {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wasm_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wasm_hash: Option<String>,
+    #[serde(default)]
+    pub dependencies: Vec<CanisterId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub init_guide: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct CanisterMetadata {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub networks: Option<Vec<Network>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Defaults {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build: Option<BuildDefaults>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BuildDefaults {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub packtool: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl CanisterInfo {
+    /// What the canister is written in, as far as dfx.json tells.
+    pub fn language(&self) -> &str {
+        match self.canister_type.as_str() {
+            "motoko" => "Motoko",
+            "rust" => "Rust",
+            "assets" => "static assets",
+            "pull" => "pulled from mainnet",
+            "custom" if self.remote.is_some() && self.wasm.is_none() => "remote",
+            "custom" => "custom Wasm",
+            other => other,
+        }
+    }
+
+    /// The command dfx builds the canister with, if it is known.
+    pub fn build_command(&self) -> Option<String> {
+        match (self.canister_type.as_str(), &self.build) {
+            (_, Some(build)) => Some(build.to_vec().join(" && ")),
+            ("rust", None) => Some(format!(
+                "cargo build --target wasm32-unknown-unknown --release -p {}",
+                self.package.as_deref().unwrap_or("?")
+            )),
+            ("motoko", None) => self.main.as_ref().map(|main| format!("moc {main}")),
+            _ => None,
+        }
+    }
+
+    /// The Candid file, of the canister or else of its remote deployments.
+    pub fn candid_file(&self) -> Option<&str> {
+        self.candid
+            .as_deref()
+            .or_else(|| self.remote.as_ref()?.candid.as_deref())
+    }
 }
 
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
@@ -83,18 +283,235 @@ pub struct ProjectDatabase {
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct DfxProjectData {
     pub canisters: HashMap<CanisterName, CanisterInfo>,
+    /// Ids on every network, merged from all canister_ids.json files.
     pub canister_ids: CanisterIds,
+    #[serde(default)]
+    pub canister_id_origins: IdOrigins,
+    /// The rest of dfx.json.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dfx: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    #[serde(default)]
+    pub networks: HashMap<Network, NetworkConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub defaults: Option<Defaults>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output_env_file: Option<PathBuf>,
+    /// How the last refresh went. Canisters and ids that could not be read
+    /// keep their last known values.
+    #[serde(default)]
+    pub health: ProjectHealth,
+    /// Top-level dfx.json fields this model does not know.
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// Whether a registered project could be read on the last refresh.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProjectHealth {
+    #[default]
+    Ok,
+    /// The project was moved or deleted, or never was a dfx project.
+    MissingDfxJson,
+    /// There is no canister_ids.json for any network yet.
+    NotDeployed,
+    /// dfx.json or canister_ids.json is not valid, with the reason.
+    ParseError(String),
+    /// A file exists but cannot be read, e.g. for lack of permissions.
+    Unreadable(String),
+}
+
+impl fmt::Display for ProjectHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProjectHealth::Ok => write!(f, "ok"),
+            ProjectHealth::MissingDfxJson => write!(f, "dfx.json missing, moved or deleted?"),
+            ProjectHealth::NotDeployed => write!(f, "not deployed yet"),
+            ProjectHealth::ParseError(e) => write!(f, "parse error: {e}"),
+            ProjectHealth::Unreadable(e) => write!(f, "unreadable: {e}"),
+        }
+    }
+}
+
+impl DfxProjectData {
+    /// Reads the project at `project_path` from scratch.
+    pub fn read(project_path: &Path) -> DfxProjectData {
+        let mut data = DfxProjectData {
+            canisters: HashMap::new(),
+            canister_ids: HashMap::new(),
+            canister_id_origins: HashMap::new(),
+            dfx: None,
+            version: None,
+            networks: HashMap::new(),
+            defaults: None,
+            output_env_file: None,
+            health: ProjectHealth::Ok,
+            extra: Extra::new(),
+        };
+        data.refresh(project_path);
+        data
+    }
+
+    /// Re-reads dfx.json and the canister ids and records how it went.
+    pub fn refresh(&mut self, project_path: &Path) {
+        self.health = match self.try_refresh(project_path) {
+            Ok(()) => ProjectHealth::Ok,
+            Err(health) => health,
+        };
+    }
+
+    fn try_refresh(&mut self, project_path: &Path) -> Result<(), ProjectHealth> {
+        let dfx_json =
+            read_file(&project_path.join("dfx.json"))?.ok_or(ProjectHealth::MissingDfxJson)?;
+        let dfx_project: DfxProject = serde_json::from_str(&dfx_json)
+            .map_err(|e| ProjectHealth::ParseError(format!("dfx.json: {e}")))?;
+        self.canisters = dfx_project.canisters;
+        self.dfx = dfx_project.dfx;
+        self.version = dfx_project.version;
+        self.networks = dfx_project.networks;
+        self.defaults = dfx_project.defaults;
+        self.output_env_file = dfx_project.output_env_file;
+        self.extra = dfx_project.extra;
+        let (canister_ids, origins) =
+            read_canister_ids(project_path)?.ok_or(ProjectHealth::NotDeployed)?;
+        self.canister_ids = canister_ids;
+        self.canister_id_origins = origins;
+        Ok(())
+    }
+
+    /// The file, relative to the project, the id of `canister_name` on
+    /// `network` was read from.
+    pub fn id_origin(&self, canister_name: &str, network: &str) -> Option<&Path> {
+        self.canister_id_origins
+            .get(canister_name)?
+            .get(network)
+            .map(PathBuf::as_path)
+    }
+}
+
+/// Merges the project-root canister_ids.json with the
+/// `.dfx/<network>/canister_ids.json` of every network, which win when both
+/// have an id. `None` if there is none of these files.
+fn read_canister_ids(
+    project_path: &Path,
+) -> Result<Option<(CanisterIds, IdOrigins)>, ProjectHealth> {
+    let mut files = vec![PathBuf::from("canister_ids.json")];
+    if let Ok(entries) = fs::read_dir(project_path.join(".dfx")) {
+        let mut networks: Vec<PathBuf> = entries
+            .filter_map(|e| e.ok())
+            .filter(|e| e.path().is_dir())
+            .map(|e| {
+                Path::new(".dfx")
+                    .join(e.file_name())
+                    .join("canister_ids.json")
+            })
+            .collect();
+        networks.sort();
+        files.extend(networks);
+    }
+    let mut found = false;
+    let mut canister_ids = CanisterIds::new();
+    let mut origins = IdOrigins::new();
+    for file in files {
+        let Some(content) = read_file(&project_path.join(&file))? else {
+            continue;
+        };
+        found = true;
+        let ids: CanisterIds = serde_json::from_str(&content)
+            .map_err(|e| ProjectHealth::ParseError(format!("{}: {e}", file.display())))?;
+        for (canister_name, networks) in ids {
+            for (network, canister_id) in networks {
+                origins
+                    .entry(canister_name.clone())
+                    .or_default()
+                    .insert(network.clone(), file.clone());
+                canister_ids
+                    .entry(canister_name.clone())
+                    .or_default()
+                    .insert(network, canister_id);
+            }
+        }
+    }
+    Ok(found.then_some((canister_ids, origins)))
+}
+
+/// The content of `path`, or `None` if there is no such file.
+fn read_file(path: &Path) -> Result<Option<String>, ProjectHealth> {
+    match fs::read_to_string(path) {
+        Ok(content) => Ok(Some(content)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(ProjectHealth::Unreadable(format!(
+            "{}: {e}",
+            path.display()
+        ))),
+    }
+}
+
+/// A lock file older than this was left behind by a process that died while
+/// holding it.
+const LOCK_STALE_AFTER: Duration = Duration::from_secs(10);
+
+/// Exclusive access to the project database, held by creating
+/// `<database>.lock` and released by removing it on drop.
+struct DatabaseLock {
+    path: PathBuf,
+}
+
+impl DatabaseLock {
+    fn acquire(db_path: &Path) -> Result<DatabaseLock> {
+        let path = sibling(db_path, "lock");
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Ok(DatabaseLock { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let stale = fs::metadata(&path)
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age > LOCK_STALE_AFTER);
+                    if stale {
+                        let _ = fs::remove_file(&path);
+                    } else {
+                        thread::sleep(Duration::from_millis(20));
+                    }
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| format!("Failed to lock {:?}", db_path));
+                }
+            }
+        }
+    }
+}
+
+impl Drop for DatabaseLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// `path` with `.extension` appended to its file name.
+fn sibling(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    path.with_file_name(name)
 }
 
 impl ProjectDatabase {
-    pub fn init(path: &PathBuf) -> Result<()> {
+    pub fn init(path: &Path) -> Result<()> {
         if !path.exists() {
-            std::fs::create_dir_all(&path.parent().unwrap())?;
+            std::fs::create_dir_all(path.parent().unwrap())?;
         }
         Ok(())
     }
     // Function to load the database from a JSON file
-    pub fn load(path: &PathBuf) -> Result<ProjectDatabase> {
+    pub fn load(path: &Path) -> Result<ProjectDatabase> {
         if path.exists() {
             let content =
                 fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
@@ -103,7 +520,7 @@ impl ProjectDatabase {
             Ok(db)
         } else {
             let db = ProjectDatabase {
-                db_path: path.clone(),
+                db_path: path.to_path_buf(),
                 ..Default::default()
             };
             db.save()?;
@@ -111,81 +528,172 @@ impl ProjectDatabase {
         }
     }
 
-    // Function to save the database to a JSON file
+    // Function to save the database to a JSON file. It is written next to
+    // the database first, so readers never see a half-written file.
     pub fn save(&self) -> Result<()> {
         let serialized = serde_json::to_string_pretty(self)
             .with_context(|| "Failed to serialize project database")?;
-        fs::write(&self.db_path, serialized)
+        let temp_path = sibling(&self.db_path, "tmp");
+        fs::write(&temp_path, serialized)
+            .and_then(|_| fs::rename(&temp_path, &self.db_path))
             .with_context(|| format!("Failed to write project database to {:?}", self.db_path))?;
         Ok(())
     }
 
+    /// Loads the database at `path`, lets `modify` change it and saves it.
+    /// The TUI, its poller and the CLI commands all change the database this
+    /// way, under a lock, so none of them overwrites the others' changes.
+    pub fn update<T>(
+        path: &Path,
+        modify: impl FnOnce(&mut ProjectDatabase) -> Result<T>,
+    ) -> Result<(ProjectDatabase, T)> {
+        let _lock = DatabaseLock::acquire(path)?;
+        let mut db = ProjectDatabase::load(path)?;
+        let value = modify(&mut db)?;
+        db.save()?;
+        Ok((db, value))
+    }
+
     pub fn add_project(&mut self, project_path: PathBuf, project_data: DfxProjectData) {
         self.projects.insert(project_path, project_data);
     }
 
-    pub fn refresh(&mut self) -> Result<()> {
+    /// Re-reads every project. A project that cannot be read keeps its last
+    /// known canisters and ids and records why in its health.
+    pub fn refresh(&mut self) {
         for (project_path, project_data) in self.projects.iter_mut() {
-            let mut dfx_project_path = project_path.clone();
-            dfx_project_path.push("dfx.json");
-            let dfx_json_content = fs::read_to_string(&dfx_project_path)
-                .with_context(|| format!("Failed to read {:?}", dfx_project_path))?;
-            let dfx_project: DfxProject = serde_json::from_str(&dfx_json_content)
-                .with_context(|| "Failed to parse dfx.json")?;
-            project_data.canisters = dfx_project.canisters;
-            let mut canister_ids_path = project_path.clone();
-            canister_ids_path.push(".dfx/local/canister_ids.json");
-            if canister_ids_path.exists() {
-                let canister_ids_content = fs::read_to_string(&canister_ids_path)
-                    .with_context(|| format!("Failed to read {:?}", canister_ids_path))?;
-                let canister_ids: CanisterIds = serde_json::from_str(&canister_ids_content)
-                    .with_context(|| "Failed to parse canister_ids.json")?;
-                project_data.canister_ids = canister_ids;
-            } else {
-                anyhow::bail!("Canister IDs file not found ({:?}).", canister_ids_path);
-            }
+            project_data.refresh(project_path);
         }
-        Ok(())
     }
 
-    pub fn register_dfx_project(path_to_dfx_project: PathBuf, db_path: PathBuf) -> Result<()> {
-        let mut dfx_project_path = path_to_dfx_project.clone();
-        dfx_project_path.push("dfx.json");
-        if !dfx_project_path.exists() {
-            anyhow::bail!("The path you provided is not a dfx project (dfx.json not found).");
+    /// Reads the project at `path` and adds it under its canonical path.
+    /// A project that was never deployed is added too, nothing else that
+    /// cannot be read.
+    pub fn register(&mut self, path: &Path) -> Result<(PathBuf, ProjectHealth)> {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if let Some(registered) = self.registered_path(&path).filter(|p| **p != path) {
+            anyhow::bail!("The project is already registered as {:?}.", registered);
         }
+        let project_data = DfxProjectData::read(&path);
+        match &project_data.health {
+            ProjectHealth::Ok | ProjectHealth::NotDeployed => {}
+            ProjectHealth::MissingDfxJson => {
+                anyhow::bail!("The path you provided is not a dfx project (dfx.json not found).")
+            }
+            health => anyhow::bail!("Failed to read the project: {health}"),
+        }
+        let health = project_data.health.clone();
+        self.add_project(path.clone(), project_data);
+        Ok((path, health))
+    }
 
-        let dfx_json_content = fs::read_to_string(&dfx_project_path)
-            .with_context(|| format!("Failed to read {:?}", dfx_project_path))?;
-        let dfx_project: DfxProject =
-            serde_json::from_str(&dfx_json_content).with_context(|| "Failed to parse dfx.json")?;
-
-        let mut canister_ids_path = path_to_dfx_project.clone();
-        canister_ids_path.push(".dfx/local/canister_ids.json");
-
-        let canister_ids: CanisterIds = if canister_ids_path.exists() {
-            let canister_ids_content = fs::read_to_string(&canister_ids_path)
-                .with_context(|| format!("Failed to read {:?}", canister_ids_path))?;
-            serde_json::from_str(&canister_ids_content)
-                .with_context(|| "Failed to parse canister_ids.json")?
-        } else {
-            anyhow::bail!("Canister IDs file not found ({:?}).", canister_ids_path);
-        };
+    /// The key `path` is registered under. Older versions stored paths as
+    /// typed, so `.`, relative paths and symlinks are resolved on both sides.
+    pub fn registered_path(&self, path: &Path) -> Option<&PathBuf> {
+        let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let path = canonical(path);
+        self.projects.keys().find(|key| canonical(key) == path)
+    }
 
-        let mut project_db = ProjectDatabase::load(&db_path)?;
-        let project_data = DfxProjectData {
-            canisters: dfx_project.canisters,
-            canister_ids,
-        };
+    /// Removes the project at `path`, given as registered or any other way.
+    pub fn unregister(&mut self, path: &Path) -> Option<(PathBuf, DfxProjectData)> {
+        let canonical = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        self.projects
+            .remove_entry(path)
+            .or_else(|| self.projects.remove_entry(&canonical))
+    }
 
-        project_db.add_project(path_to_dfx_project, project_data);
-        project_db.save()?;
+    pub fn register_dfx_project(path_to_dfx_project: PathBuf, db_path: PathBuf) -> Result<()> {
+        let (_, (path, health)) =
+            ProjectDatabase::update(&db_path, |db| db.register(&path_to_dfx_project))?;
         println!(
             "Project '{}' has been added to the database.",
-            dfx_project_path.display()
+            path.join("dfx.json").display()
+        );
+        if health == ProjectHealth::NotDeployed {
+            println!("It has not been deployed yet; its canisters show up once it is.");
+        }
+        Ok(())
+    }
+
+    pub fn unregister_dfx_project(path_to_dfx_project: PathBuf, db_path: PathBuf) -> Result<()> {
+        let (_, (path, _)) = ProjectDatabase::update(&db_path, |db| {
+            db.unregister(&path_to_dfx_project).with_context(|| {
+                format!(
+                    "No project is registered at {:?}, see `list-dfx-projects`.",
+                    path_to_dfx_project
+                )
+            })
+        })?;
+        println!(
+            "Project '{}' has been removed from the database.",
+            path.display()
         );
         Ok(())
     }
+
+    pub fn list_dfx_projects(db_path: PathBuf) -> Result<()> {
+        let mut project_db = ProjectDatabase::load(&db_path)?;
+        project_db.refresh();
+        let mut projects: Vec<_> = project_db.projects.iter().collect();
+        projects.sort_by(|a, b| a.0.cmp(b.0));
+        if projects.is_empty() {
+            println!("No projects registered.");
+        }
+        for (path, data) in projects {
+            let mut networks: Vec<&Network> = data
+                .canister_ids
+                .values()
+                .flat_map(|ids| ids.keys())
+                .collect();
+            networks.sort();
+            networks.dedup();
+            println!(
+                "{}  {} canisters, deployed to [{}], {}",
+                path.display(),
+                data.canisters.len(),
+                networks
+                    .iter()
+                    .map(|n| n.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                data.health
+            );
+        }
+        Ok(())
+    }
+
+    /// Every canister id the registered projects know about on `network`.
+    pub fn canister_ids_on(&self, network: &str) -> Vec<CanisterId> {
+        let mut ids: Vec<CanisterId> = self
+            .projects
+            .values()
+            .flat_map(|project| project.canister_ids.values())
+            .filter_map(|ids| ids.get(network).cloned())
+            .collect();
+        ids.sort();
+        ids.dedup();
+        ids
+    }
+
+    /// The project and name `canister_id` has on `network`, also for the
+    /// canisters dfx creates without a dfx.json entry, like `__Candid_UI`.
+    pub fn canister_name(
+        &self,
+        canister_id: &str,
+        network: &str,
+    ) -> Option<(&PathBuf, &CanisterName)> {
+        let mut projects: Vec<_> = self.projects.iter().collect();
+        projects.sort_by(|a, b| a.0.cmp(b.0));
+        projects.into_iter().find_map(|(path, project)| {
+            project
+                .canister_ids
+                .iter()
+                .find(|(_, ids)| ids.get(network).map(String::as_str) == Some(canister_id))
+                .map(|(name, _)| (path, name))
+        })
+    }
+
     pub fn get_info(
         &self,
         canister_id: &str,
@@ -257,4 +765,243 @@ mod tests {
         assert_eq!(info.1, "fff_backend");
         assert_eq!(info.2.canister_type, "motoko");
     }
+
+    #[test]
+    fn test_refresh_records_health_per_project() {
+        use super::{DfxProjectData, ProjectDatabase, ProjectHealth};
+        use crate::test_util::TempDir;
+        use std::fs;
+
+        let dir = TempDir::new("health");
+        let dfx_json = r#"{ "canisters": { "backend": { "type": "motoko" } } }"#;
+        let ids = r#"{ "backend": { "local": "bkyz2-fmaaa-aaaaa-qaaaq-cai" } }"#;
+        for name in ["ok", "broken", "moved", "new"] {
+            fs::create_dir_all(dir.join(name).join(".dfx/local")).unwrap();
+            fs::write(dir.join(name).join("dfx.json"), dfx_json).unwrap();
+            fs::write(dir.join(name).join(".dfx/local/canister_ids.json"), ids).unwrap();
+        }
+        let mut db = ProjectDatabase::default();
+        for name in ["ok", "broken", "moved", "new"] {
+            let path = dir.join(name);
+            db.add_project(path.clone(), DfxProjectData::read(&path));
+        }
+        fs::write(dir.join("broken/dfx.json"), "{ canisters").unwrap();
+        fs::remove_dir_all(dir.join("moved")).unwrap();
+        fs::remove_dir_all(dir.join("new/.dfx")).unwrap();
+        fs::write(
+            dir.join("ok/dfx.json"),
+            r#"{ "canisters": { "backend": { "type": "motoko" }, "web": { "type": "assets" } } }"#,
+        )
+        .unwrap();
+        db.refresh();
+
+        let project = |name: &str| &db.projects[&dir.join(name)];
+        assert_eq!(project("ok").health, ProjectHealth::Ok);
+        assert_eq!(project("ok").canisters.len(), 2);
+        assert!(matches!(
+            &project("broken").health,
+            ProjectHealth::ParseError(e) if e.starts_with("dfx.json: ")
+        ));
+        assert_eq!(project("moved").health, ProjectHealth::MissingDfxJson);
+        assert_eq!(project("new").health, ProjectHealth::NotDeployed);
+        // what could not be read keeps its last known value
+        assert_eq!(project("moved").canisters.len(), 1);
+        assert_eq!(
+            db.get_info("bkyz2-fmaaa-aaaaa-qaaaq-cai", "local")
+                .unwrap()
+                .1,
+            "backend"
+        );
+    }
+
+    #[test]
+    fn test_canister_ids_of_every_network() {
+        use super::{DfxProjectData, ProjectDatabase};
+        use crate::test_util::TempDir;
+        use std::{fs, path::Path};
+
+        let dir = TempDir::new("networks");
+        fs::create_dir_all(dir.join(".dfx/local")).unwrap();
+        fs::create_dir_all(dir.join(".dfx/testnet")).unwrap();
+        fs::write(
+            dir.join("dfx.json"),
+            r#"{ "canisters": { "backend": { "type": "rust" } } }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("canister_ids.json"),
+            r#"{ "backend": { "ic": "ryjl3-tyaaa-aaaaa-aaaba-cai", "testnet": "stale" } }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join(".dfx/local/canister_ids.json"),
+            r#"{ "backend": { "local": "bkyz2-fmaaa-aaaaa-qaaaq-cai" } }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join(".dfx/testnet/canister_ids.json"),
+            r#"{ "backend": { "testnet": "bd3sg-teaaa-aaaaa-qaaba-cai" } }"#,
+        )
+        .unwrap();
+        let mut db = ProjectDatabase::default();
+        db.add_project(dir.to_path_buf(), DfxProjectData::read(&dir));
+
+        for (id, network, origin) in [
+            ("ryjl3-tyaaa-aaaaa-aaaba-cai", "ic", "canister_ids.json"),
+            (
+                "bkyz2-fmaaa-aaaaa-qaaaq-cai",
+                "local",
+                ".dfx/local/canister_ids.json",
+            ),
+            (
+                "bd3sg-teaaa-aaaaa-qaaba-cai",
+                "testnet",
+                ".dfx/testnet/canister_ids.json",
+            ),
+        ] {
+            assert_eq!(db.get_info(id, network).unwrap().1, "backend");
+            assert_eq!(
+                db.projects[&*dir].id_origin("backend", network),
+                Some(Path::new(origin))
+            );
+        }
+        assert!(db.get_info("stale", "testnet").is_none());
+    }
+
+    #[test]
+    fn test_full_dfx_json_keeps_unknown_fields() {
+        use super::DfxProject;
+
+        let dfx_json = r#"{
+  "dfx": "0.20.0",
+  "canisters": {
+    "backend": { "type": "rust", "package": "backend", "candid": "src/backend/backend.did" },
+    "ledger": {
+      "type": "custom",
+      "candid": "ledger.did",
+      "wasm": "ledger.wasm.gz",
+      "build": "./download-ledger.sh",
+      "remote": { "id": { "ic": "ryjl3-tyaaa-aaaaa-aaaba-cai" } },
+      "init_arg": "(record {})",
+      "declarations": { "output": "src/declarations/ledger", "bindings": ["js", "ts"] },
+      "metadata": [{ "name": "candid:service", "visibility": "public" }],
+      "gzip": true
+    },
+    "internet_identity": {
+      "type": "pull",
+      "id": "rdmx6-jaaaa-aaaaa-aaadq-cai",
+      "pullable": { "wasm_url": "https://example.com/ii.wasm", "dependencies": [], "init_guide": "none" }
+    },
+    "frontend": { "type": "assets", "source": ["dist"], "frontend": { "entrypoint": "index.html", "gzip": true } }
+  },
+  "defaults": { "build": { "args": "", "packtool": "mops sources" }, "bitcoin": { "enabled": false } },
+  "networks": { "staging": { "providers": ["https://staging.example.com"], "type": "persistent" } },
+  "output_env_file": ".env",
+  "version": 1,
+  "some_future_field": { "x": 1 }
+}"#;
+        let project: DfxProject = serde_json::from_str(dfx_json).unwrap();
+        let canister = |name: &str| &project.canisters[name];
+        assert_eq!(canister("backend").language(), "Rust");
+        assert_eq!(
+            canister("backend").build_command().unwrap(),
+            "cargo build --target wasm32-unknown-unknown --release -p backend"
+        );
+        assert_eq!(
+            canister("ledger").build_command().unwrap(),
+            "./download-ledger.sh"
+        );
+        assert_eq!(canister("ledger").candid_file(), Some("ledger.did"));
+        assert_eq!(
+            canister("ledger").remote.as_ref().unwrap().id["ic"],
+            "ryjl3-tyaaa-aaaaa-aaaba-cai"
+        );
+        assert_eq!(canister("ledger").metadata[0].name, "candid:service");
+        assert_eq!(canister("ledger").extra["gzip"], true);
+        assert_eq!(
+            canister("internet_identity").id.as_deref().unwrap(),
+            "rdmx6-jaaaa-aaaaa-aaadq-cai"
+        );
+        assert_eq!(
+            project
+                .defaults
+                .as_ref()
+                .unwrap()
+                .build
+                .as_ref()
+                .unwrap()
+                .packtool
+                .as_deref(),
+            Some("mops sources")
+        );
+        assert!(project.networks.contains_key("staging"));
+
+        // what is not modelled survives a round trip
+        let round_trip = serde_json::to_value(&project).unwrap();
+        assert_eq!(round_trip["some_future_field"]["x"], 1);
+        assert_eq!(round_trip["defaults"]["bitcoin"]["enabled"], false);
+        assert_eq!(
+            round_trip["canisters"]["frontend"]["frontend"]["gzip"],
+            true
+        );
+    }
+
+    #[test]
+    fn test_stored_project_keeps_unknown_fields() {
+        use super::{DfxProjectData, ProjectDatabase};
+        use std::path::Path;
+        let record = r#"{
+  "projects": {
+    "/projects/fff": {
+      "canisters": { "backend": { "type": "motoko", "main": "main.mo" } },
+      "canister_ids": { "backend": { "local": "bkyz2-fmaaa-aaaaa-qaaaq-cai" } },
+      "dfx": "0.20.1",
+      "version": 1,
+      "some_future_field": { "x": 1 }
+    }
+  },
+  "db_path": "/tmp/projects.json"
+}"#;
+        let db: ProjectDatabase = serde_json::from_str(record).unwrap();
+        let stored = serde_json::to_string(&db).unwrap();
+        let db: ProjectDatabase = serde_json::from_str(&stored).unwrap();
+        let project: &DfxProjectData = &db.projects[Path::new("/projects/fff")];
+        assert_eq!(project.dfx.as_deref(), Some("0.20.1"));
+        assert_eq!(project.version, Some(1));
+        assert_eq!(project.extra["some_future_field"]["x"], 1);
+
+        // absent optional fields are left out rather than stored as null
+        let stored: serde_json::Value = serde_json::from_str(&stored).unwrap();
+        let project = &stored["projects"]["/projects/fff"];
+        assert!(project.get("defaults").is_none());
+        assert!(project["canisters"]["backend"].get("candid").is_none());
+    }
+
+    #[test]
+    fn test_concurrent_updates_keep_every_change() {
+        use super::{DfxProjectData, ProjectDatabase};
+        use crate::test_util::TempDir;
+        use std::{path::PathBuf, thread};
+        let dir = TempDir::new("update");
+        let db_path = dir.join("projects.json");
+        let writers: Vec<_> = (0..8)
+            .map(|i| {
+                let db_path = db_path.clone();
+                thread::spawn(move || {
+                    ProjectDatabase::update(&db_path, |db| {
+                        let project = PathBuf::from(format!("/projects/{i}"));
+                        db.add_project(project.clone(), DfxProjectData::read(&project));
+                        Ok(())
+                    })
+                    .unwrap();
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let db = ProjectDatabase::load(&db_path).unwrap();
+        assert_eq!(db.projects.len(), 8);
+        assert!(!db_path.with_file_name("projects.json.lock").exists());
+    }
 }
@@ -0,0 +1,141 @@
+use crate::{
+    config::DiscoveryConfig,
+    dfx_project::{DfxProjectData, ProjectDatabase},
+};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// A dfx project found below one of the discovery roots that is not in the
+/// database.
+#[derive(Debug, Clone)]
+pub struct DiscoveredProject {
+    pub path: PathBuf,
+    pub data: DfxProjectData,
+}
+
+impl DiscoveredProject {
+    /// Whether the project has deployed `canister_id` to `network`.
+    pub fn owns(&self, canister_id: &str, network: &str) -> bool {
+        self.data
+            .canister_ids
+            .values()
+            .any(|ids| ids.get(network).map(String::as_str) == Some(canister_id))
+    }
+}
+
+/// `path` with a leading `~` replaced by the home directory.
+pub fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), dirs::home_dir()) {
+        (Ok(rest), Some(home)) => home.join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Every directory with a dfx.json below `roots`, at most `max_depth` levels
+/// down, sorted. Directories whose name matches an ignore pattern are not
+/// entered, nor are symlinks or the projects found, so nested projects
+/// are only found by registering them.
+pub fn find_projects(roots: &[PathBuf], config: &DiscoveryConfig) -> Vec<PathBuf> {
+    let mut found = vec![];
+    for root in roots {
+        let root = expand_home(root);
+        let root = fs::canonicalize(&root).unwrap_or(root);
+        walk(&root, config.max_depth, &config.ignore, &mut found);
+    }
+    found.sort();
+    found.dedup();
+    found
+}
+
+fn walk(dir: &Path, depth: usize, ignore: &[String], found: &mut Vec<PathBuf>) {
+    if dir.join("dfx.json").is_file() {
+        found.push(dir.to_path_buf());
+        return;
+    }
+    if depth == 0 {
+        return;
+    }
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let is_dir = entry.file_type().is_ok_and(|t| t.is_dir());
+        if is_dir && !ignore.iter().any(|pattern| glob_match(pattern, &name)) {
+            walk(&entry.path(), depth - 1, ignore, found);
+        }
+    }
+}
+
+/// Whether `name` matches `pattern`, in which `*` matches any characters.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=name.len())
+                .filter(|i| name.is_char_boundary(*i))
+                .any(|i| glob_match(rest, &name[i..]))
+        }
+    }
+}
+
+/// The projects below the configured roots that `db` does not know yet.
+pub fn discover(db: &ProjectDatabase, config: &DiscoveryConfig) -> Vec<DiscoveredProject> {
+    find_projects(&config.roots, config)
+        .into_iter()
+        .filter(|path| db.registered_path(path).is_none())
+        .map(|path| DiscoveredProject {
+            data: DfxProjectData::read(&path),
+            path,
+        })
+        .collect()
+}
+
+#[test]
+fn test_glob_match() {
+    assert!(glob_match("node_modules", "node_modules"));
+    assert!(glob_match(".*", ".git"));
+    assert!(!glob_match(".*", "src"));
+    assert!(glob_match("old-*-app", "old-dfx-app"));
+    assert!(glob_match("*", ""));
+    assert!(!glob_match("old-*-app", "old-dfx"));
+}
+
+#[test]
+fn test_finds_projects_within_depth_and_ignore_rules() {
+    let temp_dir = crate::test_util::TempDir::new("discovery");
+    let dir = temp_dir.to_path_buf();
+    let project = |path: &str| {
+        fs::create_dir_all(dir.join(path)).unwrap();
+        fs::write(dir.join(path).join("dfx.json"), r#"{ "canisters": {} }"#).unwrap();
+    };
+    project("hello");
+    project("hello/frontend/nested");
+    project("work/shop");
+    project("work/deep/er/still");
+    project("node_modules/pkg");
+    project(".cache/app");
+    let config = DiscoveryConfig {
+        roots: vec![dir.clone()],
+        max_depth: 2,
+        ..Default::default()
+    };
+    let dir = fs::canonicalize(&dir).unwrap();
+    let found = find_projects(&config.roots, &config);
+
+    // registered by an older version, as typed rather than canonical
+    let typed = dir.join("work/../hello");
+    let mut db = ProjectDatabase::default();
+    db.add_project(typed.clone(), DfxProjectData::read(&typed));
+    let discovered: Vec<PathBuf> = discover(&db, &config).into_iter().map(|p| p.path).collect();
+
+    assert_eq!(found, vec![dir.join("hello"), dir.join("work/shop")]);
+    assert_eq!(discovered, vec![dir.join("work/shop")]);
+    assert!(db.register(&dir.join("hello")).is_err());
+    assert_eq!(db.projects.len(), 1);
+}
@@ -0,0 +1,281 @@
+use chrono::{DateTime, Local};
+use regex::Regex;
+use std::{fmt, sync::LazyLock};
+
+/// A canister id: four groups of five base32 characters, then `cai`.
+static CANISTER_ID: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\b[a-z2-7]{5}(?:-[a-z2-7]{5}){3}-cai\b").unwrap());
+
+/// Where a log line came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    /// A followed log file.
+    File,
+    /// Messages written by dfx-top itself, e.g. about the replica process.
+    DfxTop,
+}
+
+impl fmt::Display for LogStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogStream::Stdout => write!(f, "stdout"),
+            LogStream::Stderr => write!(f, "stderr"),
+            LogStream::File => write!(f, "file"),
+            LogStream::DfxTop => write!(f, "dfx-top"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Critical,
+}
+
+impl LogLevel {
+    /// Finds the level of a replica or dfx log line. The replica logs through
+    /// slog (`... INFO s:... message`, or `TRCE`/`DEBG`/`ERRO`/`CRIT`), dfx
+    /// prefixes its own messages with e.g. `WARN:` or `Error:`.
+    pub fn parse(line: &str) -> Option<LogLevel> {
+        strip_ansi(line)
+            .split_whitespace()
+            .take(6)
+            .find_map(|word| {
+                // case sensitive, so a canister printing "info" is not a level
+                match word.trim_matches(|c: char| !c.is_ascii_alphabetic()) {
+                    "TRCE" | "TRACE" => Some(LogLevel::Trace),
+                    "DEBG" | "DEBUG" => Some(LogLevel::Debug),
+                    "INFO" => Some(LogLevel::Info),
+                    "WARN" | "WARNING" | "Warning" => Some(LogLevel::Warn),
+                    "ERRO" | "ERROR" | "Error" => Some(LogLevel::Error),
+                    "CRIT" | "CRITICAL" => Some(LogLevel::Critical),
+                    _ => None,
+                }
+            })
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LogLevel::Trace => write!(f, "TRACE"),
+            LogLevel::Debug => write!(f, "DEBUG"),
+            LogLevel::Info => write!(f, "INFO"),
+            LogLevel::Warn => write!(f, "WARN"),
+            LogLevel::Error => write!(f, "ERROR"),
+            LogLevel::Critical => write!(f, "CRIT"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CanisterLogKind {
+    /// Output of `ic0.debug_print`, e.g. `ic_cdk::println!` or `Debug.print`.
+    Print,
+    /// The canister trapped or panicked.
+    Trap,
+}
+
+/// A message that came from a canister rather than from the replica.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CanisterLog {
+    pub canister_id: String,
+    pub kind: CanisterLogKind,
+    /// The message without the replica's prefix.
+    pub message: String,
+}
+
+impl CanisterLog {
+    /// Recognises `[Canister <id>] message` lines written for
+    /// `ic0.debug_print`, and trap reports such as `Canister <id> trapped
+    /// explicitly: ...` or `... Canister called ic0.trap with message: ...`
+    /// that name the canister.
+    pub fn parse(line: &str) -> Option<CanisterLog> {
+        let line = strip_ansi(line);
+        let is_trap = |text: &str| {
+            let text = text.to_ascii_lowercase();
+            text.contains("ic0.trap")
+                || text.contains("ic0_trap")
+                || text.contains("trapped")
+                || text.starts_with("panicked at")
+        };
+        if let Some(start) = line.find("[Canister ") {
+            let rest = &line[start + "[Canister ".len()..];
+            let (canister_id, message) = rest.split_once(']')?;
+            if CANISTER_ID.is_match(canister_id) {
+                let message = message.trim().to_string();
+                let kind = if is_trap(&message) {
+                    CanisterLogKind::Trap
+                } else {
+                    CanisterLogKind::Print
+                };
+                return Some(CanisterLog {
+                    canister_id: canister_id.to_string(),
+                    kind,
+                    message,
+                });
+            }
+        }
+        if !is_trap(&line) {
+            return None;
+        }
+        let canister_id = CANISTER_ID.find(&line)?;
+        let message = line[canister_id.end()..]
+            .trim_start_matches(|c: char| c == ':' || c.is_whitespace())
+            .to_string();
+        Some(CanisterLog {
+            canister_id: canister_id.as_str().to_string(),
+            kind: CanisterLogKind::Trap,
+            message,
+        })
+    }
+}
+
+/// One line of output, tagged when it was read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogLine {
+    pub at: DateTime<Local>,
+    pub stream: LogStream,
+    pub level: Option<LogLevel>,
+    /// The line as printed, ANSI colors included.
+    pub text: String,
+    /// Set when the line is a canister's output or trap.
+    pub canister: Option<CanisterLog>,
+}
+
+impl LogLine {
+    pub fn new(stream: LogStream, text: String) -> Self {
+        LogLine {
+            at: Local::now(),
+            stream,
+            level: LogLevel::parse(&text),
+            canister: CanisterLog::parse(&text),
+            text,
+        }
+    }
+
+    /// A message from dfx-top itself with an explicit level.
+    pub fn dfx_top(level: LogLevel, text: String) -> Self {
+        LogLine {
+            at: Local::now(),
+            stream: LogStream::DfxTop,
+            level: Some(level),
+            text,
+            canister: None,
+        }
+    }
+
+    pub fn is_trap(&self) -> bool {
+        self.canister
+            .as_ref()
+            .is_some_and(|c| c.kind == CanisterLogKind::Trap)
+    }
+}
+
+/// Removes ANSI escape sequences such as colors from `text`.
+pub fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            stripped.push(c);
+            continue;
+        }
+        if chars.next() == Some('[') {
+            // parameters and intermediates up to the final byte, e.g. `[1;31m`
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    stripped
+}
+
+#[test]
+fn test_parse_level() {
+    let cases = [
+        (
+            "Jan 15 10:01:02.123 INFO s:fscpm-uiaaa/n:7h3ey/ic_replica/replica Starting up",
+            Some(LogLevel::Info),
+        ),
+        (
+            "Jan 15 10:01:02.123 ERRO s:fscpm-uiaaa/n:7h3ey/ic_http_handler failed",
+            Some(LogLevel::Error),
+        ),
+        (
+            "\x1b[33mWARN\x1b[0m: The default identity is not stored securely.",
+            Some(LogLevel::Warn),
+        ),
+        (
+            "Error: Failed to bind to 127.0.0.1:4943",
+            Some(LogLevel::Error),
+        ),
+        ("Running dfx start for version 0.15.2", None),
+        (
+            "[Canister bkyz2-fmaaa-aaaaa-qaaaq-cai] info about greeting",
+            None,
+        ),
+    ];
+    for (line, level) in cases {
+        assert_eq!(LogLevel::parse(line), level, "{line}");
+    }
+}
+
+#[test]
+fn test_parse_canister_log() {
+    let id = "bkyz2-fmaaa-aaaaa-qaaaq-cai";
+    let cases = [
+        (
+            format!("2024-07-10 12:00:00.123456789 UTC: [Canister {id}] Hello, world"),
+            Some((CanisterLogKind::Print, "Hello, world")),
+        ),
+        (
+            format!("[Canister {id}] Panicked at 'boom', src/lib.rs:5:5"),
+            Some((CanisterLogKind::Trap, "Panicked at 'boom', src/lib.rs:5:5")),
+        ),
+        (
+            format!("Error: Canister {id} trapped explicitly: out of gas"),
+            Some((CanisterLogKind::Trap, "trapped explicitly: out of gas")),
+        ),
+        (
+            format!("Reject text: Canister {id}: Canister called `ic0.trap` with message: no"),
+            Some((
+                CanisterLogKind::Trap,
+                "Canister called `ic0.trap` with message: no",
+            )),
+        ),
+        (
+            format!("Installing code for canister backend, with canister ID {id}"),
+            None,
+        ),
+        (
+            "Canister called `ic0.trap` with message: no".to_string(),
+            None,
+        ),
+    ];
+    for (line, expected) in cases {
+        let log = CanisterLog::parse(&line);
+        assert_eq!(
+            log.as_ref().map(|l| (l.kind, l.message.as_str())),
+            expected,
+            "{line}"
+        );
+        if let Some(log) = log {
+            assert_eq!(log.canister_id, id);
+        }
+    }
+}
+
+#[test]
+fn test_strip_ansi() {
+    assert_eq!(strip_ansi("\x1b[1;31merror\x1b[0m: boom"), "error: boom");
+    assert_eq!(strip_ansi("plain"), "plain");
+}
@@ -1,23 +1,39 @@
 mod app;
+mod config;
 mod dfx_commands;
 mod dfx_project;
+mod discovery;
+mod logs;
+mod metrics;
+mod networks;
 mod parse_replica_dashboard;
+mod replica_status;
+mod replica_supervisor;
+#[cfg(test)]
+mod test_util;
 
 use crate::app::state::AppState;
-use crate::{app::layout::get_layout, dfx_project::ProjectDatabase};
+use crate::{
+    app::{
+        keymap::Action,
+        layout::{centered_rect, get_layout, split_canister_info},
+    },
+    config::{Config, DiscoveryConfig},
+    dfx_project::ProjectDatabase,
+};
 use anyhow::Result;
 use clap::{Parser, Subcommand};
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{self, Event},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::backend::CrosstermBackend;
+use ratatui::widgets::Clear;
 use ratatui::Terminal;
 use std::io;
 use std::path::PathBuf;
 use std::time::Duration;
-use std::time::Instant;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -26,13 +42,82 @@ struct CliArgs {
     #[clap(long, env = "DFX_CACHE_PATH", global = true)]
     dfx_cache_path: Option<PathBuf>,
 
+    /// Log file to follow in its own tab of the logs pane, as PATH or
+    /// NAME=PATH. Can be given several times.
+    #[clap(long = "follow", value_name = "[NAME=]PATH")]
+    follow: Vec<String>,
+
+    /// Config file to use instead of config.json in the extension directory.
+    #[clap(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Network to select on startup, overriding the config.
+    #[clap(long)]
+    network: Option<String>,
+
+    /// Identity to select on startup, overriding the config.
+    #[clap(long)]
+    identity: Option<String>,
+
+    /// How often the replica is polled, in milliseconds, overriding the config.
+    #[clap(long, value_name = "MS")]
+    refresh_ms: Option<u64>,
+
     #[clap(subcommand)]
     command: Option<Commands>,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    RegisterDfxProject { project_path: PathBuf },
+    RegisterDfxProject {
+        project_path: PathBuf,
+    },
+    UnregisterDfxProject {
+        project_path: PathBuf,
+    },
+    /// List the registered projects and whether they can be read.
+    ListDfxProjects,
+    /// Look for dfx projects below directories, the config's discovery roots
+    /// by default.
+    DiscoverDfxProjects {
+        roots: Vec<PathBuf>,
+        /// How many directories below a root to look, overriding the config.
+        #[clap(long)]
+        max_depth: Option<usize>,
+        /// Register every project found that is not registered yet.
+        #[clap(long)]
+        register: bool,
+    },
+}
+
+/// Prints the projects below the discovery roots, registering the new ones
+/// if asked to.
+fn discover_dfx_projects(config: &DiscoveryConfig, db_path: PathBuf, register: bool) -> Result<()> {
+    let found = discovery::find_projects(&config.roots, config);
+    if found.is_empty() {
+        println!("No dfx projects found.");
+    }
+    let list = |db: &mut ProjectDatabase| {
+        for path in &found {
+            if db.registered_path(path).is_some() {
+                println!("registered  {}", path.display());
+            } else if register {
+                match db.register(path) {
+                    Ok(_) => println!("added       {}", path.display()),
+                    Err(e) => println!("skipped     {}: {e}", path.display()),
+                }
+            } else {
+                println!("new         {}", path.display());
+            }
+        }
+        Ok(())
+    };
+    if register {
+        ProjectDatabase::update(&db_path, list)?;
+    } else {
+        list(&mut ProjectDatabase::load(&db_path)?)?;
+    }
+    Ok(())
 }
 
 fn main() -> Result<()> {
@@ -50,17 +135,63 @@ fn main() -> Result<()> {
         ProjectDatabase::init(&db_path)?;
     }
 
+    let config_path = cli_args
+        .config
+        .clone()
+        .unwrap_or_else(|| db_path.with_file_name("config.json"));
     if let Some(command) = cli_args.command {
         match command {
             Commands::RegisterDfxProject { project_path } => {
                 println!("Registering DFX project at {:?}", project_path);
                 ProjectDatabase::register_dfx_project(project_path, db_path)?;
-                return Ok(());
+            }
+            Commands::UnregisterDfxProject { project_path } => {
+                ProjectDatabase::unregister_dfx_project(project_path, db_path)?;
+            }
+            Commands::ListDfxProjects => ProjectDatabase::list_dfx_projects(db_path)?,
+            Commands::DiscoverDfxProjects {
+                roots,
+                max_depth,
+                register,
+            } => {
+                let mut config = Config::load(&config_path)?.discovery;
+                if !roots.is_empty() {
+                    config.roots = roots;
+                }
+                if let Some(max_depth) = max_depth {
+                    config.max_depth = max_depth;
+                }
+                if config.roots.is_empty() {
+                    anyhow::bail!(
+                        "Give the directories to look in, or set discovery.roots in {:?}.",
+                        config_path
+                    );
+                }
+                discover_dfx_projects(&config, db_path, register)?;
             }
         }
+        return Ok(());
     }
 
     let db = ProjectDatabase::load(&db_path)?;
+    let mut config = Config::load(&config_path)?;
+    if let Some(network) = cli_args.network {
+        config.default_network = network;
+    }
+    if cli_args.identity.is_some() {
+        config.default_identity = cli_args.identity;
+    }
+    if let Some(refresh_ms) = cli_args.refresh_ms {
+        config.refresh.replica_ms = refresh_ms;
+    }
+    let errors = config.validate();
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "Invalid config {:?}:\n  {}",
+            config_path,
+            errors.join("\n  ")
+        );
+    }
     enable_raw_mode()?;
     let mut stdout = io::stdout();
     execute!(stdout, EnterAlternateScreen)?;
@@ -68,25 +199,24 @@ fn main() -> Result<()> {
     // Initialize the app state
     let path_to_dfx = cli_args.dfx_cache_path.unwrap().join("dfx");
     let path_to_dfx = path_to_dfx.to_str().unwrap();
-    let mut app_state = AppState::new(path_to_dfx, db);
+    let mut app_state = AppState::new(path_to_dfx, db, config);
+    for source in cli_args.follow {
+        match source.split_once('=') {
+            Some((name, path)) if !name.contains(std::path::MAIN_SEPARATOR) => {
+                app_state.follow_file(Some(name.to_string()), PathBuf::from(path))
+            }
+            _ => app_state.follow_file(None, PathBuf::from(source)),
+        }
+    }
 
     let backend = CrosstermBackend::new(stdout);
     let mut terminal = Terminal::new(backend)?;
 
     // Main loop
-    let mut last_tick = Instant::now();
-
     'mainloop: loop {
-        // Handle user input
-        if event::poll(app_state.refresh_interval)? {
-            if let Event::Key(key_event) = event::read()? {
-                if key_event.code == KeyCode::Char('q') {
-                    break 'mainloop;
-                } else {
-                    app_state.handle_input(key_event, path_to_dfx);
-                }
-            }
-        }
+        // Apply whatever the background workers produced; never blocks
+        app_state.refresh();
+
         terminal.draw(|frame| {
             let network_widget = if app_state.network_selection_menu_active {
                 app_state.widget_network_selection_menu()
@@ -99,37 +229,59 @@ fn main() -> Result<()> {
                 app_state.widget_identity()
             };
             let canisters_list_widget = app_state.widget_canisters_list();
-            let canister_info_widget = app_state.widget_canister_info();
-            let logs_widget = app_state.widget_logs();
-            // let logs_widget = if app_state.logfile_selection_menu_active.is_some() {
-            //     app_state.widget_logs_file_selection()
-            // } else {
-            //     app_state.widget_logs()
-            // };
-
+            let canister_info_widget = if app_state.project_view.active {
+                app_state.widget_project_view()
+            } else if app_state.caller.active {
+                app_state.widget_caller()
+            } else {
+                app_state.widget_canister_info()
+            };
+            let history_series = if app_state.caller.active || app_state.project_view.active {
+                vec![]
+            } else {
+                app_state.canister_history_series()
+            };
             let (canisters_chunk, network_chunk, canister_info_chunk, identity_chunk, logs_chunk) =
-                get_layout(frame.size());
+                get_layout(frame.size(), &app_state.config.layout);
+            let logs_widget = app_state
+                .widget_logs_file_selection()
+                .unwrap_or_else(|| app_state.widget_logs(logs_chunk.height));
 
             frame.render_widget(canisters_list_widget, canisters_chunk);
+            let (canister_info_chunk, chart_chunks) =
+                split_canister_info(canister_info_chunk, history_series.len());
             frame.render_widget(canister_info_widget, canister_info_chunk);
+            for ((title, data), chunk) in history_series.iter().zip(chart_chunks) {
+                frame.render_widget(app_state.widget_canister_chart(title, data), chunk);
+            }
             frame.render_widget(identity_widget, identity_chunk);
             frame.render_widget(network_widget, network_chunk);
             frame.render_widget(logs_widget, logs_chunk);
+            if let Some(dialog_widget) = app_state.widget_dialog() {
+                let dialog_chunk = centered_rect(60, app_state.dialog_height(), frame.size());
+                frame.render_widget(Clear, dialog_chunk);
+                frame.render_widget(dialog_widget, dialog_chunk);
+            }
+            if let Some(help_widget) = app_state.widget_help() {
+                let help_chunk = centered_rect(60, app_state.help_height(), frame.size());
+                frame.render_widget(Clear, help_chunk);
+                frame.render_widget(help_widget, help_chunk);
+            }
         })?;
 
+        // Handle user input
         if event::poll(Duration::from_millis(100))? {
-            if let Event::Key(key) = event::read()? {
-                if key.code == KeyCode::Char('q') {
-                    break;
+            if let Event::Key(key_event) = event::read()? {
+                // 'q' is a regular character while typing into a form
+                if app_state.keymap.action(app_state.context(), &key_event) == Some(Action::Quit)
+                    && !app_state.captures_keys()
+                {
+                    break 'mainloop;
+                } else {
+                    app_state.handle_input(key_event);
                 }
             }
         }
-
-        if last_tick.elapsed() >= app_state.refresh_interval {
-            // Update the UI as needed
-            last_tick = Instant::now();
-            app_state.refresh(path_to_dfx);
-        }
     }
 
     // Restore terminal
@@ -141,5 +293,16 @@ fn main() -> Result<()> {
     )?;
     terminal.show_cursor()?;
 
+    // the replica cannot outlive the pipes its output goes to; one that was
+    // adopted keeps running
+    if let Some(process) = app_state
+        .replica_process
+        .take()
+        .filter(|process| !process.is_adopted())
+    {
+        println!("Stopping the local replica...");
+        process.stop(&app_state.dfx_context());
+    }
+
     Ok(())
 }
@@ -0,0 +1,325 @@
+//! Numeric canister metrics with units.
+//!
+//! The replica dashboard and `dfx canister status` print numbers in a handful
+//! of loose formats (`93_800_000_000_000`, `Nat(2294162)`, `238 bytes`, `0 %`,
+//! `3.1 T cycles`). Every type here parses all of them via [`FromStr`] and
+//! renders itself in a human-readable form via [`Display`](fmt::Display).
+
+use std::{fmt, str::FromStr, time::Duration};
+
+/// A cycles amount. Rendered with SI-style suffixes, e.g. "1.2 T cycles".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Cycles(pub u128);
+
+/// A size in bytes. Rendered with binary prefixes, e.g. "3.4 MiB".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Bytes(pub u64);
+
+/// A whole percentage, as used by compute allocations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Percent(pub u64);
+
+/// An execution round number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Round(pub u64);
+
+/// A duration given in seconds, e.g. the freezing threshold. Rendered as
+/// "30 days" or "1 h 5 min".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Seconds(pub Duration);
+
+/// A canister's reserved memory. Zero means best-effort.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MemoryAllocation {
+    #[default]
+    BestEffort,
+    Reserved(Bytes),
+}
+
+const CYCLES_UNITS: [(&str, u128); 6] = [
+    ("q", 1_000_000_000_000_000),
+    ("t", 1_000_000_000_000),
+    ("b", 1_000_000_000),
+    ("m", 1_000_000),
+    ("k", 1_000),
+    ("", 1),
+];
+
+const BYTES_UNITS: [(&str, u128); 11] = [
+    ("gib", 1 << 30),
+    ("gb", 1_000_000_000),
+    ("mib", 1 << 20),
+    ("mb", 1_000_000),
+    ("kib", 1 << 10),
+    ("kb", 1_000),
+    // wasm pages, as printed for heap_size and stable_memory_size
+    ("pages", 64 << 10),
+    ("page", 64 << 10),
+    ("bytes", 1),
+    ("b", 1),
+    ("", 1),
+];
+
+/// Splits `"3.1 T cycles"` into the number `"3.1"` and the lowercase unit
+/// `"t cycles"`, dropping `Nat(...)` wrappers and digit separators.
+fn split_number(s: &str) -> Option<(String, String)> {
+    let s = s.trim();
+    let s = s
+        .strip_prefix("Nat(")
+        .and_then(|s| s.strip_suffix(')'))
+        .unwrap_or(s);
+    let end = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '_' || c == ',' || c == '.'))
+        .unwrap_or(s.len());
+    let number: String = s[..end]
+        .chars()
+        .filter(|c| *c != '_' && *c != ',')
+        .collect();
+    if number.is_empty() || number == "." {
+        return None;
+    }
+    Some((number, s[end..].trim().to_lowercase()))
+}
+
+/// Multiplies a decimal number by `multiplier` without going through floats,
+/// so large cycle balances stay exact.
+fn scale(number: &str, multiplier: u128) -> Option<u128> {
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    let whole: u128 = if whole.is_empty() {
+        0
+    } else {
+        whole.parse().ok()?
+    };
+    let value = whole.checked_mul(multiplier)?;
+    if fraction.is_empty() {
+        return Some(value);
+    }
+    let denominator = 10u128.checked_pow(fraction.len() as u32)?;
+    let fraction: u128 = fraction.parse().ok()?;
+    value.checked_add(fraction.checked_mul(multiplier)? / denominator)
+}
+
+fn parse_with_units(s: &str, units: &[(&str, u128)], suffix: &str) -> Result<u128, String> {
+    let (number, unit) = split_number(s).ok_or_else(|| format!("'{s}' is not a number"))?;
+    let unit = unit.trim_end_matches(suffix).trim();
+    let (_, multiplier) = units
+        .iter()
+        .find(|(name, _)| *name == unit)
+        .ok_or_else(|| format!("unknown unit '{unit}' in '{s}'"))?;
+    scale(&number, *multiplier).ok_or_else(|| format!("'{s}' is out of range"))
+}
+
+fn parse_plain(s: &str, suffixes: &[&str]) -> Result<u64, String> {
+    let (number, unit) = split_number(s).ok_or_else(|| format!("'{s}' is not a number"))?;
+    if !unit.is_empty() && !suffixes.contains(&unit.as_str()) {
+        return Err(format!("unknown unit '{unit}' in '{s}'"));
+    }
+    number
+        .parse()
+        .map_err(|_| format!("'{s}' is not a whole number"))
+}
+
+/// Formats `value / unit` with one decimal, dropping a trailing ".0".
+fn format_scaled(value: u128, unit: u128) -> String {
+    let tenths = (value * 10 + unit / 2) / unit;
+    if tenths.is_multiple_of(10) {
+        format!("{}", tenths / 10)
+    } else {
+        format!("{}.{}", tenths / 10, tenths % 10)
+    }
+}
+
+impl FromStr for Cycles {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_with_units(s, &CYCLES_UNITS, "cycles").map(Cycles)
+    }
+}
+
+impl fmt::Display for Cycles {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match CYCLES_UNITS.iter().find(|(_, unit)| self.0 >= *unit) {
+            Some((name, unit)) if *unit > 1 => write!(
+                f,
+                "{} {} cycles",
+                format_scaled(self.0, *unit),
+                name.to_uppercase()
+            ),
+            _ => write!(f, "{} cycles", self.0),
+        }
+    }
+}
+
+impl FromStr for Bytes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = parse_with_units(s, &BYTES_UNITS, "")?;
+        u64::try_from(bytes)
+            .map(Bytes)
+            .map_err(|_| format!("'{s}' is out of range"))
+    }
+}
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = u128::from(self.0);
+        for (name, unit) in [("GiB", 1 << 30), ("MiB", 1 << 20), ("KiB", 1 << 10)] {
+            if bytes >= unit {
+                return write!(f, "{} {name}", format_scaled(bytes, unit));
+            }
+        }
+        write!(f, "{bytes} B")
+    }
+}
+
+impl FromStr for Percent {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_plain(s, &["%"]).map(Percent)
+    }
+}
+
+impl fmt::Display for Percent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}%", self.0)
+    }
+}
+
+impl FromStr for Round {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_plain(s, &[]).map(Round)
+    }
+}
+
+impl fmt::Display for Round {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl FromStr for Seconds {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_plain(s, &["s", "sec", "seconds"]).map(|s| Seconds(Duration::from_secs(s)))
+    }
+}
+
+impl fmt::Display for Seconds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs();
+        let (days, hours, minutes) = (secs / 86_400, secs % 86_400 / 3600, secs % 3600 / 60);
+        match (days, hours, minutes) {
+            (0, 0, 0) => write!(f, "{secs} s"),
+            (0, 0, m) => write!(f, "{m} min"),
+            (0, h, 0) => write!(f, "{h} h"),
+            (0, h, m) => write!(f, "{h} h {m} min"),
+            (1, 0, _) => write!(f, "1 day"),
+            (d, 0, _) => write!(f, "{d} days"),
+            (d, h, _) => write!(f, "{d} d {h} h"),
+        }
+    }
+}
+
+impl FromStr for MemoryAllocation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().eq_ignore_ascii_case("best-effort") {
+            return Ok(MemoryAllocation::BestEffort);
+        }
+        match s.parse::<Bytes>()? {
+            Bytes(0) => Ok(MemoryAllocation::BestEffort),
+            bytes => Ok(MemoryAllocation::Reserved(bytes)),
+        }
+    }
+}
+
+impl fmt::Display for MemoryAllocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MemoryAllocation::BestEffort => write!(f, "best-effort"),
+            MemoryAllocation::Reserved(bytes) => write!(f, "{bytes}"),
+        }
+    }
+}
+
+/// Parses an optional metric, treating anything unparsable as missing.
+pub fn parse<T: FromStr>(s: &str) -> Option<T> {
+    s.parse().ok()
+}
+
+/// Renders an optional metric, "N/A" when missing.
+pub fn display<T: fmt::Display>(value: Option<T>) -> String {
+    value.map_or_else(|| "N/A".to_string(), |v| v.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cycles() {
+        assert_eq!(
+            parse("93_800_000_000_000"),
+            Some(Cycles(93_800_000_000_000))
+        );
+        assert_eq!(
+            parse("3_100_000_000_000 Cycles"),
+            Some(Cycles(3_100_000_000_000))
+        );
+        assert_eq!(parse("3.1 T cycles"), Some(Cycles(3_100_000_000_000)));
+        assert_eq!(parse("Nat(42)"), Some(Cycles(42)));
+        assert_eq!(parse::<Cycles>("lots"), None);
+        assert_eq!(parse::<Cycles>(""), None);
+    }
+
+    #[test]
+    fn test_display_cycles() {
+        assert_eq!(Cycles(1_234_000_000_000).to_string(), "1.2 T cycles");
+        assert_eq!(Cycles(100_000_000_000_000).to_string(), "100 T cycles");
+        assert_eq!(Cycles(93_799_340_083_699).to_string(), "93.8 T cycles");
+        assert_eq!(Cycles(2_500_000).to_string(), "2.5 M cycles");
+        assert_eq!(Cycles(999).to_string(), "999 cycles");
+    }
+
+    #[test]
+    fn test_bytes() {
+        assert_eq!(parse("238 bytes"), Some(Bytes(238)));
+        assert_eq!(parse("7345934"), Some(Bytes(7_345_934)));
+        assert_eq!(parse("Nat(2294162)"), Some(Bytes(2_294_162)));
+        assert_eq!(parse("85 pages"), Some(Bytes(85 * 65536)));
+        assert_eq!(parse("1.5 KiB"), Some(Bytes(1536)));
+        assert_eq!(Bytes(3_565_158).to_string(), "3.4 MiB");
+        assert_eq!(Bytes(238).to_string(), "238 B");
+        assert_eq!(Bytes(2048).to_string(), "2 KiB");
+    }
+
+    #[test]
+    fn test_other_metrics() {
+        assert_eq!(parse("0%"), Some(Percent(0)));
+        assert_eq!(parse("25 %"), Some(Percent(25)));
+        assert_eq!(parse(" 104 "), Some(Round(104)));
+        assert_eq!(
+            parse("2_592_000"),
+            Some(Seconds(Duration::from_secs(2_592_000)))
+        );
+        assert_eq!(
+            Seconds(Duration::from_secs(2_592_000)).to_string(),
+            "30 days"
+        );
+        assert_eq!(Seconds(Duration::from_secs(3900)).to_string(), "1 h 5 min");
+        assert_eq!(parse("best-effort"), Some(MemoryAllocation::BestEffort));
+        assert_eq!(parse("0"), Some(MemoryAllocation::BestEffort));
+        assert_eq!(
+            parse("1 GiB"),
+            Some(MemoryAllocation::Reserved(Bytes(1 << 30)))
+        );
+        assert_eq!(display::<Round>(None), "N/A");
+    }
+}
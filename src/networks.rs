@@ -0,0 +1,233 @@
+use crate::dfx_project::{Extra, ProjectDatabase};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+// Structure matching a network definition, found both in the user-level
+// "networks.json" and in the "networks" section of a project's "dfx.json"
+// ```
+// {
+//   "local": {
+//     "bind": "127.0.0.1:4943",
+//     "type": "ephemeral"
+//   },
+//   "staging": {
+//     "providers": ["https://staging.example.com"],
+//     "type": "persistent"
+//   }
+// }
+// ```
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct NetworkConfig {
+    pub bind: Option<String>,
+    pub providers: Option<Vec<String>>,
+    #[serde(rename = "type")]
+    pub network_type: Option<NetworkType>,
+    pub playground: Option<serde_json::Value>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkType {
+    Ephemeral,
+    Persistent,
+    Playground,
+    /// A type newer than this model, kept as written.
+    #[serde(untagged)]
+    Other(String),
+}
+
+impl fmt::Display for NetworkType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkType::Ephemeral => write!(f, "ephemeral"),
+            NetworkType::Persistent => write!(f, "persistent"),
+            NetworkType::Playground => write!(f, "playground"),
+            NetworkType::Other(other) => write!(f, "{other}"),
+        }
+    }
+}
+
+/// Where a network definition came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum NetworkOrigin {
+    BuiltIn,
+    NetworksJson(PathBuf),
+    Project(PathBuf),
+}
+
+impl fmt::Display for NetworkOrigin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkOrigin::BuiltIn => write!(f, "built-in"),
+            NetworkOrigin::NetworksJson(_) => write!(f, "networks.json"),
+            NetworkOrigin::Project(path) => write!(
+                f,
+                "project {}",
+                path.file_name()
+                    .unwrap_or(path.as_os_str())
+                    .to_string_lossy()
+            ),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkInfo {
+    pub name: String,
+    pub provider: String,
+    pub network_type: NetworkType,
+    pub origin: NetworkOrigin,
+}
+
+impl NetworkInfo {
+    fn from_config(name: &str, config: &NetworkConfig, origin: NetworkOrigin) -> Self {
+        let provider = match (&config.providers, &config.bind) {
+            (Some(providers), _) if !providers.is_empty() => providers.join(", "),
+            (_, Some(bind)) => format!("http://{bind}"),
+            _ => "N/A".to_string(),
+        };
+        // dfx defaults to ephemeral for `bind` networks and persistent for `providers` ones
+        let network_type = if config.playground.is_some() {
+            NetworkType::Playground
+        } else {
+            config
+                .network_type
+                .clone()
+                .unwrap_or(if config.bind.is_some() {
+                    NetworkType::Ephemeral
+                } else {
+                    NetworkType::Persistent
+                })
+        };
+        NetworkInfo {
+            name: name.to_string(),
+            provider,
+            network_type,
+            origin,
+        }
+    }
+
+    /// Directory dfx has to run in to resolve this network, if it is only
+    /// defined inside a project.
+    pub fn project_dir(&self) -> Option<PathBuf> {
+        match &self.origin {
+            NetworkOrigin::Project(path) => Some(path.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn builtin() -> Vec<NetworkInfo> {
+        vec![
+            NetworkInfo {
+                name: "local".to_string(),
+                provider: "http://127.0.0.1:4943".to_string(),
+                network_type: NetworkType::Ephemeral,
+                origin: NetworkOrigin::BuiltIn,
+            },
+            NetworkInfo {
+                name: "ic".to_string(),
+                provider: "https://icp0.io".to_string(),
+                network_type: NetworkType::Persistent,
+                origin: NetworkOrigin::BuiltIn,
+            },
+        ]
+    }
+}
+
+/// Builds the network menu: the built-in `local` and `ic`, overridden or
+/// extended by the user-level networks.json, followed by the networks each
+/// registered project defines in its dfx.json.
+pub fn discover(networks_json: Option<&Path>, db: &ProjectDatabase) -> Vec<NetworkInfo> {
+    let mut networks = NetworkInfo::builtin();
+
+    if let Some(path) = networks_json {
+        if let Ok(content) = fs::read_to_string(path) {
+            if let Ok(configs) = serde_json::from_str::<HashMap<String, NetworkConfig>>(&content) {
+                let origin = NetworkOrigin::NetworksJson(path.to_path_buf());
+                for (name, config) in sorted(configs) {
+                    let info = NetworkInfo::from_config(&name, &config, origin.clone());
+                    match networks.iter_mut().find(|n| n.name == name) {
+                        Some(existing) => *existing = info,
+                        None => networks.push(info),
+                    }
+                }
+            }
+        }
+    }
+
+    let mut projects: Vec<_> = db.projects.iter().collect();
+    projects.sort_by(|a, b| a.0.cmp(b.0));
+    for (project_path, project) in projects {
+        for (name, config) in sorted(project.networks.clone()) {
+            networks.push(NetworkInfo::from_config(
+                &name,
+                &config,
+                NetworkOrigin::Project(project_path.clone()),
+            ));
+        }
+    }
+    networks
+}
+
+fn sorted(configs: HashMap<String, NetworkConfig>) -> Vec<(String, NetworkConfig)> {
+    let mut configs: Vec<_> = configs.into_iter().collect();
+    configs.sort_by(|a, b| a.0.cmp(&b.0));
+    configs
+}
+
+/// Fallback when `dfx info networks-json-path` is unavailable.
+pub fn default_networks_json_path() -> Option<PathBuf> {
+    dirs::home_dir().map(|home| home.join(".config/dfx/networks.json"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_network_config_defaults() {
+        let configs: HashMap<String, NetworkConfig> = serde_json::from_str(
+            r#"{
+  "local": { "bind": "127.0.0.1:8000" },
+  "staging": { "providers": ["https://staging.example.com"] },
+  "testnet": { "providers": ["https://testnet.example.com"], "type": "ephemeral" },
+  "playground": { "providers": ["https://icp0.io"], "playground": { "playground_canister": "rrkah-fqaaa-aaaaa-aaaaq-cai" } },
+  "edge": { "providers": ["https://edge.example.com"], "type": "sovereign" }
+}"#,
+        )
+        .unwrap();
+        let info =
+            |name: &str| NetworkInfo::from_config(name, &configs[name], NetworkOrigin::BuiltIn);
+        assert_eq!(info("local").provider, "http://127.0.0.1:8000");
+        assert_eq!(info("local").network_type, NetworkType::Ephemeral);
+        assert_eq!(info("staging").provider, "https://staging.example.com");
+        assert_eq!(info("staging").network_type, NetworkType::Persistent);
+        assert_eq!(info("testnet").network_type, NetworkType::Ephemeral);
+        assert_eq!(info("playground").network_type, NetworkType::Playground);
+
+        // an unknown type doesn't make the whole dfx.json unreadable
+        let project: Result<crate::dfx_project::DfxProject, _> =
+            serde_json::from_str(r#"{ "networks": { "edge": { "type": "sovereign" } } }"#);
+        assert!(project.is_ok());
+        let edge = info("edge").network_type;
+        assert_eq!(edge, NetworkType::Other("sovereign".to_string()));
+        assert_eq!(edge.to_string(), "sovereign");
+        assert_eq!(
+            serde_json::to_value(&configs["edge"]).unwrap()["type"],
+            "sovereign"
+        );
+    }
+
+    #[test]
+    fn test_origin_display() {
+        let origin = NetworkOrigin::Project(PathBuf::from("/home/dev/my_dapp"));
+        assert_eq!(origin.to_string(), "project my_dapp");
+        assert_eq!(NetworkOrigin::BuiltIn.to_string(), "built-in");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::metrics::{self, Bytes, Cycles, MemoryAllocation, Percent, Round, Seconds};
 use anyhow::Result;
 use scraper::{Element, ElementRef};
 use scraper::{Html, Selector};
@@ -139,11 +140,7 @@ impl Exports {
             if let Some(substring) = s.get(heartbeat_value_start..) {
                 if let Some(heartbeat_end) = substring.find(',') {
                     let heartbeat_str = &substring[..heartbeat_end].trim();
-                    exports.exports_heartbeat = if heartbeat_str == &"true" {
-                        true
-                    } else {
-                        false
-                    };
+                    exports.exports_heartbeat = heartbeat_str == &"true";
                 }
             }
         }
@@ -170,7 +167,7 @@ impl Exports {
             .filter(|s| s.starts_with(&kind))
             .map(|s| {
                 if kind == "System" {
-                    s.replace(&format!("System("), "").replace(")", "")
+                    s.replace("System(", "").replace(")", "")
                 } else {
                     s.replace(&format!("{kind}(\""), "").replace("\")", "")
                 }
@@ -192,19 +189,19 @@ pub struct ReplicaInfo {
 pub struct CanisterInfo {
     pub canister_id: String,
     pub status: String,
-    pub memory_allocation: String,
-    pub last_execution_round: String,
+    pub memory_allocation: Option<MemoryAllocation>,
+    pub last_execution_round: Option<Round>,
     pub controllers: String,
-    pub certified_data_length: String,
-    pub canister_history_memory_usage: String,
+    pub certified_data_length: Option<Bytes>,
+    pub canister_history_memory_usage: Option<Bytes>,
     pub execution_state: String,
-    pub last_full_execution_round: String,
+    pub last_full_execution_round: Option<Round>,
     pub exports: Exports,
-    pub compute_allocation: String,
-    pub freeze_threshold: String,
-    pub memory_usage: String,
-    pub accumulated_priority: String,
-    pub cycles_balance: String,
+    pub compute_allocation: Option<Percent>,
+    pub freeze_threshold: Option<Seconds>,
+    pub memory_usage: Option<Bytes>,
+    pub accumulated_priority: Option<i64>,
+    pub cycles_balance: Option<Cycles>,
 }
 
 impl ReplicaInfo {
@@ -226,7 +223,7 @@ impl ReplicaInfo {
         let header_values: Vec<String> = header_data
             .map(|n| n.inner_html().as_str().to_string())
             .collect();
-        replica_dashboard.replica_version = header_values.get(0).unwrap_or(&"".to_string()).clone();
+        replica_dashboard.replica_version = header_values.first().cloned().unwrap_or_default();
         replica_dashboard.subnet_type = header_values.get(1).unwrap_or(&"".to_string()).clone();
         replica_dashboard.total_compute_allocation =
             header_values.get(2).unwrap_or(&"".to_string()).clone();
@@ -243,17 +240,19 @@ impl ReplicaInfo {
             let mut data_map = HashMap::new();
             if let Some(details) = summary.parent_element() {
                 let td = Selector::parse("td").unwrap();
+                // summary -> details -> td -> tr: the canister's own row
                 let tds = details
                     .parent_element()
-                    .unwrap()
-                    .parent_element()
-                    .unwrap()
-                    .parent_element()
-                    .unwrap()
-                    .select(&td)
-                    .map(|v| v.inner_html())
-                    .collect::<Vec<_>>();
-                let get_val = |nth: usize| tds.iter().rev().nth(nth).unwrap().trim().to_string();
+                    .and_then(|e| e.parent_element())
+                    .map(|row| row.select(&td).map(|v| v.inner_html()).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                let get_val = |nth: usize| {
+                    tds.iter()
+                        .rev()
+                        .nth(nth)
+                        .map(|v| v.trim().to_string())
+                        .unwrap_or_default()
+                };
                 data_map.insert("status".to_string(), get_val(2));
                 data_map.insert("memory_allocation".to_string(), get_val(1));
                 data_map.insert("last_execution_round".to_string(), get_val(0));
@@ -271,22 +270,25 @@ impl ReplicaInfo {
                     });
             }
             let map_get = |key: &str| data_map.get(key).cloned().unwrap_or_default();
+            let metric = |key: &str| data_map.get(key).map(String::as_str).unwrap_or_default();
             let canister_info = CanisterInfo {
                 canister_id,
                 status: map_get("status"),
-                memory_allocation: map_get("memory_allocation"),
-                last_execution_round: map_get("last_execution_round"),
+                memory_allocation: metrics::parse(metric("memory_allocation")),
+                last_execution_round: metrics::parse(metric("last_execution_round")),
                 controllers: map_get("controllers"),
-                certified_data_length: map_get("certified_data length"),
-                canister_history_memory_usage: map_get("canister_history_memory_usage"),
+                certified_data_length: metrics::parse(metric("certified_data length")),
+                canister_history_memory_usage: metrics::parse(metric(
+                    "canister_history_memory_usage",
+                )),
                 execution_state: "".to_string(), // Field not captured correctly in your example
                 exports: Exports::from_str(map_get("exports")).unwrap_or_default(),
-                last_full_execution_round: map_get("last_full_execution_round"),
-                compute_allocation: map_get("compute_allocation"),
-                freeze_threshold: map_get("freeze_threshold (seconds)"),
-                memory_usage: map_get("memory_usage"),
-                accumulated_priority: map_get("accumulated_priority"),
-                cycles_balance: map_get("Cycles balance"),
+                last_full_execution_round: metrics::parse(metric("last_full_execution_round")),
+                compute_allocation: metrics::parse(metric("compute_allocation")),
+                freeze_threshold: metrics::parse(metric("freeze_threshold (seconds)")),
+                memory_usage: metrics::parse(metric("memory_usage")),
+                accumulated_priority: metric("accumulated_priority").trim().parse().ok(),
+                cycles_balance: metrics::parse(metric("Cycles balance")),
             };
 
             replica_dashboard.canisters.push(canister_info);
@@ -296,9 +298,156 @@ impl ReplicaInfo {
     }
 }
 
+#[cfg(test)]
+fn exports(update: &[&str], query: &[&str], system: &[&str], global_timer: bool) -> Exports {
+    let owned = |names: &[&str]| names.iter().map(|n| n.to_string()).collect();
+    Exports {
+        exported_query_functions: owned(query),
+        exported_update_functions: owned(update),
+        exported_system_functions: owned(system),
+        exports_heartbeat: false,
+        exports_global_timer: global_timer,
+    }
+}
+
+// The cycles wallet.
+#[cfg(test)]
+fn wallet_exports() -> Exports {
+    exports(
+        &[
+            "add_address",
+            "add_controller",
+            "authorize",
+            "deauthorize",
+            "remove_address",
+            "remove_controller",
+            "set_name",
+            "set_short_name",
+            "wallet_call",
+            "wallet_call128",
+            "wallet_create_canister",
+            "wallet_create_canister128",
+            "wallet_create_wallet",
+            "wallet_create_wallet128",
+            "wallet_receive",
+            "wallet_send",
+            "wallet_send128",
+            "wallet_store_wallet_wasm",
+        ],
+        &[
+            "get_chart",
+            "get_controllers",
+            "get_custodians",
+            "get_events",
+            "get_events128",
+            "get_managed_canister_events",
+            "get_managed_canister_events128",
+            "http_request",
+            "list_addresses",
+            "list_managed_canisters",
+            "name",
+            "wallet_api_version",
+            "wallet_balance",
+            "wallet_balance128",
+        ],
+        &["CanisterInit", "CanisterPreUpgrade", "CanisterPostUpgrade"],
+        false,
+    )
+}
+
+// A Motoko "hello world" backend.
+#[cfg(test)]
+fn motoko_exports() -> Exports {
+    exports(
+        &["__motoko_async_helper", "__motoko_gc_trigger"],
+        &[
+            "__get_candid_interface_tmp_hack",
+            "__motoko_stable_var_info",
+            "greet",
+        ],
+        &[
+            "CanisterStart",
+            "CanisterInit",
+            "CanisterPreUpgrade",
+            "CanisterPostUpgrade",
+            "CanisterGlobalTimer",
+        ],
+        true,
+    )
+}
+
+// The asset canister serving a frontend.
+#[cfg(test)]
+fn assets_exports() -> Exports {
+    exports(
+        &[
+            "authorize",
+            "clear",
+            "commit_batch",
+            "commit_proposed_batch",
+            "compute_evidence",
+            "configure",
+            "create_asset",
+            "create_batch",
+            "create_chunk",
+            "deauthorize",
+            "delete_asset",
+            "delete_batch",
+            "get_configuration",
+            "grant_permission",
+            "list_authorized",
+            "list_permitted",
+            "propose_commit_batch",
+            "revoke_permission",
+            "set_asset_content",
+            "set_asset_properties",
+            "store",
+            "take_ownership",
+            "unset_asset_content",
+            "validate_commit_proposed_batch",
+            "validate_configure",
+            "validate_grant_permission",
+            "validate_revoke_permission",
+            "validate_take_ownership",
+        ],
+        &[
+            "api_version",
+            "certified_tree",
+            "get",
+            "get_asset_properties",
+            "get_chunk",
+            "http_request",
+            "http_request_streaming_callback",
+            "list",
+            "retrieve",
+        ],
+        &["CanisterInit", "CanisterPreUpgrade", "CanisterPostUpgrade"],
+        false,
+    )
+}
+
+// The Candid UI canister.
+#[cfg(test)]
+fn candid_ui_exports() -> Exports {
+    exports(
+        &[],
+        &[
+            "binding",
+            "did_to_js",
+            "http_request",
+            "merge_init_args",
+            "subtype",
+        ],
+        &[],
+        false,
+    )
+}
+
 #[test]
 fn parse_example_html() {
+    use crate::metrics::*;
     use pretty_assertions::assert_eq;
+    use std::time::Duration;
     const HTML: &str = r#"
 <!DOCTYPE html>
 <!-- saved from url=(0034)http://localhost:53161/_/dashboard -->
@@ -538,72 +687,72 @@ fn parse_example_html() {
         canisters: vec![CanisterInfo {
             canister_id: "bnz7o-iuaaa-aaaaa-qaaaa-cai".to_string(),
             status: "Running".to_string(),
-            memory_allocation: "best-effort".to_string(),
-            last_execution_round: "0".to_string(),
+            memory_allocation: Some(MemoryAllocation::BestEffort),
+            last_execution_round: Some(Round(104)),
             controllers: "trg6r-vqw4x-tcu5z-pgm4z-nmas4-ailxn-rjavv-zbzhi-jy2oy-wjrpf-hqe"
                 .to_string(),
-            certified_data_length: "32 bytes".to_string(),
-            canister_history_memory_usage: "238 bytes".to_string(),
-            exports: Exports::default(),
+            certified_data_length: Some(Bytes(32)),
+            canister_history_memory_usage: Some(Bytes(238)),
+            exports: wallet_exports(),
             execution_state: "".to_string(),
-            last_full_execution_round: "104".to_string(),
-            compute_allocation: "0%".to_string(),
-            freeze_threshold: "2592000".to_string(),
-            memory_usage: "7345934".to_string(),
-            accumulated_priority: "0".to_string(),
-            cycles_balance: "93_800_000_000_000".to_string(),
+            last_full_execution_round: Some(Round(104)),
+            compute_allocation: Some(Percent(0)),
+            freeze_threshold: Some(Seconds(Duration::from_secs(2592000))),
+            memory_usage: Some(Bytes(7345934)),
+            accumulated_priority: Some(0),
+            cycles_balance: Some(Cycles(93_800_000_000_000)),
         },
 
         CanisterInfo {
             canister_id: "bkyz2-fmaaa-aaaaa-qaaaq-cai".to_string(),
             status: "Running".to_string(),
-            memory_allocation: "best-effort".to_string(),
-            last_execution_round: "0".to_string(),
+            memory_allocation: Some(MemoryAllocation::BestEffort),
+            last_execution_round: Some(Round(0)),
             controllers: "bnz7o-iuaaa-aaaaa-qaaaa-cai trg6r-vqw4x-tcu5z-pgm4z-nmas4-ailxn-rjavv-zbzhi-jy2oy-wjrpf-hqe".to_string(),
-            certified_data_length: "0 bytes".to_string(),
-            canister_history_memory_usage: "268 bytes".to_string(),
+            certified_data_length: Some(Bytes(0)),
+            canister_history_memory_usage: Some(Bytes(268)),
             execution_state: "".to_string(),
-            last_full_execution_round: "0".to_string(),
-            exports: Exports::default(),
-            compute_allocation: "0%".to_string(),
-            freeze_threshold: "2592000".to_string(),
-            memory_usage: "2294162".to_string(),
-            accumulated_priority: "0".to_string(),
-            cycles_balance: "3_100_000_000_000".to_string(),
+            last_full_execution_round: Some(Round(0)),
+            exports: motoko_exports(),
+            compute_allocation: Some(Percent(0)),
+            freeze_threshold: Some(Seconds(Duration::from_secs(2592000))),
+            memory_usage: Some(Bytes(2294162)),
+            accumulated_priority: Some(0),
+            cycles_balance: Some(Cycles(3_100_000_000_000)),
         },
         CanisterInfo {
             canister_id: "bd3sg-teaaa-aaaaa-qaaba-cai".to_string(),
             status: "Running".to_string(),
-            memory_allocation: "best-effort".to_string(),
-            last_execution_round: "0".to_string(),
+            memory_allocation: Some(MemoryAllocation::BestEffort),
+            last_execution_round: Some(Round(128)),
             controllers: "bnz7o-iuaaa-aaaaa-qaaaa-cai trg6r-vqw4x-tcu5z-pgm4z-nmas4-ailxn-rjavv-zbzhi-jy2oy-wjrpf-hqe".to_string(),
-            certified_data_length: "32 bytes".to_string(),
-            canister_history_memory_usage: "268 bytes".to_string(),
+            certified_data_length: Some(Bytes(32)),
+            canister_history_memory_usage: Some(Bytes(268)),
             execution_state: "".to_string(),
-            last_full_execution_round: "128".to_string(),
-            compute_allocation: "0%".to_string(),
-            exports: Exports::default(),
-            freeze_threshold: "2592000".to_string(),
-            memory_usage: "5023323".to_string(),
-            accumulated_priority: "0".to_string(),
-            cycles_balance: "3_100_000_000_000".to_string(),
+            last_full_execution_round: Some(Round(128)),
+            compute_allocation: Some(Percent(0)),
+            exports: assets_exports(),
+            freeze_threshold: Some(Seconds(Duration::from_secs(2592000))),
+            memory_usage: Some(Bytes(5023323)),
+            accumulated_priority: Some(0),
+            cycles_balance: Some(Cycles(3_100_000_000_000)),
         },
         CanisterInfo {
             canister_id: "be2us-64aaa-aaaaa-qaabq-cai".to_string(),
             status: "Running".to_string(),
-            memory_allocation: "best-effort".to_string(),
-            last_execution_round: "0".to_string(),
+            memory_allocation: Some(MemoryAllocation::BestEffort),
+            last_execution_round: Some(Round(0)),
             controllers: "trg6r-vqw4x-tcu5z-pgm4z-nmas4-ailxn-rjavv-zbzhi-jy2oy-wjrpf-hqe".to_string(),
-            certified_data_length: "0 bytes".to_string(),
-            canister_history_memory_usage: "238 bytes".to_string(),
+            certified_data_length: Some(Bytes(0)),
+            canister_history_memory_usage: Some(Bytes(238)),
             execution_state: "".to_string(),
-            exports: Exports::default(),
-            last_full_execution_round: "0".to_string(),
-            compute_allocation: "0%".to_string(),
-            freeze_threshold: "2592000".to_string(),
-            memory_usage: "3176904".to_string(),
-            accumulated_priority: "0".to_string(),
-            cycles_balance: "100_000_000_000_000".to_string(),
+            exports: candid_ui_exports(),
+            last_full_execution_round: Some(Round(0)),
+            compute_allocation: Some(Percent(0)),
+            freeze_threshold: Some(Seconds(Duration::from_secs(2592000))),
+            memory_usage: Some(Bytes(3176904)),
+            accumulated_priority: Some(0),
+            cycles_balance: Some(Cycles(100_000_000_000_000)),
         }
 
         ],
@@ -613,7 +762,9 @@ fn parse_example_html() {
 
 #[test]
 fn another() {
+    use crate::metrics::*;
     use pretty_assertions::assert_eq;
+    use std::time::Duration;
     const HTML: &str = r#"
 <!DOCTYPE html>
 <!-- saved from url=(0034)http://localhost:56668/_/dashboard -->
@@ -903,78 +1054,78 @@ fn another() {
     assert!(parsed.is_ok());
     let expected = ReplicaInfo {
         replica_version: "0.9.0".to_string(),
-        subnet_type: "System".to_string(),
+        subnet_type: "Application".to_string(),
         total_compute_allocation: "0 %".to_string(),
         http_server_config: "Config { listen_addr: 127.0.0.1:0, port_file_path: Some(\"/Users/mnl/Library/Application Support/org.dfinity.dfx/network/local/replica-configuration/replica-1.port\"), connection_read_timeout_seconds: 1200, request_timeout_seconds: 300, http_max_concurrent_streams: 256, max_request_size_bytes: 5242880, max_delegation_certificate_size_bytes: 1048576, max_request_receive_seconds: 300, max_read_state_concurrent_requests: 100, max_status_concurrent_requests: 100, max_catch_up_package_concurrent_requests: 100, max_dashboard_concurrent_requests: 100, max_call_concurrent_requests: 50, max_query_concurrent_requests: 400, max_pprof_concurrent_requests: 5 }".into(),
         canisters: vec![CanisterInfo {
             canister_id: "bnz7o-iuaaa-aaaaa-qaaaa-cai".to_string(),
             status: "Running".to_string(),
-            memory_allocation: "best-effort".to_string(),
-            last_execution_round: "0".to_string(),
+            memory_allocation: Some(MemoryAllocation::BestEffort),
+            last_execution_round: Some(Round(1736)),
             controllers: "trg6r-vqw4x-tcu5z-pgm4z-nmas4-ailxn-rjavv-zbzhi-jy2oy-wjrpf-hqe"
                 .to_string(),
-            certified_data_length: "32 bytes".to_string(),
-            canister_history_memory_usage: "238 bytes".to_string(),
+            certified_data_length: Some(Bytes(32)),
+            canister_history_memory_usage: Some(Bytes(238)),
             execution_state: "".to_string(),
-            last_full_execution_round: "104".to_string(),
-            exports: Exports::default(),
-            compute_allocation: "0%".to_string(),
-            freeze_threshold: "2592000".to_string(),
-            memory_usage: "7345934".to_string(),
-            accumulated_priority: "0".to_string(),
-            cycles_balance: "93_800_000_000_000".to_string(),
+            last_full_execution_round: Some(Round(1736)),
+            exports: wallet_exports(),
+            compute_allocation: Some(Percent(0)),
+            freeze_threshold: Some(Seconds(Duration::from_secs(2592000))),
+            memory_usage: Some(Bytes(7345934)),
+            accumulated_priority: Some(0),
+            cycles_balance: Some(Cycles(93_799_340_083_699)),
         },
 
         CanisterInfo {
             canister_id: "bkyz2-fmaaa-aaaaa-qaaaq-cai".to_string(),
             status: "Running".to_string(),
-            memory_allocation: "best-effort".to_string(),
-            last_execution_round: "0".to_string(),
-            exports: Exports::default(),
+            memory_allocation: Some(MemoryAllocation::BestEffort),
+            last_execution_round: Some(Round(0)),
+            exports: motoko_exports(),
             controllers: "bnz7o-iuaaa-aaaaa-qaaaa-cai trg6r-vqw4x-tcu5z-pgm4z-nmas4-ailxn-rjavv-zbzhi-jy2oy-wjrpf-hqe".to_string(),
-            certified_data_length: "0 bytes".to_string(),
-            canister_history_memory_usage: "268 bytes".to_string(),
+            certified_data_length: Some(Bytes(0)),
+            canister_history_memory_usage: Some(Bytes(268)),
             execution_state: "".to_string(),
-            last_full_execution_round: "0".to_string(),
-            compute_allocation: "0%".to_string(),
-            freeze_threshold: "2592000".to_string(),
-            memory_usage: "2294162".to_string(),
-            accumulated_priority: "0".to_string(),
-            cycles_balance: "3_100_000_000_000".to_string(),
+            last_full_execution_round: Some(Round(0)),
+            compute_allocation: Some(Percent(0)),
+            freeze_threshold: Some(Seconds(Duration::from_secs(2592000))),
+            memory_usage: Some(Bytes(2294283)),
+            accumulated_priority: Some(0),
+            cycles_balance: Some(Cycles(3_092_279_190_900)),
         },
         CanisterInfo {
             canister_id: "bd3sg-teaaa-aaaaa-qaaba-cai".to_string(),
             status: "Running".to_string(),
-            memory_allocation: "best-effort".to_string(),
-            last_execution_round: "0".to_string(),
+            memory_allocation: Some(MemoryAllocation::BestEffort),
+            last_execution_round: Some(Round(1761)),
             controllers: "bnz7o-iuaaa-aaaaa-qaaaa-cai trg6r-vqw4x-tcu5z-pgm4z-nmas4-ailxn-rjavv-zbzhi-jy2oy-wjrpf-hqe".to_string(),
-            certified_data_length: "32 bytes".to_string(),
-            canister_history_memory_usage: "268 bytes".to_string(),
+            certified_data_length: Some(Bytes(32)),
+            canister_history_memory_usage: Some(Bytes(268)),
             execution_state: "".to_string(),
-            exports: Exports::default(),
-            last_full_execution_round: "128".to_string(),
-            compute_allocation: "0%".to_string(),
-            freeze_threshold: "2592000".to_string(),
-            memory_usage: "5023323".to_string(),
-            accumulated_priority: "0".to_string(),
-            cycles_balance: "3_100_000_000_000".to_string(),
+            exports: assets_exports(),
+            last_full_execution_round: Some(Round(1761)),
+            compute_allocation: Some(Percent(0)),
+            freeze_threshold: Some(Seconds(Duration::from_secs(2592000))),
+            memory_usage: Some(Bytes(5416539)),
+            accumulated_priority: Some(0),
+            cycles_balance: Some(Cycles(3_091_813_676_556)),
         },
         CanisterInfo {
             canister_id: "be2us-64aaa-aaaaa-qaabq-cai".to_string(),
             status: "Running".to_string(),
-            memory_allocation: "best-effort".to_string(),
-            last_execution_round: "0".to_string(),
+            memory_allocation: Some(MemoryAllocation::BestEffort),
+            last_execution_round: Some(Round(0)),
             controllers: "trg6r-vqw4x-tcu5z-pgm4z-nmas4-ailxn-rjavv-zbzhi-jy2oy-wjrpf-hqe".to_string(),
-            certified_data_length: "0 bytes".to_string(),
-            exports: Exports::default(),
-            canister_history_memory_usage: "238 bytes".to_string(),
+            certified_data_length: Some(Bytes(0)),
+            exports: candid_ui_exports(),
+            canister_history_memory_usage: Some(Bytes(238)),
             execution_state: "".to_string(),
-            last_full_execution_round: "0".to_string(),
-            compute_allocation: "0%".to_string(),
-            freeze_threshold: "2592000".to_string(),
-            memory_usage: "3176904".to_string(),
-            accumulated_priority: "0".to_string(),
-            cycles_balance: "100_000_000_000_000".to_string(),
+            last_full_execution_round: Some(Round(0)),
+            compute_allocation: Some(Percent(0)),
+            freeze_threshold: Some(Seconds(Duration::from_secs(2592000))),
+            memory_usage: Some(Bytes(3176904)),
+            accumulated_priority: Some(0),
+            cycles_balance: Some(Cycles(99_999_624_176_398)),
         }
 
         ],
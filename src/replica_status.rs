@@ -0,0 +1,346 @@
+use crate::{
+    dfx_commands::{CanisterStatus, DfxCommands, DfxContext, DfxOutput},
+    metrics,
+    parse_replica_dashboard::{CanisterInfo, Exports, ReplicaInfo},
+};
+use anyhow::{bail, Context, Result};
+use ciborium::Value;
+use regex::Regex;
+use std::sync::LazyLock;
+
+/// What a status backend gets to work with.
+#[derive(Debug, Clone)]
+pub struct StatusRequest<'a> {
+    pub dfx: &'a DfxContext,
+    /// Base URL of the replica's HTTP endpoint, e.g. `http://localhost:4943`.
+    pub replica_url: &'a str,
+    /// Canister ids we know about on this network (from the registered
+    /// projects). Backends that can't list canisters themselves query these.
+    pub canister_ids: &'a [String],
+}
+
+/// A way of finding out what the replica and its canisters are doing.
+pub trait ReplicaStatusSource: Send {
+    fn name(&self) -> &'static str;
+    fn fetch(&self, request: &StatusRequest) -> Result<ReplicaInfo>;
+}
+
+/// Scrapes the replica's `/_/dashboard` HTML page. Richest data (exports,
+/// execution rounds), but only exists on a local replica and breaks whenever
+/// the markup changes.
+pub struct DashboardSource;
+
+impl ReplicaStatusSource for DashboardSource {
+    fn name(&self) -> &'static str {
+        "dashboard"
+    }
+
+    fn fetch(&self, request: &StatusRequest) -> Result<ReplicaInfo> {
+        let url = format!("{}/_/dashboard", request.replica_url);
+        let html = reqwest::blocking::get(&url)
+            .and_then(|r| r.error_for_status())
+            .and_then(|r| r.text())
+            .with_context(|| format!("Failed to fetch {url}"))?;
+        let info = ReplicaInfo::parse_from_html_dashboard(&html).map_err(anyhow::Error::msg)?;
+        if info.replica_version.is_empty() && info.canisters.is_empty() {
+            bail!("{url} doesn't look like a replica dashboard");
+        }
+        Ok(info)
+    }
+}
+
+/// Reads the replica version from the `/api/v2/status` CBOR endpoint and each
+/// known canister's state from the management canister's `canister_status`.
+/// Works on any network, as long as the selected identity controls the canisters.
+/// Exports come from the canister's `candid:service` metadata, so canisters that
+/// don't publish their interface show none.
+pub struct ManagementCanisterSource;
+
+impl ReplicaStatusSource for ManagementCanisterSource {
+    fn name(&self) -> &'static str {
+        "management canister"
+    }
+
+    fn fetch(&self, request: &StatusRequest) -> Result<ReplicaInfo> {
+        let status = fetch_api_status(request.replica_url)?;
+        let mut info = ReplicaInfo {
+            replica_version: status.impl_version.unwrap_or_default(),
+            ..Default::default()
+        };
+        let mut total_compute_allocation = 0;
+        for canister_id in request.canister_ids {
+            let command = DfxCommands::CanisterStatus {
+                canister_id: canister_id.clone(),
+            };
+            // canisters we don't control (or that were deleted) are skipped
+            let Ok(DfxOutput::CanisterStatus(status)) = command.run(request.dfx) else {
+                continue;
+            };
+            let mut canister = canister_info_from_status(status);
+            let metadata = DfxCommands::CanisterMetadata {
+                canister_id: canister_id.clone(),
+                name: "candid:service".to_string(),
+            };
+            if let Ok(DfxOutput::Metadata(candid)) = metadata.run(request.dfx) {
+                canister.exports = exports_from_candid(&candid);
+            }
+            total_compute_allocation += canister.compute_allocation.map_or(0, |p| p.0);
+            info.canisters.push(canister);
+        }
+        info.total_compute_allocation = format!("{total_compute_allocation} %");
+        Ok(info)
+    }
+}
+
+/// Tries each source in turn and returns the first success, so the TUI keeps
+/// working when the dashboard is disabled or its markup changes.
+pub struct FallbackSource(pub Vec<Box<dyn ReplicaStatusSource>>);
+
+impl FallbackSource {
+    /// The dashboard where it exists (local replicas), the management canister everywhere.
+    pub fn for_network(network: &str) -> Self {
+        if network == "local" {
+            FallbackSource(vec![
+                Box::new(DashboardSource),
+                Box::new(ManagementCanisterSource),
+            ])
+        } else {
+            FallbackSource(vec![Box::new(ManagementCanisterSource)])
+        }
+    }
+
+    /// Like [`ReplicaStatusSource::fetch`], but also tells which source answered.
+    pub fn fetch_with_source(
+        &self,
+        request: &StatusRequest,
+    ) -> Result<(&'static str, ReplicaInfo)> {
+        let mut errors = vec![];
+        for source in &self.0 {
+            match source.fetch(request) {
+                Ok(info) => return Ok((source.name(), info)),
+                Err(e) => errors.push(format!("{}: {e:#}", source.name())),
+            }
+        }
+        bail!("No replica status source succeeded ({})", errors.join("; "))
+    }
+}
+
+impl ReplicaStatusSource for FallbackSource {
+    fn name(&self) -> &'static str {
+        "fallback"
+    }
+
+    fn fetch(&self, request: &StatusRequest) -> Result<ReplicaInfo> {
+        self.fetch_with_source(request).map(|(_, info)| info)
+    }
+}
+
+/// Fields of the `/api/v2/status` reply we care about.
+#[derive(Debug, Default, PartialEq)]
+pub struct ApiStatus {
+    pub ic_api_version: Option<String>,
+    pub impl_version: Option<String>,
+    pub impl_hash: Option<String>,
+    pub replica_health_status: Option<String>,
+    pub certified_height: Option<u64>,
+}
+
+pub fn fetch_api_status(replica_url: &str) -> Result<ApiStatus> {
+    let url = format!("{replica_url}/api/v2/status");
+    let bytes = reqwest::blocking::get(&url)
+        .and_then(|r| r.error_for_status())
+        .and_then(|r| r.bytes())
+        .with_context(|| format!("Failed to fetch {url}"))?;
+    decode_api_status(&bytes)
+}
+
+/// The reply is a CBOR map wrapped in the self-describing tag 55799.
+fn decode_api_status(bytes: &[u8]) -> Result<ApiStatus> {
+    let mut value: Value =
+        ciborium::de::from_reader(bytes).with_context(|| "Failed to decode status CBOR")?;
+    while let Value::Tag(_, inner) = value {
+        value = *inner;
+    }
+    let Value::Map(entries) = value else {
+        bail!("Status reply is not a CBOR map");
+    };
+    let mut status = ApiStatus::default();
+    for (key, value) in entries {
+        let Some(key) = key.as_text() else { continue };
+        match (key, value) {
+            ("ic_api_version", Value::Text(v)) => status.ic_api_version = Some(v),
+            ("impl_version", Value::Text(v)) => status.impl_version = Some(v),
+            ("impl_hash", Value::Text(v)) => status.impl_hash = Some(v),
+            ("replica_health_status", Value::Text(v)) => status.replica_health_status = Some(v),
+            ("certified_height", Value::Integer(v)) => {
+                status.certified_height = u64::try_from(v).ok()
+            }
+            _ => {}
+        }
+    }
+    Ok(status)
+}
+
+fn canister_info_from_status(status: CanisterStatus) -> CanisterInfo {
+    CanisterInfo {
+        canister_id: status.canister_id,
+        status: status.status,
+        memory_allocation: metrics::parse(&status.memory_allocation),
+        controllers: status.controllers,
+        compute_allocation: metrics::parse(&status.compute_allocation),
+        freeze_threshold: metrics::parse(&status.freezing_threshold),
+        memory_usage: metrics::parse(&status.memory_size),
+        cycles_balance: metrics::parse(&status.balance),
+        ..Default::default()
+    }
+}
+
+static CANDID_SERVICE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?m)^\s*service\s*:").unwrap());
+
+/// Query and update methods of the `service` in a Candid interface. Methods
+/// typed by a named `func` reference are counted as updates.
+fn exports_from_candid(candid: &str) -> Exports {
+    let mut exports = Exports::default();
+    let Some(start) = CANDID_SERVICE.find(candid).map(|m| m.start()) else {
+        return exports;
+    };
+    // skip the init arguments, which may contain records, to the service body
+    let mut parens = 0;
+    let mut body_start = None;
+    for (i, c) in candid[start..].char_indices() {
+        match c {
+            '(' => parens += 1,
+            ')' => parens -= 1,
+            '{' if parens == 0 => {
+                body_start = Some(start + i + 1);
+                break;
+            }
+            _ => {}
+        }
+    }
+    let Some(body_start) = body_start else {
+        return exports;
+    };
+    let mut depth = 0;
+    let mut method = String::new();
+    for c in candid[body_start..].chars() {
+        match c {
+            '{' | '(' => depth += 1,
+            '}' | ')' if depth > 0 => depth -= 1,
+            '}' => break,
+            ';' if depth == 0 => {
+                add_candid_method(&mut exports, &method);
+                method.clear();
+                continue;
+            }
+            _ => {}
+        }
+        method.push(c);
+    }
+    add_candid_method(&mut exports, &method);
+    exports
+}
+
+fn add_candid_method(exports: &mut Exports, method: &str) {
+    let Some((name, signature)) = method.split_once(':') else {
+        return;
+    };
+    let name = name.trim().trim_matches('"').to_string();
+    if name.is_empty() {
+        return;
+    }
+    let annotations = signature.rsplit(')').next().unwrap_or_default();
+    if annotations.contains("query") {
+        exports.exported_query_functions.push(name);
+    } else {
+        exports.exported_update_functions.push(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_api_status() {
+        let reply = Value::Tag(
+            55799,
+            Box::new(Value::Map(vec![
+                (Value::from("ic_api_version"), Value::from("0.18.0")),
+                (Value::from("impl_version"), Value::from("0.9.0")),
+                (Value::from("replica_health_status"), Value::from("healthy")),
+                (Value::from("certified_height"), Value::from(1736u64)),
+                (Value::from("root_key"), Value::Bytes(vec![48, 129])),
+            ])),
+        );
+        let mut bytes = vec![];
+        ciborium::ser::into_writer(&reply, &mut bytes).unwrap();
+        let status = decode_api_status(&bytes).unwrap();
+        assert_eq!(status.impl_version.as_deref(), Some("0.9.0"));
+        assert_eq!(status.replica_health_status.as_deref(), Some("healthy"));
+        assert_eq!(status.certified_height, Some(1736));
+    }
+
+    #[test]
+    fn test_exports_from_candid() {
+        let candid = r#"type service_config = record { owner : principal; limits : vec nat };
+service : (opt service_config) -> {
+  greet : (text) -> (text) query;
+  "set-config" : (service_config) -> (record { ok : bool });
+  http_request : (record { url : text }) -> (record { body : blob }) query;
+  peek : () -> (nat) composite_query;
+  inc : () -> ();
+}
+"#;
+        let exports = exports_from_candid(candid);
+        assert_eq!(
+            exports.exported_query_functions,
+            vec!["greet", "http_request", "peek"]
+        );
+        assert_eq!(exports.exported_update_functions, vec!["set-config", "inc"]);
+        assert_eq!(exports_from_candid("type T = nat;"), Exports::default());
+    }
+
+    struct Failing;
+    impl ReplicaStatusSource for Failing {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+        fn fetch(&self, _: &StatusRequest) -> Result<ReplicaInfo> {
+            bail!("markup changed")
+        }
+    }
+
+    struct Fixed;
+    impl ReplicaStatusSource for Fixed {
+        fn name(&self) -> &'static str {
+            "fixed"
+        }
+        fn fetch(&self, _: &StatusRequest) -> Result<ReplicaInfo> {
+            Ok(ReplicaInfo {
+                replica_version: "0.9.0".to_string(),
+                ..Default::default()
+            })
+        }
+    }
+
+    #[test]
+    fn test_fallback_uses_next_source() {
+        let dfx = DfxContext::default();
+        let request = StatusRequest {
+            dfx: &dfx,
+            replica_url: "http://localhost:1",
+            canister_ids: &[],
+        };
+        let fallback = FallbackSource(vec![Box::new(Failing), Box::new(Fixed)]);
+        let (source, info) = fallback.fetch_with_source(&request).unwrap();
+        assert_eq!(source, "fixed");
+        assert_eq!(info.replica_version, "0.9.0");
+
+        let err = FallbackSource(vec![Box::new(Failing)])
+            .fetch(&request)
+            .unwrap_err();
+        assert!(err.to_string().contains("markup changed"));
+    }
+}
@@ -0,0 +1,621 @@
+use crate::{
+    app::log_files::FileTail,
+    dfx_commands::{DfxCommands, DfxContext},
+    logs::{LogLevel, LogLine, LogStream},
+};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    fs,
+    io::{self, BufRead, BufReader, Read},
+    path::{Path, PathBuf},
+    process::{Child, Command, ExitStatus, Stdio},
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// How long `dfx stop` gets to bring the replica down before it is killed.
+const STOP_TIMEOUT: Duration = Duration::from_secs(10);
+/// How often an adopted replica is checked for still being alive.
+const ADOPTED_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How many of the last lines of a crashed replica are kept with the crash.
+pub const CRASH_LOG_LINES: usize = 20;
+/// Consecutive crashes after which auto-restart gives up.
+pub const MAX_RESTARTS: u32 = 5;
+/// Wait before the first automatic restart; doubles with every crash in a row.
+const RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+/// A replica that stays up this long no longer counts as crashing in a row.
+const STABLE_UPTIME: Duration = Duration::from_secs(60);
+
+/// What the supervised `dfx start` process is doing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessState {
+    Running {
+        pid: u32,
+    },
+    /// The exit status is only known for processes started by dfx-top.
+    Exited(Option<ExitStatus>),
+}
+
+/// Flags of `dfx start` and where to run it, as chosen in the start dialog
+/// or saved in a start profile.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StartOptions {
+    /// `--clean`: starts from a fresh replica state.
+    pub clean: bool,
+    /// `--background`: dfx returns once the replica is up and the replica
+    /// outlives dfx-top.
+    pub background: bool,
+    /// `--host`, e.g. `127.0.0.1:8080`.
+    pub host: Option<String>,
+    /// `--artificial-delay`, in milliseconds.
+    pub artificial_delay: Option<u32>,
+    /// `--enable-bitcoin`
+    pub enable_bitcoin: bool,
+    /// `--enable-canister-http`
+    pub enable_canister_http: bool,
+    /// Project whose local network is started; `None` starts the shared
+    /// local network from the home directory, outside any project.
+    pub project_dir: Option<PathBuf>,
+}
+
+impl StartOptions {
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec!["start".to_string()];
+        let flags = [
+            (self.clean, "--clean"),
+            (self.background, "--background"),
+            (self.enable_bitcoin, "--enable-bitcoin"),
+            (self.enable_canister_http, "--enable-canister-http"),
+        ];
+        args.extend(
+            flags
+                .iter()
+                .filter(|(on, _)| *on)
+                .map(|(_, f)| f.to_string()),
+        );
+        if let Some(host) = &self.host {
+            args.extend(["--host".to_string(), host.clone()]);
+        }
+        if let Some(delay) = self.artificial_delay {
+            args.extend(["--artificial-delay".to_string(), delay.to_string()]);
+        }
+        args
+    }
+
+    /// Directory `dfx start` and `dfx stop` run in.
+    pub fn cwd(&self) -> Option<PathBuf> {
+        self.project_dir.clone().or_else(dirs::home_dir)
+    }
+}
+
+/// An unexpected exit of the supervised replica.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Crash {
+    pub at: DateTime<Local>,
+    pub pid: u32,
+    /// Only known for processes started by dfx-top.
+    pub status: Option<ExitStatus>,
+    /// The last [`CRASH_LOG_LINES`] lines the process printed.
+    pub last_lines: Vec<LogLine>,
+    /// Options to start it again with.
+    pub options: StartOptions,
+}
+
+impl Crash {
+    pub fn reason(&self) -> String {
+        match self.status {
+            Some(status) => status.to_string(),
+            None => "exit status unknown".to_string(),
+        }
+    }
+}
+
+/// Restarts a crashed replica after a backoff that doubles with every crash
+/// in a row, up to [`MAX_RESTARTS`] times.
+#[derive(Debug, Default)]
+pub struct AutoRestart {
+    pub enabled: bool,
+    /// Restarts since the replica last stayed up for a while.
+    pub restarts: u32,
+    /// When the pending restart is due.
+    pub next_at: Option<Instant>,
+}
+
+impl AutoRestart {
+    /// Schedules a restart after a crash, returning the wait, or `None` when
+    /// disabled or after too many crashes in a row.
+    pub fn schedule(&mut self) -> Option<Duration> {
+        if !self.enabled || self.restarts >= MAX_RESTARTS {
+            return None;
+        }
+        let backoff = (RESTART_BACKOFF * 2u32.pow(self.restarts)).min(MAX_RESTART_BACKOFF);
+        self.restarts += 1;
+        self.next_at = Some(Instant::now() + backoff);
+        Some(backoff)
+    }
+
+    /// Whether the pending restart is due; it is no longer pending afterwards.
+    pub fn due(&mut self) -> bool {
+        let due = self.next_at.is_some_and(|at| at <= Instant::now());
+        if due {
+            self.next_at = None;
+        }
+        due
+    }
+
+    pub fn cancel(&mut self) {
+        self.next_at = None;
+        self.restarts = 0;
+    }
+
+    /// Called while the replica runs; forgets earlier crashes once it is stable.
+    pub fn running_for(&mut self, uptime: Duration) {
+        if uptime >= STABLE_UPTIME {
+            self.restarts = 0;
+        }
+    }
+}
+
+/// A `dfx start` found running outside dfx-top.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunningReplica {
+    pub pid: u32,
+    /// The network's data directory holding the pid file.
+    pub network_dir: PathBuf,
+    /// Files the process writes its stdout and stderr to, if they are files.
+    pub log_files: Vec<(LogStream, PathBuf)>,
+}
+
+impl RunningReplica {
+    /// Looks for a live `dfx start` of the local network in the `.dfx`
+    /// directories of `project_dirs`, then in the shared network data
+    /// directory used when no project defines its own local network.
+    pub fn find(project_dirs: &[PathBuf]) -> Option<RunningReplica> {
+        project_dirs
+            .iter()
+            .map(|dir| dir.join(".dfx").join("network").join("local"))
+            .chain(shared_network_dir())
+            .find_map(|network_dir| RunningReplica::in_network_dir(&network_dir))
+    }
+
+    /// The project owning the network, unless it is the shared one.
+    pub fn project_dir(&self) -> Option<PathBuf> {
+        let dfx_dir = self.network_dir.parent()?.parent()?;
+        (dfx_dir.file_name()? == ".dfx").then(|| dfx_dir.parent().map(Path::to_path_buf))?
+    }
+
+    fn in_network_dir(network_dir: &Path) -> Option<RunningReplica> {
+        let pid = fs::read_to_string(network_dir.join("pid")).ok()?;
+        let pid: u32 = pid.trim().parse().ok()?;
+        if !is_alive(pid) {
+            return None;
+        }
+        Some(RunningReplica {
+            pid,
+            network_dir: network_dir.to_path_buf(),
+            log_files: output_files(pid),
+        })
+    }
+}
+
+/// Data directory of the shared local network, see `dfx info networks-json-path`.
+fn shared_network_dir() -> Option<PathBuf> {
+    let app_dir = if cfg!(target_os = "macos") {
+        "org.dfinity.dfx"
+    } else {
+        "dfx"
+    };
+    Some(
+        dirs::data_local_dir()?
+            .join(app_dir)
+            .join("network")
+            .join("local"),
+    )
+}
+
+/// Regular files the process's stdout and stderr are redirected to, e.g.
+/// when it runs under a process manager or as `dfx start > replica.log`.
+#[cfg(target_os = "linux")]
+fn output_files(pid: u32) -> Vec<(LogStream, PathBuf)> {
+    let mut files: Vec<(LogStream, PathBuf)> = vec![];
+    for (fd, stream) in [(1, LogStream::Stdout), (2, LogStream::Stderr)] {
+        let Ok(target) = fs::read_link(format!("/proc/{pid}/fd/{fd}")) else {
+            continue;
+        };
+        // stdout and stderr often go to the same file
+        if target.is_file() && !files.iter().any(|(_, path)| *path == target) {
+            files.push((stream, target));
+        }
+    }
+    files
+}
+
+#[cfg(not(target_os = "linux"))]
+fn output_files(_pid: u32) -> Vec<(LogStream, PathBuf)> {
+    vec![]
+}
+
+fn is_alive(pid: u32) -> bool {
+    Command::new("kill")
+        .args(["-0", &pid.to_string()])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[derive(Debug)]
+enum Process {
+    /// Started by dfx-top, output piped.
+    Child(Child),
+    /// Started elsewhere and adopted, with the last liveness check.
+    Adopted { pid: u32, checked: Instant },
+}
+
+/// Owns a `dfx start` process, started by dfx-top or adopted, and captures
+/// whatever it prints.
+#[derive(Debug)]
+pub struct ReplicaSupervisor {
+    process: Process,
+    receiver: Receiver<LogLine>,
+    /// How the replica was started; only the project is known when adopted.
+    pub options: StartOptions,
+    started: Instant,
+    /// The last [`CRASH_LOG_LINES`] lines, for the crash report.
+    recent: VecDeque<LogLine>,
+}
+
+impl ReplicaSupervisor {
+    /// Runs `dfx start` with `options` with both output streams piped, so
+    /// nothing leaks into the terminal the dashboard is drawn on.
+    pub fn start(ctx: &DfxContext, options: StartOptions) -> io::Result<Self> {
+        let mut dfx = Command::new(&ctx.path_to_dfx);
+        if let Some(cwd) = options.cwd() {
+            dfx.current_dir(cwd);
+        }
+        let args = options.args();
+        let mut child = dfx
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        let (sender, receiver) = mpsc::channel();
+        let _ = sender.send(LogLine::dfx_top(
+            LogLevel::Info,
+            format!(
+                "[dfx-top] started dfx {} (pid {})",
+                args.join(" "),
+                child.id()
+            ),
+        ));
+        forward(
+            child.stdout.take().map(|p| Box::new(p) as _),
+            LogStream::Stdout,
+            &sender,
+        );
+        forward(
+            child.stderr.take().map(|p| Box::new(p) as _),
+            LogStream::Stderr,
+            &sender,
+        );
+        Ok(ReplicaSupervisor {
+            process: Process::Child(child),
+            receiver,
+            options,
+            started: Instant::now(),
+            recent: VecDeque::new(),
+        })
+    }
+
+    /// Takes over a replica started outside dfx-top, following the files
+    /// its output goes to.
+    pub fn adopt(running: &RunningReplica) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let output = if running.log_files.is_empty() {
+            "its output is not written to a file dfx-top can follow".to_string()
+        } else {
+            let files: Vec<_> = running
+                .log_files
+                .iter()
+                .map(|(_, path)| path.display().to_string())
+                .collect();
+            format!("following {}", files.join(", "))
+        };
+        let _ = sender.send(LogLine::dfx_top(
+            LogLevel::Info,
+            format!(
+                "[dfx-top] attached to dfx start (pid {}) of {}, {output}",
+                running.pid,
+                running.network_dir.display()
+            ),
+        ));
+        for (stream, path) in &running.log_files {
+            follow(FileTail::new(path.clone()), *stream, &sender);
+        }
+        ReplicaSupervisor {
+            process: Process::Adopted {
+                pid: running.pid,
+                checked: Instant::now(),
+            },
+            receiver,
+            options: StartOptions {
+                project_dir: running.project_dir(),
+                ..Default::default()
+            },
+            started: Instant::now(),
+            recent: VecDeque::new(),
+        }
+    }
+
+    /// Keeps supervising the replica `dfx start --background` left running
+    /// as `pid`, as if it had been adopted.
+    pub fn detach(&mut self, pid: u32) {
+        self.process = Process::Adopted {
+            pid,
+            checked: Instant::now(),
+        };
+    }
+
+    pub fn pid(&self) -> u32 {
+        match &self.process {
+            Process::Child(child) => child.id(),
+            Process::Adopted { pid, .. } => *pid,
+        }
+    }
+
+    /// Whether the replica was started outside dfx-top.
+    pub fn is_adopted(&self) -> bool {
+        matches!(self.process, Process::Adopted { .. })
+    }
+
+    /// Checks the process without blocking.
+    pub fn state(&mut self) -> io::Result<ProcessState> {
+        let pid = self.pid();
+        match &mut self.process {
+            Process::Child(child) => Ok(match child.try_wait()? {
+                Some(status) => ProcessState::Exited(Some(status)),
+                None => ProcessState::Running { pid },
+            }),
+            Process::Adopted { checked, .. } if checked.elapsed() < ADOPTED_CHECK_INTERVAL => {
+                Ok(ProcessState::Running { pid })
+            }
+            Process::Adopted { checked, .. } => {
+                *checked = Instant::now();
+                Ok(if is_alive(pid) {
+                    ProcessState::Running { pid }
+                } else {
+                    ProcessState::Exited(None)
+                })
+            }
+        }
+    }
+
+    /// Lines captured since the last call. Never blocks.
+    pub fn lines(&mut self) -> Vec<LogLine> {
+        let lines: Vec<LogLine> = self.receiver.try_iter().collect();
+        for line in &lines {
+            if self.recent.len() == CRASH_LOG_LINES {
+                self.recent.pop_front();
+            }
+            self.recent.push_back(line.clone());
+        }
+        lines
+    }
+
+    /// How long ago the process was started or adopted.
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Describes the process having exited with `status` on its own.
+    pub fn crash(&self, status: Option<ExitStatus>) -> Crash {
+        Crash {
+            at: Local::now(),
+            pid: self.pid(),
+            status,
+            last_lines: self.recent.iter().cloned().collect(),
+            options: self.options.clone(),
+        }
+    }
+
+    /// Asks dfx to stop the replica and waits for `dfx start` to exit,
+    /// killing it if it takes longer than [`STOP_TIMEOUT`]. `dfx stop` runs in
+    /// the directory the replica was started in, whatever `ctx.cwd` says.
+    /// Returns the lines printed while shutting down.
+    pub fn stop(mut self, ctx: &DfxContext) -> Vec<LogLine> {
+        let mut lines = self.lines();
+        let ctx = DfxContext {
+            cwd: self.options.cwd(),
+            ..ctx.clone()
+        };
+        if let Err(e) = DfxCommands::StopReplica.run(&ctx) {
+            lines.push(LogLine::dfx_top(LogLevel::Warn, format!("[dfx-top] {e}")));
+        }
+        let deadline = Instant::now() + STOP_TIMEOUT;
+        let status = loop {
+            match self.exit_status() {
+                Ok(Some(status)) => break Ok(status),
+                Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(50)),
+                Ok(None) | Err(_) => {
+                    lines.push(LogLine::dfx_top(
+                        LogLevel::Warn,
+                        format!(
+                            "[dfx-top] dfx start (pid {}) did not exit, killing it",
+                            self.pid()
+                        ),
+                    ));
+                    break self.kill();
+                }
+            }
+        };
+        lines.extend(self.lines());
+        lines.push(match status {
+            Ok(Some(status)) => LogLine::dfx_top(
+                LogLevel::Info,
+                format!("[dfx-top] dfx start exited with {status}"),
+            ),
+            Ok(None) => LogLine::dfx_top(LogLevel::Info, "[dfx-top] dfx start exited".to_string()),
+            Err(e) => LogLine::dfx_top(LogLevel::Error, format!("[dfx-top] dfx start: {e}")),
+        });
+        lines
+    }
+
+    /// `Some` once the process has exited, with its status if it is known.
+    fn exit_status(&mut self) -> io::Result<Option<Option<ExitStatus>>> {
+        match &mut self.process {
+            Process::Child(child) => Ok(child.try_wait()?.map(Some)),
+            Process::Adopted { pid, .. } => Ok((!is_alive(*pid)).then_some(None)),
+        }
+    }
+
+    fn kill(&mut self) -> io::Result<Option<ExitStatus>> {
+        match &mut self.process {
+            Process::Child(child) => {
+                let _ = child.kill();
+                child.wait().map(Some)
+            }
+            Process::Adopted { pid, .. } => {
+                Command::new("kill")
+                    .args(["-9", &pid.to_string()])
+                    .stderr(Stdio::null())
+                    .status()?;
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// Sends every line of `pipe` as a [`LogLine`] of `stream` until the pipe closes.
+fn forward(pipe: Option<Box<dyn Read + Send>>, stream: LogStream, sender: &Sender<LogLine>) {
+    let Some(pipe) = pipe else {
+        return;
+    };
+    let sender = sender.clone();
+    thread::spawn(move || {
+        for line in BufReader::new(pipe).lines().map_while(Result::ok) {
+            if sender.send(LogLine::new(stream, line)).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Sends the lines appended to a file as [`LogLine`]s of `stream` until the
+/// supervisor is dropped.
+fn follow(mut tail: FileTail, stream: LogStream, sender: &Sender<LogLine>) {
+    let sender = sender.clone();
+    thread::spawn(move || loop {
+        for line in tail.read_lines().unwrap_or_default() {
+            if sender.send(LogLine::new(stream, line)).is_err() {
+                return;
+            }
+        }
+        thread::sleep(Duration::from_millis(250));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_captures_both_streams_and_exit() {
+        // `sh start` runs the script below in place of `dfx start`
+        let dir = TempDir::new("supervisor");
+        fs::write(
+            dir.join("start"),
+            "echo \"Running dfx start $*\"\necho 'ERROR: port in use' >&2\nexit 3\n",
+        )
+        .unwrap();
+        let ctx = DfxContext {
+            path_to_dfx: "sh".to_string(),
+            ..Default::default()
+        };
+        let options = StartOptions {
+            clean: true,
+            host: Some("127.0.0.1:8080".to_string()),
+            project_dir: Some(dir.to_path_buf()),
+            ..Default::default()
+        };
+        let mut supervisor = ReplicaSupervisor::start(&ctx, options).unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let mut lines = vec![];
+        let status = loop {
+            lines.extend(supervisor.lines());
+            match supervisor.state().unwrap() {
+                ProcessState::Exited(status) if lines.len() >= 3 => break status.unwrap(),
+                _ if Instant::now() > deadline => panic!("no exit, got {lines:?}"),
+                _ => thread::sleep(Duration::from_millis(10)),
+            }
+        };
+
+        assert_eq!(status.code(), Some(3));
+        assert_eq!(lines[0].stream, LogStream::DfxTop);
+        let stdout = lines
+            .iter()
+            .find(|l| l.stream == LogStream::Stdout)
+            .unwrap();
+        assert_eq!(
+            stdout.text,
+            "Running dfx start --clean --host 127.0.0.1:8080"
+        );
+        assert_eq!(stdout.level, None);
+        let stderr = lines
+            .iter()
+            .find(|l| l.stream == LogStream::Stderr)
+            .unwrap();
+        assert_eq!(stderr.level, Some(LogLevel::Error));
+
+        let crash = supervisor.crash(Some(status));
+        assert_eq!(crash.last_lines, lines);
+        assert_eq!(crash.reason(), "exit status: 3");
+        assert!(crash.options.clean);
+    }
+
+    #[test]
+    fn test_auto_restart_backs_off_and_gives_up() {
+        let mut restart = AutoRestart::default();
+        assert_eq!(restart.schedule(), None);
+        restart.enabled = true;
+        let backoffs: Vec<_> = std::iter::from_fn(|| restart.schedule()).collect();
+        assert_eq!(backoffs.len(), MAX_RESTARTS as usize);
+        assert_eq!(backoffs[0], RESTART_BACKOFF);
+        assert_eq!(backoffs[2], RESTART_BACKOFF * 4);
+        assert!(!restart.due());
+
+        restart.running_for(STABLE_UPTIME);
+        assert_eq!(restart.schedule(), Some(RESTART_BACKOFF));
+    }
+
+    #[test]
+    fn test_finds_running_replica_by_pid_file() {
+        let dir = TempDir::new("running");
+        let project = dir.to_path_buf();
+        let network_dir = project.join(".dfx").join("network").join("local");
+        fs::create_dir_all(&network_dir).unwrap();
+        let in_project = |project: &PathBuf| {
+            RunningReplica::find(std::slice::from_ref(project))
+                .filter(|running| running.network_dir == network_dir)
+        };
+        assert_eq!(in_project(&project), None);
+
+        // this test process stands in for a live dfx start
+        fs::write(network_dir.join("pid"), std::process::id().to_string()).unwrap();
+        let running = in_project(&project).unwrap();
+        assert_eq!(running.pid, std::process::id());
+        assert_eq!(running.project_dir(), Some(project.clone()));
+        let mut supervisor = ReplicaSupervisor::adopt(&running);
+        assert!(supervisor.is_adopted());
+        assert!(supervisor.lines()[0].text.contains("attached"));
+    }
+}